@@ -0,0 +1,470 @@
+use std::{
+    collections::VecDeque,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Condvar, Mutex,
+    },
+    thread::JoinHandle,
+};
+
+use chrono::{DateTime, FixedOffset};
+
+use crate::{Level, Logger, LoggerError};
+
+type ErrorHandler = Box<dyn Fn(&LoggerError) + Send>;
+
+/// What an `AsyncLogger` does with a new entry when its queue is full
+/// # Example
+/// Holding up the writer thread with a slow error handler shows what each policy keeps
+/// ```rust
+/// use d_logger::{Logger, OverflowPolicy};
+/// use std::sync::{mpsc, Mutex};
+/// 
+/// for policy in [OverflowPolicy::DropNewest, OverflowPolicy::DropOldest] {
+///     let path = std::env::temp_dir().join(format!("d_logger_overflow_{policy:?}"));
+/// #   let _ = std::fs::remove_file(&path);
+///     let logger = Logger::builder().path(&path).build().unwrap();
+///     let file = path.join(chrono::Local::now().format("log_%Y%m%d.log").to_string());
+/// 
+///     // The first write fails with a file in the way of the directory, the handler then keeps the writer thread busy
+///     // until it's released
+///     let (entered_tx, entered) = mpsc::channel();
+///     let (release, release_rx) = mpsc::channel::<()>();
+///     let release_rx = Mutex::new(release_rx);
+///     let mut logger = logger.into_background_with_handler(4, move |_| {
+///         entered_tx.send(()).unwrap();
+///         let _ = release_rx.lock().unwrap().recv();
+///     });
+///     logger.set_overflow_policy(policy);
+///     std::fs::remove_dir_all(&path).unwrap();
+///     std::fs::write(&path, "").unwrap();
+///     logger.write_log("blocker");
+///     entered.recv().unwrap();
+///     std::fs::remove_file(&path).unwrap();
+///     std::fs::create_dir_all(&path).unwrap();
+/// 
+///     for i in 1..=10 {
+///         assert!(logger.write_log(&format!("line {i}")));
+///     }
+///     assert_eq!(logger.dropped_count(), 6);
+///     release.send(()).unwrap();
+///     logger.join();
+/// 
+///     let contents = std::fs::read_to_string(&file).unwrap();
+///     let lines: Vec<&str> = contents.lines().collect();
+///     let kept = match policy {
+///         OverflowPolicy::DropNewest => 1..=4,
+///         _ => 7..=10,
+///     };
+///     assert_eq!(lines.len(), 5);
+///     for (line, i) in lines.iter().zip(kept) {
+///         assert!(line.ends_with(&format!(" line {i}")));
+///     }
+///     assert!(lines[4].ends_with(" Warning = 6 log entries dropped due to backpressure"));
+/// }
+/// ```
+/// With the default `Block` the writer waits for room instead
+/// ```rust
+/// use d_logger::Logger;
+/// use std::sync::{mpsc, Mutex};
+/// 
+/// let path = std::env::temp_dir().join("d_logger_overflow_block");
+/// # let _ = std::fs::remove_file(&path);
+/// let logger = Logger::builder().path(&path).build().unwrap();
+/// let file = path.join(chrono::Local::now().format("log_%Y%m%d.log").to_string());
+/// 
+/// let (entered_tx, entered) = mpsc::channel();
+/// let (release, release_rx) = mpsc::channel::<()>();
+/// let release_rx = Mutex::new(release_rx);
+/// let mut logger = logger.into_background_with_handler(4, move |_| {
+///     entered_tx.send(()).unwrap();
+///     let _ = release_rx.lock().unwrap().recv();
+/// });
+/// std::fs::remove_dir_all(&path).unwrap();
+/// std::fs::write(&path, "").unwrap();
+/// logger.write_log("blocker");
+/// entered.recv().unwrap();
+/// std::fs::remove_file(&path).unwrap();
+/// std::fs::create_dir_all(&path).unwrap();
+/// 
+/// std::thread::scope(|s| {
+///     let writer = s.spawn(|| {
+///         for i in 1..=5 {
+///             logger.write_log(&format!("line {i}"));
+///         }
+///     });
+///     std::thread::sleep(std::time::Duration::from_millis(200));
+///     // The fifth line is waiting for room in the queue
+///     assert!(!writer.is_finished());
+///     release.send(()).unwrap();
+/// });
+/// logger.join();
+/// 
+/// assert_eq!(logger.dropped_count(), 0);
+/// let contents = std::fs::read_to_string(&file).unwrap();
+/// assert_eq!(contents.lines().count(), 5);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OverflowPolicy {
+    /// Wait for the writer thread to make room, nothing is lost
+    #[default]
+    Block,
+    /// Throw away the new entry
+    DropNewest,
+    /// Throw away the oldest queued entry to make room for the new one
+    DropOldest,
+}
+
+// An entry as the caller gave it, with the time it was given
+struct Queued {
+    time: DateTime<FixedOffset>,
+    level: Option<Level>,
+    line: String,
+    kvs: Vec<(String, String)>,
+}
+
+// Entries waiting for the writer thread
+struct Queue {
+    state: Mutex<QueueState>,
+    not_empty: Condvar,
+    not_full: Condvar,
+    capacity: usize,
+    dropped: AtomicU64,
+}
+
+struct QueueState {
+    entries: VecDeque<Queued>,
+    policy: OverflowPolicy,
+    closed: bool,
+    // Dropped since the last summary line was written
+    unreported: u64,
+}
+
+impl Queue {
+    fn new(capacity: usize) -> Queue {
+        Queue {
+            state: Mutex::new(QueueState { entries: VecDeque::with_capacity(capacity), policy: OverflowPolicy::Block, closed: false, unreported: 0 }),
+            not_empty: Condvar::new(),
+            not_full: Condvar::new(),
+            capacity,
+            dropped: AtomicU64::new(0),
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, QueueState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn push(&self, entry: Queued) -> Result<(), LoggerError> {
+        let mut state = self.lock();
+        // The policy is looked at again after each wait, it may have been changed while this writer was blocked
+        loop {
+            if state.closed {
+                return Err(LoggerError::WriterStopped);
+            }
+            if state.entries.len() < self.capacity {
+                break;
+            }
+            match state.policy {
+                OverflowPolicy::Block => state = self.not_full.wait(state).unwrap_or_else(|e| e.into_inner()),
+                OverflowPolicy::DropNewest => {
+                    self.dropped.fetch_add(1, Ordering::Relaxed);
+                    state.unreported += 1;
+                    return Ok(());
+                }
+                OverflowPolicy::DropOldest => {
+                    state.entries.pop_front();
+                    self.dropped.fetch_add(1, Ordering::Relaxed);
+                    state.unreported += 1;
+                    break;
+                }
+            }
+        }
+        state.entries.push_back(entry);
+        self.not_empty.notify_one();
+        Ok(())
+    }
+
+    // Wait for the next entry, None once closed and empty.
+    // Also gives the number of dropped entries to report once the queue has emptied
+    fn pop(&self) -> Option<(Queued, u64)> {
+        let mut state = self.lock();
+        loop {
+            if let Some(entry) = state.entries.pop_front() {
+                self.not_full.notify_one();
+                let dropped = if state.entries.is_empty() { std::mem::take(&mut state.unreported) } else { 0 };
+                return Some((entry, dropped));
+            }
+            if state.closed {
+                return None;
+            }
+            state = self.not_empty.wait(state).unwrap_or_else(|e| e.into_inner());
+        }
+    }
+
+    fn close(&self) {
+        self.lock().closed = true;
+        self.not_empty.notify_all();
+        self.not_full.notify_all();
+    }
+}
+
+/// A `Logger` running on its own writer thread. Entries are timestamped by the caller, then queued on a bounded queue
+/// so the calling thread never waits on the file unless the queue is full. The writer thread does everything else:
+/// the low space, duplicate and rate limit checks, formatting, and writing to the files and any sinks.
+/// Made with `Logger::into_background`. Dropping it, `join` or `shutdown` write everything still queued.
+/// When the queue is full writers wait by default, see `OverflowPolicy` and `set_overflow_policy` to drop entries instead.
+/// # Example
+/// ```rust
+/// use d_logger::Logger;
+/// 
+/// let path = std::env::temp_dir().join("d_logger_background");
+/// let logger = Logger::builder().path(&path).file_name_format("background_%Y%m%d.log").build().unwrap();
+/// let file = path.join(chrono::Local::now().format("background_%Y%m%d.log").to_string());
+/// # let _ = std::fs::remove_file(&file);
+/// let mut logger = logger.into_background(1024);
+/// 
+/// std::thread::scope(|s| {
+///     for t in 0..8 {
+///         let logger = &logger;
+///         s.spawn(move || {
+///             for i in 0..500 {
+///                 assert!(logger.write_log(&format!("thread {t} line {i}")));
+///             }
+///         });
+///     }
+/// });
+/// logger.join();
+/// assert_eq!(logger.errors(), 0);
+/// 
+/// let contents = std::fs::read_to_string(&file).unwrap();
+/// assert_eq!(contents.lines().count(), 8 * 500);
+/// ```
+/// The rate limit applies as it does to the `Logger`, and sinks are written from the writer thread
+/// ```rust
+/// use d_logger::{Logger, Sink};
+/// use std::sync::{Arc, Mutex};
+/// use std::time::Duration;
+/// 
+/// struct Threads(Arc<Mutex<Vec<String>>>);
+/// impl Sink for Threads {
+///     fn write_entry(&self, _formatted: &str) -> std::io::Result<()> {
+///         self.0.lock().unwrap().push(std::thread::current().name().unwrap_or_default().to_string());
+///         Ok(())
+///     }
+/// }
+/// 
+/// let path = std::env::temp_dir().join("d_logger_background_filters");
+/// # let _ = std::fs::remove_dir_all(&path);
+/// let logger = Logger::builder().path(&path).file_name_format("filters.log").rate_limit(2, Duration::from_secs(60)).build().unwrap();
+/// let threads = Arc::new(Mutex::new(Vec::new()));
+/// logger.add_sink(Box::new(Threads(threads.clone())));
+/// let mut logger = logger.into_background(16);
+/// for _ in 0..5 {
+///     assert!(logger.write_log("same again"));
+/// }
+/// logger.join();
+/// 
+/// assert_eq!(std::fs::read_to_string(path.join("filters.log")).unwrap().lines().count(), 2);
+/// assert_eq!(*threads.lock().unwrap(), ["d_logger-writer", "d_logger-writer"]);
+/// ```
+pub struct AsyncLogger {
+    // Only taken by shutdown
+    logger: Option<Logger>,
+    queue: Arc<Queue>,
+    writer: Option<JoinHandle<()>>,
+    errors: Arc<AtomicU64>,
+}
+
+impl AsyncLogger {
+    pub(crate) fn spawn(logger: Logger, capacity: usize, on_error: Option<ErrorHandler>) -> AsyncLogger {
+        let errors = Arc::new(AtomicU64::new(0));
+        // A queue needs room for at least one entry to make progress
+        let queue = Arc::new(Queue::new(capacity.max(1)));
+
+        let writer_logger = logger.clone();
+        let writer_errors = Arc::clone(&errors);
+        let writer_queue = Arc::clone(&queue);
+        let writer = std::thread::Builder::new()
+            .name("d_logger-writer".to_string())
+            .spawn(move || {
+                let report = |result: Result<(), LoggerError>| {
+                    if let Err(e) = result {
+                        writer_errors.fetch_add(1, Ordering::Relaxed);
+                        if let Some(on_error) = &on_error {
+                            on_error(&e);
+                        }
+                    }
+                };
+                // Runs until the queue is closed and empty
+                while let Some((entry, dropped)) = writer_queue.pop() {
+                    let kvs: Vec<(&str, &str)> = entry.kvs.iter().map(|(key, value)| (key.as_str(), value.as_str())).collect();
+                    report(writer_logger.inner.write_keyed_at(&entry.time, &entry.line, entry.level, &entry.line, &kvs));
+                    if dropped > 0 {
+                        writer_logger.inner.stats.entries_dropped.fetch_add(dropped, Ordering::Relaxed);
+                        report(writer_logger.try_write_log(&format!("Warning = {dropped} log entries dropped due to backpressure")));
+                    }
+                }
+            })
+            .expect("failed to spawn the d_logger writer thread");
+
+        AsyncLogger { logger: Some(logger), queue, writer: Some(writer), errors }
+    }
+
+    /// Queue a line for the log
+    /// # Arguments
+    /// * `line` - The line to write to the log
+    /// # Returns
+    /// * `true` if the line was queued, or dropped by the overflow policy
+    /// * `false` if the writer thread has stopped
+    pub fn write_log(&self, line: &str) -> bool {
+        self.try_write_log(line).is_ok()
+    }
+
+    /// Queue a line for the log, returning the reason if it fails.
+    /// Errors from formatting and writing the entry happen later on the writer thread, see `errors`
+    /// # Arguments
+    /// * `line` - The line to write to the log
+    pub fn try_write_log(&self, line: &str) -> Result<(), LoggerError> {
+        self.send(None, line, &[])
+    }
+
+    /// Queue a line at the given level, lines below the logger's minimum level are dropped
+    /// # Arguments
+    /// * `level` - The severity of the line
+    /// * `line` - The line to write to the log
+    pub fn write_level(&self, level: Level, line: &str) -> bool {
+        if level < self.logger().level() {
+            return true;
+        }
+        self.send(Some(level), line, &[]).is_ok()
+    }
+
+    /// Queue a line followed by key=value pairs, see `Logger::write_log_kv`
+    /// # Arguments
+    /// * `msg` - The message to write to the log
+    /// * `kvs` - The key value pairs to append after the message
+    pub fn write_log_kv(&self, msg: &str, kvs: &[(&str, &str)]) -> bool {
+        self.send(None, msg, kvs).is_ok()
+    }
+
+    /// Shorthand for `write_level(Level::Trace, line)`
+    pub fn trace(&self, line: &str) -> bool {
+        self.write_level(Level::Trace, line)
+    }
+
+    /// Shorthand for `write_level(Level::Debug, line)`
+    pub fn debug(&self, line: &str) -> bool {
+        self.write_level(Level::Debug, line)
+    }
+
+    /// Shorthand for `write_level(Level::Info, line)`
+    pub fn info(&self, line: &str) -> bool {
+        self.write_level(Level::Info, line)
+    }
+
+    /// Shorthand for `write_level(Level::Warn, line)`
+    pub fn warn(&self, line: &str) -> bool {
+        self.write_level(Level::Warn, line)
+    }
+
+    /// Shorthand for `write_level(Level::Error, line)`
+    pub fn error(&self, line: &str) -> bool {
+        self.write_level(Level::Error, line)
+    }
+
+    /// How many queued entries the writer thread has failed to write so far
+    pub fn errors(&self) -> u64 {
+        self.errors.load(Ordering::Relaxed)
+    }
+
+    /// How many entries have been thrown away because the queue was full
+    pub fn dropped_count(&self) -> u64 {
+        self.queue.dropped.load(Ordering::Relaxed)
+    }
+
+    /// Change what happens to new entries when the queue is full, see `OverflowPolicy`.
+    /// Writers already waiting for room under `Block` follow the new policy straight away
+    /// # Example
+    /// ```rust
+    /// use d_logger::{Logger, OverflowPolicy};
+    /// use std::sync::{mpsc, Mutex};
+    /// 
+    /// let path = std::env::temp_dir().join("d_logger_overflow_switch");
+    /// # let _ = std::fs::remove_file(&path);
+    /// let logger = Logger::builder().path(&path).file_name_format("switch.log").build().unwrap();
+    /// let (entered_tx, entered) = mpsc::channel();
+    /// let (release, release_rx) = mpsc::channel::<()>();
+    /// let release_rx = Mutex::new(release_rx);
+    /// let mut logger = logger.into_background_with_handler(2, move |_| {
+    ///     entered_tx.send(()).unwrap();
+    ///     let _ = release_rx.lock().unwrap().recv();
+    /// });
+    /// 
+    /// // A failed write holds up the writer thread in the handler while the queue fills
+    /// std::fs::remove_dir_all(&path).unwrap();
+    /// std::fs::write(&path, "").unwrap();
+    /// logger.write_log("blocker");
+    /// entered.recv().unwrap();
+    /// std::fs::remove_file(&path).unwrap();
+    /// std::fs::create_dir_all(&path).unwrap();
+    /// 
+    /// std::thread::scope(|s| {
+    ///     let writer = s.spawn(|| {
+    ///         for i in 1..=3 {
+    ///             logger.write_log(&format!("line {i}"));
+    ///         }
+    ///     });
+    ///     std::thread::sleep(std::time::Duration::from_millis(200));
+    ///     assert!(!writer.is_finished());
+    ///     // The waiting third line is dropped rather than waiting on
+    ///     logger.set_overflow_policy(OverflowPolicy::DropNewest);
+    ///     writer.join().unwrap();
+    /// });
+    /// assert_eq!(logger.dropped_count(), 1);
+    /// release.send(()).unwrap();
+    /// logger.join();
+    /// let contents = std::fs::read_to_string(path.join("switch.log")).unwrap();
+    /// assert!(contents.contains(" line 2") && !contents.contains(" line 3"));
+    /// ```
+    pub fn set_overflow_policy(&self, policy: OverflowPolicy) {
+        self.queue.lock().policy = policy;
+        // Writers waiting for room look at the policy again and may drop instead
+        self.queue.not_full.notify_all();
+    }
+
+    /// The logger the writer thread writes through, e.g. to change the level
+    pub fn logger(&self) -> &Logger {
+        self.logger.as_ref().expect("the logger is only taken by shutdown")
+    }
+
+    /// Stop taking entries and wait for the writer thread to write everything still queued.
+    /// After this `errors` is final and writes return `LoggerError::WriterStopped`
+    pub fn join(&mut self) {
+        self.queue.close();
+        if let Some(writer) = self.writer.take() {
+            let _ = writer.join();
+        }
+    }
+
+    /// Join the writer thread and give the logger back, to carry on writing from the calling thread
+    pub fn shutdown(mut self) -> Logger {
+        self.join();
+        self.logger.take().expect("the logger is only taken by shutdown")
+    }
+
+    fn send(&self, level: Option<Level>, line: &str, kvs: &[(&str, &str)]) -> Result<(), LoggerError> {
+        if self.logger().is_disabled() {
+            return Ok(());
+        }
+        let time = self.logger().inner.now();
+        let kvs = kvs.iter().map(|(key, value)| (key.to_string(), value.to_string())).collect();
+        self.queue.push(Queued { time, level, line: line.to_string(), kvs })
+    }
+}
+
+impl Drop for AsyncLogger {
+    fn drop(&mut self) {
+        self.join();
+    }
+}
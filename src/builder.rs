@@ -0,0 +1,1829 @@
+use std::{env, fmt, path::{Path, PathBuf}, sync::Arc, time::Duration};
+use crate::{low_space::FreeSpaceProvider, CleanStrategy, Clock, ConsoleMode, Level, LineEnding, LineFormatter, Logger, LoggerError, LowSpacePolicy, OutputFormat, SubsecPrecision, SyncPolicy, TimeZoneMode, test_support::ConsoleCapture};
+
+const DEFAULT_AUTO_CLEAN_INTERVAL: Duration = Duration::from_secs(24 * 60 * 60);
+const DEFAULT_DUPLICATE_TIMEOUT: Duration = Duration::from_secs(30);
+const DEFAULT_LOW_SPACE_CHECK_INTERVAL: Duration = Duration::from_secs(10);
+const DEFAULT_FALLBACK_RETRY_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Builder for a `Logger`, so call sites say which setting is which.
+/// Anything not set uses the default:
+/// * `path` - the current directory
+/// * `expand_path` - false, the path is used as given
+/// * `long_paths` - false, paths past MAX_PATH on Windows are used as they are
+/// * `file_name_format` - `log_%Y%m%d.log`
+/// * `line_date_format` - `%Y-%m-%d %H:%M:%S`
+/// * `days_keep` - None, log clean is disabled
+/// * `separator` - a single space between the timestamp and the message
+/// * `level` - Trace, every level is written
+/// * `console` - Off, entries only go to the file
+/// * `output_format` - Plain text lines
+/// * `csv_header` - false, CSV files start straight with the first record
+/// * `file_header` - None, files start with their first entry
+/// * `line_ending` - Native, \r\n on Windows and \n elsewhere
+/// * `time_zone` - Local time
+/// * `subsec_precision` - None, timestamps are as the line date format gives them
+/// * `sequence_numbers` - false
+/// * `include_hostname`, `include_pid`, `include_thread_id`, `include_thread_name` - false
+/// * `formatter` - None, the output format is used
+/// * `sync_policy` - every line is flushed and synced
+/// * `sync_directory` - false, the directory isn't synced when a file is created
+/// * `buffer_capacity` - None, entries are written straight to the file
+/// * `use_file_lock` - false, no lock is taken around writes
+/// * `file_mode` - None, new files get the usual permissions less the umask
+/// * `dir_mode` - None, new directories get the usual permissions less the umask
+/// * `shutdown_marker` - None, nothing is written when the logger closes
+/// * `startup_banner` and `shutdown_banner` - None, no banner lines are written
+/// * `max_file_size` - None, files only change with the date
+/// * `rotation_count` - 5 numbered files are kept when rotating by size
+/// * `max_files` - None, no limit on the number of files
+/// * `max_total_size` - None, no limit on the size of the log directory
+/// * `compress` - false, finished files are left as they are
+/// * `derive_clean_filter` - false, `log_clean(None)` looks at every file
+/// * `clean_strategy` - files are aged by their modified time
+/// * `clean_subdirectories` - 0, `log_clean` only looks at files directly in the log directory
+/// * `auto_clean` - off, `log_clean` is only run when called
+/// * `latest_link` - None, no link to the current file is kept
+/// * `route_level` - no routes, every level goes to the main file
+/// * `rate_limit` - None, every entry is written
+/// * `retry` - None, a failed write is not tried again
+/// * `collapse_duplicates` - off, repeated messages are all written
+/// * `sanitize` - true, line breaks and control characters in messages are escaped
+/// * `max_line_len` - None, messages are written whatever their length
+/// * `min_free_space` - None, free space isn't watched. `low_space_policy` is Degrade, checked every 10 seconds
+/// * `fallback_path` - None, writes fail while the log directory can't be written. The main one is retried every 30 seconds
+/// * `clock` - None, the time comes from `SystemClock`
+/// * `console_capture` - None, echoes go to the real standard output and error
+/// * `file_follows_timestamp` - false, entries written with their own timestamp go in the file for now
+///
+/// Everything is validated when `build` is called.
+/// # Example
+/// ```rust
+/// use d_logger::LoggerBuilder;
+/// 
+/// let path = std::env::temp_dir().join("d_logger_builder");
+/// let logger = LoggerBuilder::new()
+///     .path(path)
+///     .file_name_format("Log%d%m%y.log")
+///     .line_date_format("%Y-%m-%d %H:%M:%S")
+///     .separator(" - ")
+///     .days_keep(Some(7))
+///     .build()
+///     .unwrap();
+/// logger.write_log("This is a test log entry");
+/// ```
+#[derive(Clone)]
+pub struct LoggerBuilder {
+    pub(crate) path: PathBuf,
+    pub(crate) file_name_format: String,
+    pub(crate) line_date_format: String,
+    pub(crate) days_keep: Option<u64>,
+    pub(crate) separator: String,
+    pub(crate) level: Level,
+    pub(crate) console: ConsoleMode,
+    pub(crate) output_format: OutputFormat,
+    pub(crate) csv_header: bool,
+    pub(crate) file_header: Option<Arc<dyn Fn() -> String + Send + Sync>>,
+    pub(crate) line_ending: LineEnding,
+    pub(crate) time_zone: TimeZoneMode,
+    pub(crate) subsec_precision: Option<SubsecPrecision>,
+    pub(crate) sequence_numbers: bool,
+    pub(crate) include_hostname: bool,
+    pub(crate) hostname_provider: Option<Arc<dyn Fn() -> Option<String> + Send + Sync>>,
+    pub(crate) include_pid: bool,
+    pub(crate) include_thread_id: bool,
+    pub(crate) include_thread_name: bool,
+    pub(crate) formatter: Option<Arc<dyn LineFormatter + Send + Sync>>,
+    pub(crate) sync_policy: SyncPolicy,
+    pub(crate) sync_directory: bool,
+    pub(crate) buffer_capacity: Option<usize>,
+    pub(crate) file_lock: bool,
+    pub(crate) expand_path: bool,
+    pub(crate) long_paths: bool,
+    pub(crate) file_mode: Option<u32>,
+    pub(crate) dir_mode: Option<u32>,
+    pub(crate) shutdown_marker: Option<String>,
+    pub(crate) startup_banner: Option<String>,
+    pub(crate) shutdown_banner: Option<String>,
+    pub(crate) max_file_size: Option<u64>,
+    pub(crate) rotation_count: usize,
+    pub(crate) max_files: Option<usize>,
+    pub(crate) max_total_size: Option<u64>,
+    pub(crate) compress: bool,
+    pub(crate) derive_clean_filter: bool,
+    pub(crate) clean_strategy: CleanStrategy,
+    pub(crate) clean_subdirectories: (usize, bool),
+    pub(crate) auto_clean: Option<Duration>,
+    pub(crate) latest_link: Option<String>,
+    pub(crate) routes: Vec<(Level, String, bool)>,
+    pub(crate) rate_limit: Option<(u32, Duration)>,
+    pub(crate) retry: Option<(u32, Duration)>,
+    pub(crate) collapse_duplicates: Option<Duration>,
+    pub(crate) sanitize: bool,
+    pub(crate) max_line_len: Option<usize>,
+    pub(crate) min_free_space: Option<u64>,
+    pub(crate) low_space_policy: LowSpacePolicy,
+    pub(crate) low_space_check_interval: Duration,
+    pub(crate) free_space_provider: Option<FreeSpaceProvider>,
+    pub(crate) fallback_path: Option<PathBuf>,
+    pub(crate) clock: Option<Arc<dyn Clock>>,
+    pub(crate) console_capture: Option<ConsoleCapture>,
+    pub(crate) file_follows_timestamp: bool,
+    pub(crate) fallback_retry_interval: Duration,
+    // Only set by Logger::disabled
+    pub(crate) disabled: bool,
+}
+
+impl fmt::Debug for LoggerBuilder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("LoggerBuilder")
+            .field("path", &self.path)
+            .field("file_name_format", &self.file_name_format)
+            .field("line_date_format", &self.line_date_format)
+            .field("days_keep", &self.days_keep)
+            .field("separator", &self.separator)
+            .field("level", &self.level)
+            .field("console", &self.console)
+            .field("output_format", &self.output_format)
+            .field("csv_header", &self.csv_header)
+            .field("file_header", &self.file_header.is_some())
+            .field("line_ending", &self.line_ending)
+            .field("time_zone", &self.time_zone)
+            .field("subsec_precision", &self.subsec_precision)
+            .field("sequence_numbers", &self.sequence_numbers)
+            .field("include_hostname", &self.include_hostname)
+            .field("hostname_provider", &self.hostname_provider.is_some())
+            .field("include_pid", &self.include_pid)
+            .field("include_thread_id", &self.include_thread_id)
+            .field("include_thread_name", &self.include_thread_name)
+            .field("formatter", &self.formatter.is_some())
+            .field("sync_policy", &self.sync_policy)
+            .field("sync_directory", &self.sync_directory)
+            .field("buffer_capacity", &self.buffer_capacity)
+            .field("file_lock", &self.file_lock)
+            .field("expand_path", &self.expand_path)
+            .field("long_paths", &self.long_paths)
+            .field("file_mode", &self.file_mode.map(|mode| format!("{mode:o}")))
+            .field("dir_mode", &self.dir_mode.map(|mode| format!("{mode:o}")))
+            .field("shutdown_marker", &self.shutdown_marker)
+            .field("startup_banner", &self.startup_banner)
+            .field("shutdown_banner", &self.shutdown_banner)
+            .field("max_file_size", &self.max_file_size)
+            .field("rotation_count", &self.rotation_count)
+            .field("max_files", &self.max_files)
+            .field("max_total_size", &self.max_total_size)
+            .field("compress", &self.compress)
+            .field("derive_clean_filter", &self.derive_clean_filter)
+            .field("clean_strategy", &self.clean_strategy)
+            .field("clean_subdirectories", &self.clean_subdirectories)
+            .field("auto_clean", &self.auto_clean)
+            .field("latest_link", &self.latest_link)
+            .field("routes", &self.routes)
+            .field("rate_limit", &self.rate_limit)
+            .field("retry", &self.retry)
+            .field("collapse_duplicates", &self.collapse_duplicates)
+            .field("sanitize", &self.sanitize)
+            .field("max_line_len", &self.max_line_len)
+            .field("min_free_space", &self.min_free_space)
+            .field("low_space_policy", &self.low_space_policy)
+            .field("low_space_check_interval", &self.low_space_check_interval)
+            .field("free_space_provider", &self.free_space_provider.is_some())
+            .field("fallback_path", &self.fallback_path)
+            .field("clock", &self.clock.is_some())
+            .field("console_capture", &self.console_capture.is_some())
+            .field("file_follows_timestamp", &self.file_follows_timestamp)
+            .field("fallback_retry_interval", &self.fallback_retry_interval)
+            .field("disabled", &self.disabled)
+            .finish()
+    }
+}
+
+impl Default for LoggerBuilder {
+    fn default() -> Self {
+        LoggerBuilder {
+            path: PathBuf::from("."),
+            file_name_format: "log_%Y%m%d.log".to_string(),
+            line_date_format: "%Y-%m-%d %H:%M:%S".to_string(),
+            days_keep: None,
+            separator: " ".to_string(),
+            level: Level::Trace,
+            console: ConsoleMode::Off,
+            output_format: OutputFormat::Plain,
+            csv_header: false,
+            file_header: None,
+            line_ending: LineEnding::Native,
+            time_zone: TimeZoneMode::Local,
+            subsec_precision: None,
+            sequence_numbers: false,
+            include_hostname: false,
+            hostname_provider: None,
+            include_pid: false,
+            include_thread_id: false,
+            include_thread_name: false,
+            formatter: None,
+            sync_policy: SyncPolicy::EveryLine,
+            sync_directory: false,
+            buffer_capacity: None,
+            file_lock: false,
+            expand_path: false,
+            long_paths: false,
+            file_mode: None,
+            dir_mode: None,
+            shutdown_marker: None,
+            startup_banner: None,
+            shutdown_banner: None,
+            max_file_size: None,
+            rotation_count: 5,
+            max_files: None,
+            max_total_size: None,
+            compress: false,
+            derive_clean_filter: false,
+            clean_strategy: CleanStrategy::ModifiedTime,
+            clean_subdirectories: (0, false),
+            auto_clean: None,
+            latest_link: None,
+            routes: Vec::new(),
+            rate_limit: None,
+            retry: None,
+            collapse_duplicates: None,
+            sanitize: true,
+            max_line_len: None,
+            min_free_space: None,
+            low_space_policy: LowSpacePolicy::Degrade,
+            low_space_check_interval: DEFAULT_LOW_SPACE_CHECK_INTERVAL,
+            free_space_provider: None,
+            fallback_path: None,
+            clock: None,
+            console_capture: None,
+            file_follows_timestamp: false,
+            fallback_retry_interval: DEFAULT_FALLBACK_RETRY_INTERVAL,
+            disabled: false,
+        }
+    }
+}
+
+impl LoggerBuilder {
+    /// Create a builder with all settings at their defaults
+    /// # Example
+    /// ```rust
+    /// use d_logger::LoggerBuilder;
+    /// 
+    /// let path = std::env::temp_dir().join("d_logger_builder_new");
+    /// let logger = LoggerBuilder::new().path(path).build().unwrap();
+    /// ```
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the path to the log directory, a trailing separator is optional
+    pub fn path(mut self, path: impl AsRef<Path>) -> Self {
+        self.path = path.as_ref().to_path_buf();
+        self
+    }
+
+    /// Expand the path, and the fallback path, once when the logger is built: a leading `~` becomes the home directory
+    /// and `${VAR}` or `%VAR%` the value of the environment variable, on any platform. `Logger::path` gives back the result
+    /// # Example
+    /// ```rust
+    /// use d_logger::{Logger, LoggerError};
+    /// 
+    /// let root = std::env::temp_dir().join("d_logger_expand_path");
+    /// unsafe { std::env::set_var("D_LOGGER_EXPAND_ROOT", &root) };
+    /// 
+    /// for path in ["${D_LOGGER_EXPAND_ROOT}/unix", "%D_LOGGER_EXPAND_ROOT%/windows"] {
+    ///     let logger = Logger::builder().path(path).expand_path(true).build().unwrap();
+    ///     assert!(logger.path().starts_with(&root));
+    ///     assert!(logger.write_log("found"));
+    /// }
+    /// assert!(root.join("unix").is_dir() && root.join("windows").is_dir());
+    /// 
+    /// # #[cfg(unix)]
+    /// # {
+    /// # unsafe { std::env::set_var("HOME", root.join("home")) };
+    /// let logger = Logger::builder().path("~/logs").expand_path(true).build().unwrap();
+    /// assert_eq!(logger.path(), std::env::home_dir().unwrap().join("logs"));
+    /// # }
+    /// 
+    /// // An unset variable is an error rather than an empty part of the path
+    /// let result = Logger::builder().path("${D_LOGGER_NOT_SET}/logs").expand_path(true).build();
+    /// assert!(matches!(result, Err(LoggerError::MissingVariable(name)) if name == "D_LOGGER_NOT_SET"));
+    /// ```
+    pub fn expand_path(mut self, expand: bool) -> Self {
+        self.expand_path = expand;
+        self
+    }
+
+    /// On Windows, give the path, and the fallback path, the extended-length `\\?\` prefix when the log file in it
+    /// would come near MAX_PATH (260 characters), leaving room for rotation and compression to add to the name.
+    /// A UNC share such as `\\fileserver\logs` becomes `\\?\UNC\fileserver\logs`. The path is made absolute first, and rotation,
+    /// compression and log clean all work from the changed path, which `Logger::path` gives back.
+    /// Other platforms have no such limit and the path is left alone
+    /// # Example
+    /// ```rust
+    /// use d_logger::{test_support::ManualClock, Logger};
+    /// use chrono::TimeZone;
+    /// 
+    /// // Six levels of 50 characters each take the file well past MAX_PATH
+    /// let root = std::env::temp_dir().join("d_logger_long_paths");
+    /// # let _ = std::fs::remove_dir_all(&root);
+    /// let deep = (0..6).fold(root.clone(), |dir, i| dir.join(format!("{i}{}", "x".repeat(49))));
+    /// let start = chrono::Local.with_ymd_and_hms(2024, 5, 1, 12, 0, 0).unwrap();
+    /// let logger = Logger::builder().path(&deep).file_name_format("long_%Y%m%d.log").days_keep(Some(7)).long_paths(true).clock(ManualClock::new(start)).build().unwrap();
+    /// assert!(logger.write_log("deep"));
+    /// let name = start.format("long_%Y%m%d.log").to_string();
+    /// assert!(std::fs::read_to_string(logger.path().join(&name)).unwrap().contains("deep"));
+    /// 
+    /// #[cfg(windows)]
+    /// {
+    ///     assert!(logger.path().to_str().unwrap().starts_with(r"\\?\"));
+    ///     assert!(logger.path().join(&name).as_os_str().len() > 260);
+    /// }
+    /// #[cfg(not(windows))]
+    /// assert_eq!(logger.path(), deep);
+    /// 
+    /// // Log clean finds and deletes old files down there too
+    /// let old = logger.path().join("long_20240101.log");
+    /// std::fs::write(&old, "old").unwrap();
+    /// let month_ago = std::time::SystemTime::from(start) - std::time::Duration::from_secs(86400 * 30);
+    /// std::fs::File::options().append(true).open(&old).unwrap().set_modified(month_ago).unwrap();
+    /// let report = logger.log_clean(None).unwrap();
+    /// assert_eq!(report.deleted, ["long_20240101.log"]);
+    /// assert!(!old.exists());
+    /// ```
+    pub fn long_paths(mut self, long_paths: bool) -> Self {
+        self.long_paths = long_paths;
+        self
+    }
+
+    /// Set the date format for the file name, must contain full file name. e.g. Log%d%m%y.log
+    pub fn file_name_format(mut self, format: impl Into<String>) -> Self {
+        self.file_name_format = format.into();
+        self
+    }
+
+    /// Set the date format for lines
+    pub fn line_date_format(mut self, format: impl Into<String>) -> Self {
+        self.line_date_format = format.into();
+        self
+    }
+
+    /// Set the number of days to keep if using log clean, set to None to disable.
+    /// Some(0) keeps only files written since the start of today, in the logger's time zone.
+    /// The file currently being written to is never deleted, whatever its modified time
+    /// # Example
+    /// ```rust
+    /// use d_logger::{test_support::ManualClock, Logger};
+    /// use chrono::TimeZone;
+    /// use std::time::{Duration, SystemTime};
+    /// 
+    /// let path = std::env::temp_dir().join("d_logger_days_keep_zero");
+    /// # let _ = std::fs::remove_dir_all(&path);
+    /// let start = chrono::Local.with_ymd_and_hms(2024, 5, 1, 12, 0, 0).unwrap();
+    /// let logger = Logger::builder().path(&path).days_keep(Some(0)).clock(ManualClock::new(start)).build().unwrap();
+    /// let today = path.join(start.format("log_%Y%m%d.log").to_string());
+    /// 
+    /// // Every file looks too old, including today's after a clock jump
+    /// let old = SystemTime::from(start) - Duration::from_secs(86400 * 2);
+    /// for name in ["log_20240101.log", "log_20240102.log"] {
+    ///     std::fs::write(path.join(name), "old").unwrap();
+    ///     std::fs::File::options().append(true).open(path.join(name)).unwrap().set_modified(old).unwrap();
+    /// }
+    /// std::fs::File::options().append(true).open(&today).unwrap().set_modified(old).unwrap();
+    /// 
+    /// logger.log_clean(None).unwrap();
+    /// 
+    /// assert!(today.exists());
+    /// assert_eq!(std::fs::read_dir(&path).unwrap().count(), 1);
+    /// ```
+    pub fn days_keep(mut self, days_keep: Option<u64>) -> Self {
+        self.days_keep = days_keep;
+        self
+    }
+
+    /// Set the text written between the timestamp and the message, can be empty
+    pub fn separator(mut self, separator: impl Into<String>) -> Self {
+        self.separator = separator.into();
+        self
+    }
+
+    /// Set the minimum level written by the level methods, anything below is dropped
+    pub fn level(mut self, level: Level) -> Self {
+        self.level = level;
+        self
+    }
+
+    /// Echo each entry to the console as well as the file, see `ConsoleMode`
+    pub fn console(mut self, mode: ConsoleMode) -> Self {
+        self.console = mode;
+        self
+    }
+
+    /// Set how entries are written to the file, see `OutputFormat`
+    pub fn output_format(mut self, output_format: OutputFormat) -> Self {
+        self.output_format = output_format;
+        self
+    }
+
+    /// With `OutputFormat::Csv`, start each file with the row "timestamp,level,thread,message". It's written when a
+    /// file is empty as the first entry goes in, so once for each file whether it was started by the date or by rotation
+    pub fn csv_header(mut self, enabled: bool) -> Self {
+        self.csv_header = enabled;
+        self
+    }
+
+    /// Start each file with this block of text, such as the app's name and version and what the columns mean.
+    /// It's written when a file didn't exist or was empty as the first entry goes in, so exactly once for each file
+    /// whether it was started by the date or by rotation, and only one of several threads racing to start it writes it.
+    /// Each line of the text gets the logger's line ending. With `csv_header` too the CSV header row comes after it
+    /// # Example
+    /// ```rust
+    /// use d_logger::{test_support::ManualClock, Logger};
+    /// use chrono::TimeZone;
+    /// 
+    /// let path = std::env::temp_dir().join("d_logger_file_header");
+    /// # let _ = std::fs::remove_dir_all(&path);
+    /// let clock = ManualClock::new(chrono::Local.with_ymd_and_hms(2024, 5, 1, 23, 59, 59).unwrap());
+    /// let logger = Logger::builder()
+    ///     .path(&path)
+    ///     .file_name_format("header_%Y%m%d.log")
+    ///     .file_header("# billing 1.4.2 on web-1\n# time | level | message")
+    ///     .clock(clock.clone())
+    ///     .build()
+    ///     .unwrap();
+    /// 
+    /// // Several threads starting the first file at once still leave one header
+    /// std::thread::scope(|s| {
+    ///     for t in 0..8 {
+    ///         let logger = &logger;
+    ///         s.spawn(move || logger.info(&format!("thread {t}")));
+    ///     }
+    /// });
+    /// clock.advance(std::time::Duration::from_secs(1));
+    /// logger.info("next day");
+    /// clock.advance(std::time::Duration::from_secs(86400));
+    /// logger.info("day after");
+    /// 
+    /// for (file, entries) in [("header_20240501.log", 8), ("header_20240502.log", 1), ("header_20240503.log", 1)] {
+    ///     let contents = std::fs::read_to_string(path.join(file)).unwrap();
+    ///     let lines: Vec<&str> = contents.lines().collect();
+    ///     assert_eq!(lines[..2], ["# billing 1.4.2 on web-1", "# time | level | message"]);
+    ///     assert_eq!(lines.iter().filter(|l| l.starts_with("# billing")).count(), 1);
+    ///     assert_eq!(lines.len(), 2 + entries);
+    /// }
+    /// ```
+    pub fn file_header(mut self, header: impl Into<String>) -> Self {
+        let header = header.into();
+        self.file_header = Some(Arc::new(move || header.clone()));
+        self
+    }
+
+    /// Like `file_header`, with the text made by the closure each time a file is started, so it can say when the
+    /// file was started or hold anything else that changes. It's called while the file is held, so it mustn't log
+    /// # Example
+    /// ```rust
+    /// use d_logger::Logger;
+    /// use std::sync::atomic::{AtomicU32, Ordering};
+    /// 
+    /// let path = std::env::temp_dir().join("d_logger_file_header_provider");
+    /// # let _ = std::fs::remove_dir_all(&path);
+    /// let started = AtomicU32::new(0);
+    /// let logger = Logger::builder()
+    ///     .path(&path)
+    ///     .file_name_format("provided.log")
+    ///     .line_date_format("")
+    ///     .separator("")
+    ///     .max_file_size(20)
+    ///     .file_header_provider(move || format!("== file {} ==", started.fetch_add(1, Ordering::Relaxed) + 1))
+    ///     .build()
+    ///     .unwrap();
+    /// logger.write_log("first");
+    /// logger.write_log("second");
+    /// 
+    /// // The second entry went over the size limit and started a fresh file with a new header
+    /// let read = |name: &str| std::fs::read_to_string(path.join(name)).unwrap().lines().map(str::to_string).collect::<Vec<_>>();
+    /// assert_eq!(read("provided.log.1"), ["== file 1 ==", "first"]);
+    /// assert_eq!(read("provided.log"), ["== file 2 ==", "second"]);
+    /// ```
+    pub fn file_header_provider(mut self, provider: impl Fn() -> String + Send + Sync + 'static) -> Self {
+        self.file_header = Some(Arc::new(provider));
+        self
+    }
+
+    /// Set what ends each entry, see `LineEnding`
+    pub fn line_ending(mut self, line_ending: LineEnding) -> Self {
+        self.line_ending = line_ending;
+        self
+    }
+
+    /// Set the time zone used for both line timestamps and dated file names, see `TimeZoneMode`
+    pub fn time_zone(mut self, time_zone: TimeZoneMode) -> Self {
+        self.time_zone = time_zone;
+        self
+    }
+
+    /// Append fractional seconds to line timestamps, unless the line date format already has them.
+    /// Custom formatters render the timestamp themselves so this doesn't apply to them
+    pub fn subsec_precision(mut self, precision: Option<SubsecPrecision>) -> Self {
+        self.subsec_precision = precision;
+        self
+    }
+
+    /// Write an increasing sequence number with every line, e.g. "#000123" after the timestamp,
+    /// so entries in the same instant can still be ordered. Numbers start at 1 for each logger
+    /// # Example
+    /// ```rust
+    /// use d_logger::Logger;
+    /// 
+    /// let path = std::env::temp_dir().join("d_logger_sequence");
+    /// let logger = Logger::builder().path(&path).file_name_format("sequence_%Y%m%d.log").sequence_numbers(true).build().unwrap();
+    /// # let file = path.join(chrono::Local::now().format("sequence_%Y%m%d.log").to_string());
+    /// # let _ = std::fs::remove_file(&file);
+    /// for i in 0..1000 {
+    ///     logger.write_log(&format!("line {i}"));
+    /// }
+    /// 
+    /// let contents = std::fs::read_to_string(&file).unwrap();
+    /// let seqs: Vec<u64> = contents.lines().map(|l| l.split_once('#').unwrap().1[..6].parse().unwrap()).collect();
+    /// assert_eq!(seqs.len(), 1000);
+    /// assert!(seqs.windows(2).all(|w| w[0] < w[1]));
+    /// assert!(contents.lines().next().unwrap().ends_with(" #000001 line 0"));
+    /// ```
+    pub fn sequence_numbers(mut self, enabled: bool) -> Self {
+        self.sequence_numbers = enabled;
+        self
+    }
+
+    /// Add the machine's host name to each line, e.g. "[host web-1] message".
+    /// The name is looked up once when the logger is built, with the `hostname` feature it comes from the OS,
+    /// otherwise from the HOSTNAME or COMPUTERNAME environment variables. If it can't be found "unknown-host" is used
+    pub fn include_hostname(mut self, enabled: bool) -> Self {
+        self.include_hostname = enabled;
+        self
+    }
+
+    /// Replace how the host name is looked up, returning None falls back to "unknown-host"
+    /// # Example
+    /// ```rust
+    /// use d_logger::Logger;
+    /// 
+    /// let path = std::env::temp_dir().join("d_logger_hostname");
+    /// let logger = Logger::builder()
+    ///     .path(&path)
+    ///     .file_name_format("hostname_%Y%m%d.log")
+    ///     .include_hostname(true)
+    ///     .hostname_provider(|| Some("web-1".to_string()))
+    ///     .build()
+    ///     .unwrap();
+    /// logger.write_log("hello");
+    /// let failing = Logger::builder()
+    ///     .path(&path)
+    ///     .file_name_format("hostname_%Y%m%d.log")
+    ///     .include_hostname(true)
+    ///     .hostname_provider(|| None)
+    ///     .build()
+    ///     .unwrap();
+    /// failing.write_log("hello");
+    /// 
+    /// let contents = std::fs::read_to_string(path.join(chrono::Local::now().format("hostname_%Y%m%d.log").to_string())).unwrap();
+    /// assert!(contents.contains(" [host web-1] hello"));
+    /// assert!(contents.contains(" [host unknown-host] hello"));
+    /// ```
+    pub fn hostname_provider(mut self, provider: impl Fn() -> Option<String> + Send + Sync + 'static) -> Self {
+        self.hostname_provider = Some(Arc::new(provider));
+        self
+    }
+
+    /// Add the process id to each line, e.g. "[pid 4242] message"
+    pub fn include_pid(mut self, enabled: bool) -> Self {
+        self.include_pid = enabled;
+        self
+    }
+
+    /// Add the numeric id of the writing thread to each line, e.g. "[tid 5] message"
+    pub fn include_thread_id(mut self, enabled: bool) -> Self {
+        self.include_thread_id = enabled;
+        self
+    }
+
+    /// Add the name of the writing thread to each line, e.g. "[worker-3] message".
+    /// Unnamed threads use their numeric id instead
+    /// # Example
+    /// ```rust
+    /// use d_logger::Logger;
+    /// 
+    /// let path = std::env::temp_dir().join("d_logger_thread_name");
+    /// let logger = Logger::builder()
+    ///     .path(&path)
+    ///     .file_name_format("threads_%Y%m%d.log")
+    ///     .include_pid(true)
+    ///     .include_thread_name(true)
+    ///     .build()
+    ///     .unwrap();
+    /// # let file = path.join(chrono::Local::now().format("threads_%Y%m%d.log").to_string());
+    /// # let _ = std::fs::remove_file(&file);
+    /// std::thread::scope(|s| {
+    ///     for i in 0..3 {
+    ///         let logger = &logger;
+    ///         std::thread::Builder::new().name(format!("worker-{i}")).spawn_scoped(s, move || logger.write_log("hello")).unwrap();
+    ///     }
+    ///     s.spawn(|| logger.write_log("unnamed"));
+    /// });
+    /// 
+    /// let contents = std::fs::read_to_string(&file).unwrap();
+    /// let pid = std::process::id();
+    /// for i in 0..3 {
+    ///     assert!(contents.contains(&format!(" [pid {pid}] [worker-{i}] hello")));
+    /// }
+    /// let unnamed = contents.lines().find(|l| l.ends_with("unnamed")).unwrap();
+    /// let name = unnamed.split("] [").nth(1).unwrap().split(']').next().unwrap();
+    /// assert!(name.chars().all(|c| c.is_ascii_digit()));
+    /// ```
+    pub fn include_thread_name(mut self, enabled: bool) -> Self {
+        self.include_thread_name = enabled;
+        self
+    }
+
+    /// Use a custom formatter for lines, this replaces the output format and the line date format.
+    /// See `LineFormatter`, `DefaultFormatter` and `BracketFormatter`
+    pub fn formatter(mut self, formatter: Box<dyn LineFormatter + Send + Sync>) -> Self {
+        self.formatter = Some(Arc::from(formatter));
+        self
+    }
+
+    /// Set when entries are flushed and synced to disk, see `SyncPolicy`
+    pub fn sync_policy(mut self, sync_policy: SyncPolicy) -> Self {
+        self.sync_policy = sync_policy;
+        self
+    }
+
+    /// Sync the log directory each time a log file is created in it, at the first write, when the date changes and
+    /// when a file is rotated. Syncing a file doesn't make its new directory entry durable on Unix, so without this a
+    /// power cut just after a file is created can lose the whole file. Files the logger finds already there are left
+    /// to the sync policy. Windows has no way to sync a directory, so this does nothing there
+    /// # Example
+    /// ```rust
+    /// use d_logger::{test_support::ManualClock, Logger, SyncPolicy};
+    /// use chrono::TimeZone;
+    /// 
+    /// let path = std::env::temp_dir().join("d_logger_sync_directory");
+    /// # let _ = std::fs::remove_dir_all(&path);
+    /// let clock = ManualClock::new(chrono::Local.with_ymd_and_hms(2024, 5, 1, 23, 59, 59).unwrap());
+    /// let logger = Logger::builder()
+    ///     .path(&path)
+    ///     .file_name_format("durable_%Y%m%d.log")
+    ///     .sync_policy(SyncPolicy::EveryLine)
+    ///     .sync_directory(true)
+    ///     .max_file_size(40)
+    ///     .clock(clock.clone())
+    ///     .build()
+    ///     .unwrap();
+    /// 
+    /// // The first file, a rotation and the next day's file each sync the directory along the way
+    /// assert!(logger.try_write_log("before midnight").is_ok());
+    /// assert!(logger.try_write_log("rotated, still before midnight").is_ok());
+    /// clock.advance(std::time::Duration::from_secs(1));
+    /// assert!(logger.try_write_log("after midnight").is_ok());
+    /// assert!(path.join("durable_20240501.log.1").exists());
+    /// assert!(std::fs::read_to_string(path.join("durable_20240502.log")).unwrap().contains("after midnight"));
+    /// ```
+    pub fn sync_directory(mut self, sync_directory: bool) -> Self {
+        self.sync_directory = sync_directory;
+        self
+    }
+
+    /// Hold written entries in a buffer of this many bytes in front of the file.
+    /// The buffer is flushed according to the sync policy, when it fills, when the dated file changes and when the logger is dropped
+    /// # Example
+    /// ```rust
+    /// use d_logger::{test_support::ManualClock, Logger, SyncPolicy};
+    /// use chrono::TimeZone;
+    /// 
+    /// let path = std::env::temp_dir().join("d_logger_buffer_capacity");
+    /// # let _ = std::fs::remove_dir_all(&path);
+    /// let clock = ManualClock::new(chrono::Local.with_ymd_and_hms(2024, 5, 1, 23, 59, 59).unwrap());
+    /// let logger = Logger::builder()
+    ///     .path(&path)
+    ///     .file_name_format("buffered_%Y%m%d.log")
+    ///     .buffer_capacity(1024 * 1024)
+    ///     .sync_policy(SyncPolicy::Never)
+    ///     .clock(clock.clone())
+    ///     .build()
+    ///     .unwrap();
+    /// for i in 0..100 {
+    ///     logger.write_log(&format!("line {i}"));
+    /// }
+    /// // Still buffered until the date changes
+    /// assert_eq!(std::fs::read_to_string(path.join("buffered_20240501.log")).unwrap(), "");
+    /// clock.advance(std::time::Duration::from_secs(1));
+    /// for i in 100..200 {
+    ///     logger.write_log(&format!("line {i}"));
+    /// }
+    /// assert_eq!(std::fs::read_to_string(path.join("buffered_20240501.log")).unwrap().lines().count(), 100);
+    /// drop(logger);
+    /// 
+    /// // Every line made it out, including the ones buffered when the file switched
+    /// assert_eq!(std::fs::read_to_string(path.join("buffered_20240502.log")).unwrap().lines().count(), 100);
+    /// ```
+    pub fn buffer_capacity(mut self, capacity: usize) -> Self {
+        self.buffer_capacity = Some(capacity);
+        self
+    }
+
+    /// Take an exclusive advisory lock on the file around every write (flock on Unix, LockFileEx on Windows),
+    /// for when several processes append to the same dated file. Entries, including anything buffered,
+    /// are written in full while the lock is held, so no process can land in the middle of another's line.
+    /// Only writers that also lock are kept out, other programs opening the file are not stopped
+    /// # Example
+    /// ```rust
+    /// use d_logger::Logger;
+    /// 
+    /// let path = std::env::temp_dir().join("d_logger_file_lock");
+    /// let logger = |name: &str| Logger::builder()
+    ///     .path(&path)
+    ///     .file_name_format("locked.log")
+    ///     .line_date_format(name)
+    ///     .use_file_lock(true)
+    ///     .build()
+    ///     .unwrap();
+    /// let payload = "x".repeat(8 * 1024);
+    /// 
+    /// // Run again as a child process, each one appending long lines to the same file
+    /// if let Ok(name) = std::env::var("D_LOGGER_LOCK_CHILD") {
+    ///     let logger = logger(&name);
+    ///     for _ in 0..200 {
+    ///         assert!(logger.write_log(&payload));
+    ///     }
+    ///     return;
+    /// }
+    /// # let _ = std::fs::remove_dir_all(&path);
+    /// let children: Vec<_> = (0..4).map(|i| {
+    ///     std::process::Command::new(std::env::current_exe().unwrap())
+    ///         .env("D_LOGGER_LOCK_CHILD", format!("child{i}"))
+    ///         .spawn()
+    ///         .unwrap()
+    /// }).collect();
+    /// for mut child in children {
+    ///     assert!(child.wait().unwrap().success());
+    /// }
+    /// 
+    /// // Every line is whole, one child's name followed by its full payload
+    /// let contents = std::fs::read_to_string(path.join("locked.log")).unwrap();
+    /// let lines: Vec<&str> = contents.lines().filter(|l| !l.is_empty()).collect();
+    /// assert_eq!(lines.len(), 4 * 200);
+    /// for line in lines {
+    ///     let (name, rest) = line.split_once(' ').unwrap();
+    ///     assert!(name.starts_with("child") && name.len() == 6);
+    ///     assert_eq!(rest, payload);
+    /// }
+    /// ```
+    pub fn use_file_lock(mut self, lock: bool) -> Self {
+        self.file_lock = lock;
+        self
+    }
+
+    /// Permission bits for log directories the logger creates, e.g. `0o750`, including any missing parents.
+    /// They're set again once the directory is made, so the umask can't take any away. Directories that already
+    /// exist are left alone. Unix only, accepted and ignored elsewhere
+    /// # Example
+    /// ```rust
+    /// use d_logger::Logger;
+    /// 
+    /// let root = std::env::temp_dir().join("d_logger_dir_mode");
+    /// # let _ = std::fs::remove_dir_all(&root);
+    /// // None of these exist yet, all three levels are made
+    /// let path = root.join("myapp").join("workers").join("logs");
+    /// let logger = Logger::builder().path(&path).dir_mode(0o750).build().unwrap();
+    /// assert!(logger.write_log("deep"));
+    /// 
+    /// # #[cfg(unix)]
+    /// # {
+    /// use std::os::unix::fs::PermissionsExt;
+    /// for dir in [root.join("myapp"), path] {
+    ///     assert_eq!(std::fs::metadata(dir).unwrap().permissions().mode() & 0o777, 0o750);
+    /// }
+    /// # }
+    /// ```
+    pub fn dir_mode(mut self, mode: u32) -> Self {
+        self.dir_mode = Some(mode);
+        self
+    }
+
+    /// Permission bits for log files the logger creates, e.g. `0o640` to keep them from being world readable.
+    /// Applied when a file is first made, by a new day, a rotation or the first write, and set again straight after
+    /// so the umask can't take any away. Files that already exist are left alone, and compressed copies keep the
+    /// mode of the file they came from. Unix only, accepted and ignored elsewhere
+    /// # Example
+    /// ```rust
+    /// use d_logger::Logger;
+    /// 
+    /// let path = std::env::temp_dir().join("d_logger_file_mode");
+    /// # let _ = std::fs::remove_dir_all(&path);
+    /// let logger = Logger::builder().path(&path).file_name_format("mode_%Y%m%d.log").file_mode(0o640).build().unwrap();
+    /// assert!(logger.write_log("not for everyone"));
+    /// 
+    /// # #[cfg(unix)]
+    /// # {
+    /// use std::os::unix::fs::PermissionsExt;
+    /// let file = path.join(chrono::Local::now().format("mode_%Y%m%d.log").to_string());
+    /// assert_eq!(std::fs::metadata(file).unwrap().permissions().mode() & 0o777, 0o640);
+    /// # }
+    /// ```
+    pub fn file_mode(mut self, mode: u32) -> Self {
+        self.file_mode = Some(mode);
+        self
+    }
+
+    /// Write this line to the log when the logger is dropped or closed, e.g. `=== logger shutdown ===`.
+    /// It's written as given with no level, see `shutdown_banner` for an Info line with placeholders
+    /// # Example
+    /// ```rust
+    /// use d_logger::Logger;
+    /// 
+    /// let path = std::env::temp_dir().join("d_logger_shutdown_marker");
+    /// let logger = Logger::builder()
+    ///     .path(&path)
+    ///     .file_name_format("marker_%Y%m%d.log")
+    ///     .buffer_capacity(64 * 1024)
+    ///     .shutdown_marker("=== logger shutdown ===")
+    ///     .build()
+    ///     .unwrap();
+    /// let file = path.join(chrono::Local::now().format("marker_%Y%m%d.log").to_string());
+    /// # let _ = std::fs::remove_file(&file);
+    /// 
+    /// logger.write_log("first");
+    /// logger.write_log("second");
+    /// drop(logger);
+    /// 
+    /// let contents = std::fs::read_to_string(&file).unwrap();
+    /// let lines: Vec<&str> = contents.lines().collect();
+    /// assert_eq!(lines.len(), 3);
+    /// assert!(lines[1].ends_with("second"));
+    /// assert!(lines[2].ends_with("=== logger shutdown ==="));
+    /// ```
+    pub fn shutdown_marker(mut self, marker: impl Into<String>) -> Self {
+        self.shutdown_marker = Some(marker.into());
+        self
+    }
+
+    /// Write an Info line when the logger is built, None for nothing. The banner is formatted like any other entry and
+    /// can use placeholders filled in when it's written: `{version}` for the d_logger version, `{pid}` for the process id
+    /// and `{timestamp}` for the time in RFC 3339. Sinks added after building don't see it
+    /// # Example
+    /// ```rust
+    /// use d_logger::Logger;
+    /// 
+    /// let path = std::env::temp_dir().join("d_logger_banners");
+    /// # let _ = std::fs::remove_dir_all(&path);
+    /// let logger = Logger::builder()
+    ///     .path(&path)
+    ///     .file_name_format("banner.log")
+    ///     .startup_banner(Some("=== started pid {pid} with d_logger {version} ===".to_string()))
+    ///     .shutdown_banner(Some("=== stopped at {timestamp} ===".to_string()))
+    ///     .build()
+    ///     .unwrap();
+    /// logger.write_log("working");
+    /// drop(logger);
+    /// 
+    /// let contents = std::fs::read_to_string(path.join("banner.log")).unwrap();
+    /// let lines: Vec<&str> = contents.lines().collect();
+    /// assert_eq!(lines.len(), 3);
+    /// assert!(lines[0].ends_with(&format!("[INFO] === started pid {} with d_logger {} ===", std::process::id(), env!("CARGO_PKG_VERSION"))));
+    /// assert!(lines[1].ends_with(" working"));
+    /// let stopped = lines[2].split("[INFO] === stopped at ").nth(1).unwrap().trim_end_matches(" ===");
+    /// assert!(chrono::DateTime::parse_from_rfc3339(stopped).is_ok());
+    /// 
+    /// // None writes nothing at all
+    /// let logger = Logger::builder().path(&path).file_name_format("no_banner.log").startup_banner(None).shutdown_banner(None).build().unwrap();
+    /// drop(logger);
+    /// assert_eq!(std::fs::read_to_string(path.join("no_banner.log")).unwrap(), "");
+    /// ```
+    pub fn startup_banner(mut self, banner: Option<String>) -> Self {
+        self.startup_banner = banner;
+        self
+    }
+
+    /// Write an Info line when the logger is dropped or closed, None for nothing.
+    /// It takes the same placeholders as `startup_banner`
+    pub fn shutdown_banner(mut self, banner: Option<String>) -> Self {
+        self.shutdown_banner = banner;
+        self
+    }
+
+    /// Rotate the log once the current file would grow past this many bytes.
+    /// The full file is renamed to "<name>.1", older copies move up to "<name>.2" and so on, and a fresh file is started.
+    /// See `rotation_count` for how many numbered copies are kept
+    /// # Example
+    /// ```rust
+    /// use d_logger::Logger;
+    /// 
+    /// let path = std::env::temp_dir().join("d_logger_max_file_size");
+    /// # let _ = std::fs::remove_dir_all(&path);
+    /// let logger = Logger::builder()
+    ///     .path(&path)
+    ///     .file_name_format("size_%Y%m%d.log")
+    ///     .max_file_size(1024)
+    ///     .rotation_count(3)
+    ///     .build()
+    ///     .unwrap();
+    /// let file = path.join(chrono::Local::now().format("size_%Y%m%d.log").to_string());
+    /// # std::fs::remove_file(&file).unwrap();
+    /// for i in 0..200 {
+    ///     logger.write_log(&format!("line {i:03}"));
+    /// }
+    /// 
+    /// let numbered = |n: usize| std::path::PathBuf::from(format!("{}.{n}", file.display()));
+    /// assert!(numbered(3).exists());
+    /// assert!(!numbered(4).exists());
+    /// let mut last = None;
+    /// for name in [numbered(3), numbered(2), numbered(1), file.clone()] {
+    ///     let contents = std::fs::read_to_string(&name).unwrap();
+    ///     assert!(contents.len() <= 1024);
+    ///     // Each file carries on from where the older one stopped
+    ///     for line in contents.lines() {
+    ///         let number: u32 = line.rsplit(' ').next().unwrap().parse().unwrap();
+    ///         if let Some(last) = last {
+    ///             assert_eq!(number, last + 1);
+    ///         }
+    ///         last = Some(number);
+    ///     }
+    /// }
+    /// assert!(std::fs::read_to_string(&file).unwrap().ends_with(&format!("line 199{}", if cfg!(windows) { "\r\n" } else { "\n" })));
+    /// ```
+    pub fn max_file_size(mut self, bytes: u64) -> Self {
+        self.max_file_size = Some(bytes);
+        self
+    }
+
+    /// How many numbered copies to keep when rotating by size, the oldest is deleted once there are more.
+    /// At least one copy is always kept, so 0 works like 1 rather than throwing the full file away
+    /// # Example
+    /// ```rust
+    /// use d_logger::Logger;
+    /// 
+    /// let path = std::env::temp_dir().join("d_logger_rotation_count");
+    /// # let _ = std::fs::remove_dir_all(&path);
+    /// let logger = Logger::builder()
+    ///     .path(&path)
+    ///     .file_name_format("count.log")
+    ///     .line_date_format("")
+    ///     .separator("")
+    ///     .max_file_size(10)
+    ///     .rotation_count(0)
+    ///     .build()
+    ///     .unwrap();
+    /// for i in 1..=3 {
+    ///     logger.write_log(&format!("entry {i}"));
+    /// }
+    /// 
+    /// // Only the newest copy is left, but the entry before the current one is still there
+    /// let newline = if cfg!(windows) { "\r\n" } else { "\n" };
+    /// assert_eq!(std::fs::read_to_string(path.join("count.log")).unwrap(), format!("entry 3{newline}"));
+    /// assert_eq!(std::fs::read_to_string(path.join("count.log.1")).unwrap(), format!("entry 2{newline}"));
+    /// assert!(!path.join("count.log.2").exists());
+    /// ```
+    pub fn rotation_count(mut self, count: usize) -> Self {
+        self.rotation_count = count;
+        self
+    }
+
+    /// Keep at most this many log files. Rotation keeps one fewer numbered copy than this to leave room for the current file,
+    /// but never fewer than one, so with `max_files(1)` a rotated file still leaves "<name>.1" for `log_clean` to deal with.
+    /// and `log_clean` deletes the oldest matching files by modified time, after cleaning by age, until only this many remain.
+    /// The file currently being written to always counts as one of them and is never deleted
+    /// # Example
+    /// ```rust
+    /// use d_logger::{test_support::ManualClock, Logger};
+    /// use chrono::TimeZone;
+    /// use std::time::{Duration, SystemTime};
+    /// 
+    /// let path = std::env::temp_dir().join("d_logger_max_files");
+    /// # let _ = std::fs::remove_dir_all(&path);
+    /// let start = chrono::Local.with_ymd_and_hms(2024, 5, 1, 12, 0, 0).unwrap();
+    /// let logger = Logger::builder().path(&path).file_name_format("app_%Y%m%d.log").max_files(3).clock(ManualClock::new(start)).build().unwrap();
+    /// 
+    /// // Ten older dated files, modified in the opposite order to their names and all well within any age limit
+    /// let day = Duration::from_secs(86400);
+    /// for i in 0..10 {
+    ///     let file = std::fs::File::create(path.join(format!("app_202401{:02}.log", i + 1))).unwrap();
+    ///     file.set_modified(SystemTime::from(start) - day * (i + 1)).unwrap();
+    /// }
+    /// // Even with the oldest modified time, the current file is kept
+    /// let current = path.join(start.format("app_%Y%m%d.log").to_string());
+    /// std::fs::File::options().append(true).open(&current).unwrap().set_modified(SystemTime::from(start) - day * 30).unwrap();
+    /// 
+    /// logger.log_clean(Some(r"^app_\d{8}\.log$")).unwrap();
+    /// 
+    /// let mut left: Vec<String> = std::fs::read_dir(&path).unwrap().map(|f| f.unwrap().file_name().into_string().unwrap()).collect();
+    /// left.sort();
+    /// let mut expected = vec!["app_20240101.log".to_string(), "app_20240102.log".to_string(), current.file_name().unwrap().to_string_lossy().into_owned()];
+    /// expected.sort();
+    /// assert_eq!(left, expected);
+    /// ```
+    pub fn max_files(mut self, count: usize) -> Self {
+        self.max_files = Some(count);
+        self
+    }
+
+    /// Keep the files matched by `log_clean` under this many bytes in total.
+    /// After cleaning by age and by count the oldest files by modified time are deleted until the rest fit.
+    /// Directories are skipped and the file currently being written to is never deleted, though its size counts
+    /// # Example
+    /// ```rust
+    /// use d_logger::{test_support::ManualClock, Logger};
+    /// use chrono::TimeZone;
+    /// use std::time::{Duration, SystemTime};
+    /// 
+    /// let path = std::env::temp_dir().join("d_logger_max_total_size");
+    /// # let _ = std::fs::remove_dir_all(&path);
+    /// let start = chrono::Local.with_ymd_and_hms(2024, 5, 1, 12, 0, 0).unwrap();
+    /// let logger = Logger::builder().path(&path).file_name_format("quota_%Y%m%d.log").days_keep(Some(10)).max_total_size(2500).clock(ManualClock::new(start)).build().unwrap();
+    /// 
+    /// // 1000 bytes each, modified 1 to 5 days ago, then one past the age limit
+    /// let day = Duration::from_secs(86400);
+    /// for (i, name) in ["a.log", "b.log", "c.log", "d.log", "e.log", "old.log"].iter().enumerate() {
+    ///     std::fs::write(path.join(name), vec![b'x'; 1000]).unwrap();
+    ///     let age = if *name == "old.log" { 20 } else { i as u32 + 1 };
+    ///     std::fs::File::options().append(true).open(path.join(name)).unwrap().set_modified(SystemTime::from(start) - day * age).unwrap();
+    /// }
+    /// std::fs::create_dir(path.join("archive")).unwrap();
+    /// 
+    /// logger.log_clean(Some(r"\.log$")).unwrap();
+    /// 
+    /// // The old file goes by age, then e, d and c by size, which leaves the current (nearly empty) file, a and b
+    /// let mut left: Vec<String> = std::fs::read_dir(&path).unwrap().map(|f| f.unwrap().file_name().into_string().unwrap()).collect();
+    /// left.sort();
+    /// let current = start.format("quota_%Y%m%d.log").to_string();
+    /// assert_eq!(left, ["a.log", "archive", "b.log", current.as_str()]);
+    /// let total: u64 = left.iter().map(|name| std::fs::metadata(path.join(name)).unwrap()).filter(|m| m.is_file()).map(|m| m.len()).sum();
+    /// assert!(total <= 2500);
+    /// ```
+    pub fn max_total_size(mut self, bytes: u64) -> Self {
+        self.max_total_size = Some(bytes);
+        self
+    }
+
+    /// Gzip log files once they are finished with, when the date moves on to a new file or size rotation moves them to "<name>.1".
+    /// "Log010125.log" becomes "Log010125.log.gz" and the original is only removed once the compressed copy is complete.
+    /// `log_clean` treats the ".gz" files like the originals, matching the filter against the name with or without ".gz",
+    /// and removes any ".gz.tmp" files left half written by an interrupted run.
+    /// A file that can't be compressed is left as it was, the error kept for `Logger::last_error` and sent as `LogEvent::CompressFailed`
+    /// # Example
+    /// ```rust
+    /// use d_logger::{test_support::ManualClock, LogEvent, Logger, LoggerError};
+    /// use chrono::TimeZone;
+    /// use std::io::Read;
+    /// use std::sync::{Arc, Mutex};
+    /// 
+    /// let path = std::env::temp_dir().join("d_logger_compress");
+    /// # let _ = std::fs::remove_dir_all(&path);
+    /// let clock = ManualClock::new(chrono::Local.with_ymd_and_hms(2024, 5, 1, 23, 59, 59).unwrap());
+    /// let logger = Logger::builder().path(&path).file_name_format("zip_%Y%m%d.log").compress(true).clock(clock.clone()).build().unwrap();
+    /// let first = path.join("zip_20240501.log");
+    /// logger.write_log("first file");
+    /// let original = std::fs::read(&first).unwrap();
+    /// clock.advance(std::time::Duration::from_secs(1));
+    /// logger.write_log("second file");
+    /// 
+    /// // The first file was compressed when the second one started
+    /// assert!(!first.exists());
+    /// let mut unzipped = Vec::new();
+    /// let gz = std::fs::File::open(path.join("zip_20240501.log.gz")).unwrap();
+    /// flate2::read::GzDecoder::new(gz).read_to_end(&mut unzipped).unwrap();
+    /// assert_eq!(unzipped, original);
+    /// 
+    /// // Leftovers from an interrupted compress are cleaned up
+    /// std::fs::write(path.join("zip_20240101.log.gz.tmp"), b"half").unwrap();
+    /// logger.log_clean(Some(r"^zip_\d{8}\.log$")).unwrap();
+    /// assert!(!path.join("zip_20240101.log.gz.tmp").exists());
+    /// 
+    /// // A file that can't be compressed is left in place and reported, not written about in the log
+    /// std::fs::create_dir_all(path.join("zip_20240502.log.gz.tmp").join("in the way")).unwrap();
+    /// let failed = Arc::new(Mutex::new(Vec::new()));
+    /// let seen = Arc::clone(&failed);
+    /// logger.on_event(move |event| if let LogEvent::CompressFailed { path, .. } = event { seen.lock().unwrap().push(path) });
+    /// clock.advance(std::time::Duration::from_secs(86400));
+    /// logger.write_log("third file");
+    /// assert!(path.join("zip_20240502.log").exists());
+    /// assert_eq!(*failed.lock().unwrap(), [path.join("zip_20240502.log")]);
+    /// assert!(matches!(logger.last_error(), Some(LoggerError::Compress(_))));
+    /// assert_eq!(std::fs::read_to_string(path.join("zip_20240503.log")).unwrap().lines().count(), 1);
+    /// ```
+    #[cfg(feature = "compress")]
+    pub fn compress(mut self, enabled: bool) -> Self {
+        self.compress = enabled;
+        self
+    }
+
+    /// When `log_clean` is given no filter, only clean files whose names could have come from `file_name_format`,
+    /// see `Logger::file_name_pattern` for the regex used. Rotated and compressed copies of those files are included.
+    /// This keeps the cleaner away from other files when the logger shares a directory
+    /// # Example
+    /// ```rust
+    /// use d_logger::{test_support::ManualClock, Logger};
+    /// use chrono::TimeZone;
+    /// use std::time::{Duration, SystemTime};
+    /// 
+    /// let path = std::env::temp_dir().join("d_logger_derive_clean_filter");
+    /// # let _ = std::fs::remove_dir_all(&path);
+    /// let start = chrono::Local.with_ymd_and_hms(2024, 5, 1, 12, 0, 0).unwrap();
+    /// let logger = Logger::builder().path(&path).file_name_format("app_%Y%m%d.log").days_keep(Some(7)).derive_clean_filter(true).clock(ManualClock::new(start)).build().unwrap();
+    /// let old = SystemTime::from(start) - Duration::from_secs(86400 * 30);
+    /// for name in ["app_20240101.log", "report.xlsx"] {
+    ///     std::fs::write(path.join(name), "old").unwrap();
+    ///     std::fs::File::options().append(true).open(path.join(name)).unwrap().set_modified(old).unwrap();
+    /// }
+    /// 
+    /// logger.log_clean(None).unwrap();
+    /// assert!(!path.join("app_20240101.log").exists());
+    /// assert!(path.join("report.xlsx").exists());
+    /// ```
+    pub fn derive_clean_filter(mut self, enabled: bool) -> Self {
+        self.derive_clean_filter = enabled;
+        self
+    }
+
+    /// Set how `log_clean` works out how old each file is, see `CleanStrategy`
+    pub fn clean_strategy(mut self, strategy: CleanStrategy) -> Self {
+        self.clean_strategy = strategy;
+        self
+    }
+
+    /// Have `log_clean` go down into subdirectories of the log directory, up to `depth` levels, for logs kept in
+    /// folders like "logs/2024-05". Files inside are cleaned by the same age, filter, count and size rules as the rest,
+    /// with the filter matched against the file name alone, and are reported as "2024-05/app.log".
+    /// With `remove_empty` a subdirectory the clean empties is removed as well, and listed in `CleanReport::removed_dirs`.
+    /// Symlinks to directories are never followed
+    /// # Example
+    /// ```rust
+    /// use d_logger::{test_support::ManualClock, Logger};
+    /// use chrono::TimeZone;
+    /// use std::time::{Duration, SystemTime};
+    /// 
+    /// let path = std::env::temp_dir().join("d_logger_clean_subdirectories");
+    /// # let _ = std::fs::remove_dir_all(&path);
+    /// let start = chrono::Local.with_ymd_and_hms(2024, 5, 1, 12, 0, 0).unwrap();
+    /// let logger = Logger::builder().path(&path).file_name_format("app_%Y%m%d.log").days_keep(Some(7)).clean_subdirectories(2, true).clock(ManualClock::new(start)).build().unwrap();
+    /// 
+    /// let old = SystemTime::from(start) - Duration::from_secs(86400 * 30);
+    /// let files = [("2024-04/01/app_20240401.log", true), ("2024-04/app_20240430.log", true), ("2024-05/app_20240501.log", true), ("2024-05/app_20240502.log", false), ("2024-05/01/02/app_20240501.log", true)];
+    /// for (name, is_old) in files {
+    ///     let file = path.join(name);
+    ///     std::fs::create_dir_all(file.parent().unwrap()).unwrap();
+    ///     std::fs::write(&file, "log").unwrap();
+    ///     if is_old {
+    ///         std::fs::File::options().append(true).open(&file).unwrap().set_modified(old).unwrap();
+    ///     }
+    /// }
+    /// 
+    /// // A link to a directory elsewhere is left alone, along with everything in it
+    /// let elsewhere = std::env::temp_dir().join("d_logger_clean_subdirectories_elsewhere");
+    /// # #[cfg(unix)]
+    /// # {
+    /// # let _ = std::fs::remove_dir_all(&elsewhere);
+    /// std::fs::create_dir_all(&elsewhere).unwrap();
+    /// std::fs::write(elsewhere.join("app_20240101.log"), "log").unwrap();
+    /// std::fs::File::options().append(true).open(elsewhere.join("app_20240101.log")).unwrap().set_modified(old).unwrap();
+    /// std::os::unix::fs::symlink(&elsewhere, path.join("linked")).unwrap();
+    /// # }
+    /// 
+    /// let mut report = logger.log_clean(Some(r"^app_\d{8}\.log$")).unwrap();
+    /// report.deleted.sort();
+    /// assert_eq!(report.deleted, ["2024-04/01/app_20240401.log", "2024-04/app_20240430.log", "2024-05/app_20240501.log"]);
+    /// assert_eq!(report.removed_dirs, ["2024-04/01", "2024-04"]);
+    /// 
+    /// // Three levels down is past the depth, and the month with a recent file stays
+    /// assert!(path.join("2024-05/01/02/app_20240501.log").exists());
+    /// assert!(path.join("2024-05/app_20240502.log").exists());
+    /// assert!(!path.join("2024-04").exists());
+    /// # #[cfg(unix)]
+    /// assert!(elsewhere.join("app_20240101.log").exists());
+    /// ```
+    pub fn clean_subdirectories(mut self, depth: usize, remove_empty: bool) -> Self {
+        self.clean_subdirectories = (depth, remove_empty);
+        self
+    }
+
+    /// Have writes run `log_clean(None)` once a day, so old files go without anyone calling it.
+    /// The first write cleans straight away. The check is a single atomic load, and when it's time exactly one
+    /// writer runs the clean after its own entry while the others carry on. See `auto_clean_interval` to change how often
+    pub fn auto_clean(mut self, enabled: bool) -> Self {
+        self.auto_clean = if enabled { Some(self.auto_clean.unwrap_or(DEFAULT_AUTO_CLEAN_INTERVAL)) } else { None };
+        self
+    }
+
+    /// How often auto clean runs, 24 hours by default. Setting this turns auto clean on
+    /// # Example
+    /// ```rust
+    /// use d_logger::{test_support::ManualClock, Logger};
+    /// use std::time::Duration;
+    /// 
+    /// let path = std::env::temp_dir().join("d_logger_auto_clean");
+    /// # let _ = std::fs::remove_dir_all(&path);
+    /// let clock = ManualClock::new(chrono::Local::now());
+    /// let logger = Logger::builder().path(&path).days_keep(Some(7)).auto_clean_interval(Duration::from_secs(3600)).clock(clock.clone()).build().unwrap();
+    /// 
+    /// // Files written now are past days_keep once the clock has moved on 8 days
+    /// std::fs::write(path.join("log_20240101.log"), "old").unwrap();
+    /// clock.advance(Duration::from_secs(86400 * 8));
+    /// logger.write_log("first write cleans");
+    /// assert!(!path.join("log_20240101.log").exists());
+    /// 
+    /// // Within the interval nothing is cleaned, after it the next write cleans again
+    /// std::fs::write(path.join("log_20240102.log"), "old").unwrap();
+    /// logger.write_log("too soon");
+    /// assert!(path.join("log_20240102.log").exists());
+    /// clock.advance(Duration::from_secs(3600));
+    /// logger.write_log("time to clean");
+    /// assert!(!path.join("log_20240102.log").exists());
+    /// ```
+    pub fn auto_clean_interval(mut self, interval: Duration) -> Self {
+        self.auto_clean = Some(interval);
+        self
+    }
+
+    /// Keep a link with this name in the log directory pointing at the current dated file, so there's one name to tail.
+    /// It's updated each time a new file is started. On Unix it's a symlink, elsewhere a hard link, or if that
+    /// fails a text file with the extension changed to `.txt` holding the current file's name.
+    /// A link that can't be updated doesn't stop the write, it's kept for `Logger::last_error` and sent as
+    /// `LogEvent::LinkFailed`. `log_clean` never removes the link
+    /// # Example
+    /// ```rust
+    /// use d_logger::{test_support::ManualClock, LogEvent, Logger, LoggerError};
+    /// use chrono::TimeZone;
+    /// use std::sync::{Arc, Mutex};
+    /// 
+    /// let path = std::env::temp_dir().join("d_logger_latest_link");
+    /// # let _ = std::fs::remove_dir_all(&path);
+    /// let clock = ManualClock::new(chrono::Local.with_ymd_and_hms(2024, 5, 1, 23, 59, 59).unwrap());
+    /// let logger = Logger::builder()
+    ///     .path(&path)
+    ///     .file_name_format("latest_%Y%m%d.log")
+    ///     .create_latest_link("latest.log")
+    ///     .days_keep(Some(0))
+    ///     .clock(clock.clone())
+    ///     .build()
+    ///     .unwrap();
+    /// logger.write_log("before the roll");
+    /// clock.advance(std::time::Duration::from_secs(1));
+    /// logger.write_log("after the roll");
+    /// 
+    /// let latest = std::fs::read_to_string(path.join("latest.log")).unwrap();
+    /// assert!(latest.contains("after the roll") && !latest.contains("before the roll"));
+    /// if cfg!(unix) {
+    ///     let target = std::fs::read_link(path.join("latest.log")).unwrap();
+    ///     assert_eq!(target, std::path::Path::new("latest_20240502.log"));
+    /// }
+    /// 
+    /// // The link is old but still left by the cleaner
+    /// logger.log_clean(None).unwrap();
+    /// assert!(path.join("latest.log").exists());
+    /// 
+    /// // A directory in the link's place can't be replaced, the entry is still written and nothing is added to the log
+    /// std::fs::remove_file(path.join("latest.log")).unwrap();
+    /// std::fs::create_dir_all(path.join("latest.log").join("in the way")).unwrap();
+    /// let failures = Arc::new(Mutex::new(0));
+    /// let counted = Arc::clone(&failures);
+    /// logger.on_event(move |event| if let LogEvent::LinkFailed { .. } = event { *counted.lock().unwrap() += 1 });
+    /// clock.advance(std::time::Duration::from_secs(86400));
+    /// assert!(logger.write_log("link not updated"));
+    /// assert!(matches!(logger.last_error(), Some(LoggerError::LatestLink(_))));
+    /// assert_eq!(*failures.lock().unwrap(), 1);
+    /// assert_eq!(std::fs::read_to_string(path.join("latest_20240503.log")).unwrap().lines().count(), 1);
+    /// ```
+    pub fn create_latest_link(mut self, name: impl Into<String>) -> Self {
+        self.latest_link = Some(name.into());
+        self
+    }
+
+    /// Also write entries at this level to their own dated file in the log directory, e.g. "error_%Y%m%d.log".
+    /// With `exclusive` they go only to that file instead of the main one as well. Only the level methods are routed,
+    /// lines written without a level stay in the main file. Routed files are rotated like the main one,
+    /// and are covered by `file_name_pattern` so the derived clean filter cleans them too.
+    /// Can be called for several levels, or more than once for the same level
+    /// # Arguments
+    /// * `level` - The level to route, only that exact level
+    /// * `file_name_format` - Date format for the routed file's name, like `file_name_format`
+    /// * `exclusive` - true to leave these entries out of the main file
+    /// # Example
+    /// ```rust
+    /// use d_logger::{Level, Logger};
+    /// 
+    /// let path = std::env::temp_dir().join("d_logger_route_level");
+    /// let logger = Logger::builder()
+    ///     .path(&path)
+    ///     .file_name_format("all_%Y%m%d.log")
+    ///     .route_level(Level::Error, "error_%Y%m%d.log", false)
+    ///     .route_level(Level::Debug, "debug_%Y%m%d.log", true)
+    ///     .build()
+    ///     .unwrap();
+    /// let all = path.join(chrono::Local::now().format("all_%Y%m%d.log").to_string());
+    /// let errors = path.join(chrono::Local::now().format("error_%Y%m%d.log").to_string());
+    /// let debug = path.join(chrono::Local::now().format("debug_%Y%m%d.log").to_string());
+    /// # for file in [&all, &errors, &debug] { let _ = std::fs::remove_file(file); }
+    /// 
+    /// logger.info("service started");
+    /// logger.error("disk on fire");
+    /// logger.debug("cache warmed");
+    /// 
+    /// let all = std::fs::read_to_string(&all).unwrap();
+    /// let errors = std::fs::read_to_string(&errors).unwrap();
+    /// assert!(all.contains("[INFO] service started") && all.contains("[ERROR] disk on fire"));
+    /// assert!(errors.contains("[ERROR] disk on fire") && !errors.contains("service started"));
+    /// assert!(!all.contains("cache warmed"));
+    /// assert!(std::fs::read_to_string(&debug).unwrap().contains("[DEBUG] cache warmed"));
+    /// 
+    /// let pattern = regex::Regex::new(&logger.file_name_pattern()).unwrap();
+    /// assert!(pattern.is_match("all_20240101.log") && pattern.is_match("error_20240101.log"));
+    /// ```
+    pub fn route_level(mut self, level: Level, file_name_format: impl Into<String>, exclusive: bool) -> Self {
+        self.routes.push((level, file_name_format.into(), exclusive));
+        self
+    }
+
+    /// Write at most `max` entries with the same message in each `window`, dropping the rest.
+    /// Once a window has passed, the next entry for that message is preceded by one summary line,
+    /// e.g. "Warning = suppressed 5321 similar messages | connection refused".
+    /// `Logger::write_log_limited` counts entries under a key of the caller's choosing instead of the message.
+    /// The most recently seen 1024 keys are tracked, older ones are summarised and forgotten
+    /// # Example
+    /// ```rust
+    /// use d_logger::Logger;
+    /// use std::time::Duration;
+    /// 
+    /// let path = std::env::temp_dir().join("d_logger_rate_limit");
+    /// let logger = Logger::builder().path(&path).file_name_format("limited_%Y%m%d.log").rate_limit(10, Duration::from_secs(1)).build().unwrap();
+    /// let file = path.join(chrono::Local::now().format("limited_%Y%m%d.log").to_string());
+    /// # let _ = std::fs::remove_file(&file);
+    /// for _ in 0..500 {
+    ///     assert!(logger.write_log("connection refused"));
+    /// }
+    /// // Lines in a batch count towards the limit too
+    /// assert_eq!(logger.write_lines(["connection refused"; 500]).unwrap(), 0);
+    /// logger.write_log("something else");
+    /// std::thread::sleep(Duration::from_millis(1100));
+    /// logger.write_log("connection refused");
+    /// 
+    /// let contents = std::fs::read_to_string(&file).unwrap();
+    /// let lines: Vec<&str> = contents.lines().filter(|l| !l.is_empty()).collect();
+    /// assert_eq!(lines.len(), 13);
+    /// assert_eq!(lines.iter().filter(|l| l.ends_with(" connection refused") && !l.contains("suppressed")).count(), 11);
+    /// assert!(lines[10].ends_with(" something else"));
+    /// assert!(lines[11].ends_with(" Warning = suppressed 990 similar messages | connection refused"));
+    /// ```
+    pub fn rate_limit(mut self, max: u32, window: Duration) -> Self {
+        self.rate_limit = Some((max, window));
+        self
+    }
+
+    /// Try a failed write again up to `attempts` more times, waiting `backoff` before the first retry and twice as long
+    /// before each one after. Only failures that may clear up on their own are retried: interrupted and would block,
+    /// and on Windows permission denied and sharing violations, as when an antivirus scanner briefly holds the file.
+    /// A missing directory fails straight away. The waits for one entry never add up to more than a second.
+    /// Opening and writing the dated file are retried, and so are writes to sinks
+    /// # Example
+    /// ```rust
+    /// use d_logger::{Logger, Sink};
+    /// use std::{io, sync::{atomic::{AtomicU32, Ordering}, Arc}, time::Duration};
+    /// 
+    /// // Fails this many times before it works
+    /// struct Flaky(Arc<AtomicU32>, u32, io::ErrorKind);
+    /// impl Sink for Flaky {
+    ///     fn write_entry(&self, _formatted: &str) -> io::Result<()> {
+    ///         if self.0.fetch_add(1, Ordering::Relaxed) < self.1 { Err(self.2.into()) } else { Ok(()) }
+    ///     }
+    /// }
+    /// 
+    /// let path = std::env::temp_dir().join("d_logger_retry");
+    /// let logger = Logger::builder().path(&path).file_name_format("retry_%Y%m%d.log").retry(3, Duration::from_millis(5)).build().unwrap();
+    /// 
+    /// let calls = Arc::new(AtomicU32::new(0));
+    /// logger.add_sink(Box::new(Flaky(Arc::clone(&calls), 2, io::ErrorKind::Interrupted)));
+    /// assert!(logger.write_log("third time lucky"));
+    /// assert_eq!(calls.load(Ordering::Relaxed), 3);
+    /// assert_eq!(logger.sink_errors(), 0);
+    /// 
+    /// // Something that won't clear up is only tried once
+    /// let calls = Arc::new(AtomicU32::new(0));
+    /// logger.add_sink(Box::new(Flaky(Arc::clone(&calls), 10, io::ErrorKind::NotFound)));
+    /// logger.write_log("not retried");
+    /// assert_eq!(calls.load(Ordering::Relaxed), 1);
+    /// assert_eq!(logger.sink_errors(), 1);
+    /// ```
+    pub fn retry(mut self, attempts: u32, backoff: Duration) -> Self {
+        self.retry = Some((attempts, backoff));
+        self
+    }
+
+    /// Write a message repeated back to back only once, followed by "last message repeated N times"
+    /// when a different message arrives. Messages are compared without their timestamps, by level, text and key value pairs.
+    /// There's no timer behind this: a held back count is only written by the next write, `Logger::flush` or when the
+    /// logger closes. If the message stops repeating and nothing else is written, the count waits until one of those.
+    /// A message that keeps repeating has its count written by the first repeat 30 seconds or more after it started
+    /// being held back, see `collapse_duplicates_timeout`
+    /// # Example
+    /// ```rust
+    /// use d_logger::Logger;
+    /// 
+    /// let path = std::env::temp_dir().join("d_logger_collapse_duplicates");
+    /// let logger = Logger::builder().path(&path).file_name_format("dedup_%Y%m%d.log").collapse_duplicates(true).build().unwrap();
+    /// let file = path.join(chrono::Local::now().format("dedup_%Y%m%d.log").to_string());
+    /// # let _ = std::fs::remove_file(&file);
+    /// for _ in 0..50 {
+    ///     assert!(logger.write_log("disk full"));
+    /// }
+    /// // Repeats in a batch are collapsed the same way
+    /// assert_eq!(logger.write_lines(["disk full"; 50]).unwrap(), 0);
+    /// logger.write_log("disk ok");
+    /// 
+    /// let contents = std::fs::read_to_string(&file).unwrap();
+    /// let lines: Vec<&str> = contents.lines().collect();
+    /// assert_eq!(lines.len(), 3);
+    /// assert!(lines[0].ends_with(" disk full"));
+    /// assert!(lines[1].ends_with(" last message repeated 99 times"));
+    /// assert!(lines[2].ends_with(" disk ok"));
+    /// 
+    /// // The count held back is written on drop
+    /// logger.write_log("disk ok");
+    /// drop(logger);
+    /// assert!(std::fs::read_to_string(&file).unwrap().trim_end().ends_with(" last message repeated 1 time"));
+    /// ```
+    pub fn collapse_duplicates(mut self, enabled: bool) -> Self {
+        self.collapse_duplicates = if enabled { Some(self.collapse_duplicates.unwrap_or(DEFAULT_DUPLICATE_TIMEOUT)) } else { None };
+        self
+    }
+
+    /// How long a message can keep repeating before its count is written, 30 seconds by default.
+    /// Counting then starts again. The timeout is only looked at when something is written, so the count goes out with
+    /// the first write after it has passed, a repeat or any other entry, not at the moment it passes.
+    /// `Logger::flush` writes it straight away. Setting this turns `collapse_duplicates` on
+    /// # Example
+    /// ```rust
+    /// use d_logger::Logger;
+    /// use std::time::Duration;
+    /// 
+    /// let path = std::env::temp_dir().join("d_logger_collapse_timeout");
+    /// # let _ = std::fs::remove_dir_all(&path);
+    /// let logger = Logger::builder().path(&path).line_date_format("").separator("").collapse_duplicates_timeout(Duration::from_millis(50)).build().unwrap();
+    /// let read = || std::fs::read_to_string(logger.current_file_path()).unwrap();
+    /// 
+    /// for _ in 0..3 {
+    ///     logger.write_log("disk full");
+    /// }
+    /// logger.flush().unwrap();
+    /// assert_eq!(read().lines().collect::<Vec<_>>(), ["disk full", "last message repeated 2 times"]);
+    /// 
+    /// // Still collapsed after the flush, and written ahead of the next entry once the timeout has passed
+    /// logger.write_log("disk full");
+    /// std::thread::sleep(Duration::from_millis(60));
+    /// logger.write_log_stamped(chrono::Local::now(), "imported");
+    /// assert_eq!(read().lines().skip(2).collect::<Vec<_>>(), ["last message repeated 1 time", "imported"]);
+    /// ```
+    pub fn collapse_duplicates_timeout(mut self, timeout: Duration) -> Self {
+        self.collapse_duplicates = Some(timeout);
+        self
+    }
+
+    /// Escape line breaks and other control characters in messages, on by default.
+    /// A "\n" in the message is written as the two characters `\n`, "\r" as `\r` and anything else as `\xNN`,
+    /// tabs are kept. This stops text from users starting what looks like a new entry.
+    /// Plain, syslog and CSV entries and custom formatters get the escaped message.
+    /// The JSON format escapes as part of the JSON and isn't changed by this. Sinks get the escaped entry, with the message as given in `Record::message`.
+    /// Earlier versions wrote messages as given, `sanitize(false)` keeps that
+    /// # Example
+    /// ```rust
+    /// use d_logger::{Logger, OutputFormat};
+    /// 
+    /// let path = std::env::temp_dir().join("d_logger_sanitize");
+    /// let logger = Logger::builder().path(&path).file_name_format("sanitize_%Y%m%d.log").build().unwrap();
+    /// let file = path.join(chrono::Local::now().format("sanitize_%Y%m%d.log").to_string());
+    /// # let _ = std::fs::remove_file(&file);
+    /// let user = "bob\n2024-01-01 00:00:00 FAKE ADMIN LOGIN\r\x1b[2J";
+    /// logger.write_log(&format!("failed login for {user}"));
+    /// 
+    /// let contents = std::fs::read_to_string(&file).unwrap();
+    /// assert_eq!(contents.lines().count(), 1);
+    /// assert!(contents.trim_end().ends_with(r"failed login for bob\n2024-01-01 00:00:00 FAKE ADMIN LOGIN\r\x1B[2J"));
+    /// 
+    /// // A CSV row stays on one line too
+    /// let logger = Logger::builder().path(&path).file_name_format("sanitize.csv").output_format(OutputFormat::Csv).build().unwrap();
+    /// # let _ = std::fs::remove_file(path.join("sanitize.csv"));
+    /// logger.write_log("first\nsecond");
+    /// let contents = std::fs::read_to_string(path.join("sanitize.csv")).unwrap();
+    /// assert_eq!(contents.lines().count(), 1);
+    /// assert!(contents.trim_end().ends_with(r",first\nsecond"));
+    /// ```
+    pub fn sanitize(mut self, enabled: bool) -> Self {
+        self.sanitize = enabled;
+        self
+    }
+
+    /// Cut messages longer than this many bytes short, ending them with "…[truncated N bytes]".
+    /// The limit is on the message after redaction and sanitizing, not counting the timestamp, key value pairs or the marker,
+    /// and the cut never falls inside a character
+    /// # Example
+    /// ```rust
+    /// use d_logger::Logger;
+    /// 
+    /// let path = std::env::temp_dir().join("d_logger_max_line_len");
+    /// let logger = Logger::builder().path(&path).file_name_format("short.log").line_date_format("").separator("").max_line_len(8).build().unwrap();
+    /// # let _ = std::fs::remove_file(path.join("short.log"));
+    /// logger.write_log("12345678");
+    /// logger.write_log("123456789");
+    /// // "é" is two bytes, the cut goes before one that would only half fit
+    /// logger.write_log("1234567é");
+    /// logger.write_log("ééééé");
+    /// 
+    /// let contents = std::fs::read_to_string(path.join("short.log")).unwrap();
+    /// let lines: Vec<&str> = contents.lines().collect();
+    /// assert_eq!(lines, ["12345678", "12345678…[truncated 1 bytes]", "1234567…[truncated 2 bytes]", "éééé…[truncated 2 bytes]"]);
+    /// ```
+    pub fn max_line_len(mut self, max: usize) -> Self {
+        self.max_line_len = Some(max);
+        self
+    }
+
+    /// Watch free space on the log volume and follow the `low_space_policy` while it's below this many bytes.
+    /// Space is looked at on a write once the check interval has passed, every 10 seconds by default.
+    /// A warning line is written on going into low space and another on coming out of it, whatever the policy
+    /// # Example
+    /// ```rust
+    /// use d_logger::Logger;
+    /// use std::sync::{atomic::{AtomicU64, Ordering}, Arc};
+    /// use std::time::Duration;
+    /// 
+    /// let path = std::env::temp_dir().join("d_logger_min_free_space");
+    /// let free = Arc::new(AtomicU64::new(10_000_000));
+    /// let provider = Arc::clone(&free);
+    /// let logger = Logger::builder()
+    ///     .path(&path)
+    ///     .file_name_format("space_%Y%m%d.log")
+    ///     .min_free_space(1_000_000)
+    ///     .low_space_check_interval(Duration::ZERO)
+    ///     .free_space_provider(move || Some(provider.load(Ordering::Relaxed)))
+    ///     .build()
+    ///     .unwrap();
+    /// let file = path.join(chrono::Local::now().format("space_%Y%m%d.log").to_string());
+    /// # let _ = std::fs::remove_file(&file);
+    /// logger.info("plenty of space");
+    /// free.store(500_000, Ordering::Relaxed);
+    /// logger.info("dropped");
+    /// logger.write_log("dropped too");
+    /// logger.warn("still written");
+    /// // Batches are checked line by line the same way
+    /// assert_eq!(logger.write_lines(["batch dropped", "batch dropped too"]).unwrap(), 0);
+    /// free.store(2_000_000, Ordering::Relaxed);
+    /// logger.info("back to normal");
+    /// 
+    /// let contents = std::fs::read_to_string(&file).unwrap();
+    /// let lines: Vec<&str> = contents.lines().collect();
+    /// assert_eq!(lines.len(), 5);
+    /// assert!(lines[0].ends_with("[INFO] plenty of space"));
+    /// assert!(lines[1].contains("Warning = Log low space, 500000 bytes free is below 1000000"));
+    /// assert!(lines[2].ends_with("[WARN] still written"));
+    /// assert!(lines[3].contains("Warning = Log low space, 2000000 bytes free again"));
+    /// assert!(lines[4].ends_with("[INFO] back to normal"));
+    /// assert_eq!(logger.stats().entries_dropped, 4);
+    /// ```
+    pub fn min_free_space(mut self, bytes: u64) -> Self {
+        self.min_free_space = Some(bytes);
+        self
+    }
+
+    /// What to do while free space is below `min_free_space`, see `LowSpacePolicy`
+    pub fn low_space_policy(mut self, policy: LowSpacePolicy) -> Self {
+        self.low_space_policy = policy;
+        self
+    }
+
+    /// How long to wait between looks at the free space, 10 seconds by default
+    pub fn low_space_check_interval(mut self, interval: Duration) -> Self {
+        self.low_space_check_interval = interval;
+        self
+    }
+
+    /// Replace how free space is found for `min_free_space`, e.g. in tests. None counts as having space
+    pub fn free_space_provider(mut self, provider: impl Fn() -> Option<u64> + Send + Sync + 'static) -> Self {
+        self.free_space_provider = Some(Arc::new(provider));
+        self
+    }
+
+    /// Take the time from this clock instead of the system's, for timestamps, dated file names, auto clean and the
+    /// ages `log_clean` works out. Mostly for tests, see `test_support::ManualClock`
+    pub fn clock(mut self, clock: impl Clock + 'static) -> Self {
+        self.clock = Some(Arc::new(clock));
+        self
+    }
+
+    /// Send what the console mode echoes to this capture instead of standard output and error, so a test can
+    /// check it, see `test_support::ConsoleCapture`
+    pub fn console_capture(mut self, capture: ConsoleCapture) -> Self {
+        self.console_capture = Some(capture);
+        self
+    }
+
+    /// Put entries written with their own timestamp, by `Logger::write_log_stamped` and `write_level_stamped`, in the
+    /// dated file for that timestamp rather than the file for now. A timestamp whose day is already past `days_keep`
+    /// still goes in the file for now, so log clean doesn't delete the entry as soon as it's written
+    pub fn file_follows_timestamp(mut self, enabled: bool) -> Self {
+        self.file_follows_timestamp = enabled;
+        self
+    }
+
+    /// Write to this directory instead while the log directory can't be written, e.g. a network mount that has
+    /// gone away. Files keep their dated names. The main directory is tried again every 30 seconds,
+    /// see `fallback_retry_interval`. A warning line is written to the fallback on switching to it, and to the main
+    /// directory on switching back. The fallback directory is made when first needed
+    /// # Example
+    /// ```rust
+    /// use d_logger::Logger;
+    /// use std::time::Duration;
+    /// 
+    /// let path = std::env::temp_dir().join("d_logger_fallback_main");
+    /// # let _ = std::fs::remove_file(&path);
+    /// let fallback = std::env::temp_dir().join("d_logger_fallback_spare");
+    /// # let _ = std::fs::remove_dir_all(&fallback);
+    /// # std::fs::create_dir_all(&fallback).unwrap();
+    /// let logger = Logger::builder()
+    ///     .path(&path)
+    ///     .file_name_format("fallback_%Y%m%d.log")
+    ///     .fallback_path(&fallback)
+    ///     .fallback_retry_interval(Duration::from_millis(500))
+    ///     .build()
+    ///     .unwrap();
+    /// let name = chrono::Local::now().format("fallback_%Y%m%d.log").to_string();
+    /// logger.write_log("before");
+    /// 
+    /// // A file takes the main directory's place, writes carry on in the fallback once the open file is noticed missing
+    /// std::fs::remove_dir_all(&path).unwrap();
+    /// std::fs::write(&path, "").unwrap();
+    /// std::thread::sleep(Duration::from_millis(1100));
+    /// assert!(logger.write_log("during"));
+    /// let spare = std::fs::read_to_string(fallback.join(&name)).unwrap();
+    /// let lines: Vec<&str> = spare.lines().collect();
+    /// assert!(lines[0].contains("Warning = Log fallback, could not write to"));
+    /// assert!(lines[1].ends_with(" during"));
+    /// 
+    /// // It comes back, and after the retry interval writes return to it
+    /// std::fs::remove_file(&path).unwrap();
+    /// std::fs::create_dir(&path).unwrap();
+    /// std::thread::sleep(Duration::from_millis(600));
+    /// assert!(logger.write_log("after"));
+    /// let main = std::fs::read_to_string(path.join(&name)).unwrap();
+    /// let lines: Vec<&str> = main.lines().collect();
+    /// assert!(lines[0].contains("Warning = Log fallback, writing to"));
+    /// assert!(lines[1].ends_with(" after"));
+    /// ```
+    pub fn fallback_path(mut self, path: impl AsRef<Path>) -> Self {
+        self.fallback_path = Some(path.as_ref().to_path_buf());
+        self
+    }
+
+    /// How long to keep writing to the fallback before trying the main directory again, 30 seconds by default
+    pub fn fallback_retry_interval(mut self, interval: Duration) -> Self {
+        self.fallback_retry_interval = interval;
+        self
+    }
+
+    /// Override settings from environment variables named with the prefix, like `D_LOGGER_PATH` for the prefix `D_LOGGER`.
+    /// Variables that aren't set, or are set to an empty string, leave the setting as it is, except that an empty
+    /// `DAYS_KEEP` or `MAX_FILE_SIZE` turns that option off. The variables are:
+    /// * `PATH`, `FILE_NAME_FORMAT` and `LINE_DATE_FORMAT`
+    /// * `LEVEL` - trace, debug, info, warn or error in any case
+    /// * `CONSOLE` - off, stdout or stderr
+    /// * `DAYS_KEEP` and `MAX_FILE_SIZE` - whole numbers
+    /// # Example
+    /// ```rust
+    /// use d_logger::{Level, Logger, LoggerError};
+    /// 
+    /// let path = std::env::temp_dir().join("d_logger_from_env");
+    /// // The environment is shared by the whole process, so this example uses a prefix of its own
+    /// unsafe {
+    ///     std::env::set_var("D_LOGGER_FROM_ENV_PATH", &path);
+    ///     std::env::set_var("D_LOGGER_FROM_ENV_LEVEL", "warn");
+    ///     std::env::set_var("D_LOGGER_FROM_ENV_DAYS_KEEP", "");
+    /// }
+    /// let logger = Logger::builder()
+    ///     .path("set/in/code")
+    ///     .level(Level::Debug)
+    ///     .days_keep(Some(7))
+    ///     .from_env("D_LOGGER_FROM_ENV")
+    ///     .unwrap()
+    ///     .build()
+    ///     .unwrap();
+    /// assert_eq!(logger.path(), path);
+    /// assert_eq!(logger.level(), Level::Warn);
+    /// assert_eq!(logger.days_keep(), None);
+    /// 
+    /// // A value that can't be used names the variable
+    /// unsafe { std::env::set_var("D_LOGGER_FROM_ENV_DAYS_KEEP", "a week") };
+    /// let result = Logger::builder().from_env("D_LOGGER_FROM_ENV");
+    /// assert!(matches!(result, Err(LoggerError::InvalidConfig(msg)) if msg.starts_with("D_LOGGER_FROM_ENV_DAYS_KEEP")));
+    /// # unsafe {
+    /// #     for name in ["PATH", "LEVEL", "DAYS_KEEP"] {
+    /// #         std::env::remove_var(format!("D_LOGGER_FROM_ENV_{name}"));
+    /// #     }
+    /// # }
+    /// ```
+    /// # Returns
+    /// * `Err(LoggerError::InvalidConfig)` naming the variable if a value can't be used
+    pub fn from_env(mut self, prefix: &str) -> Result<Self, LoggerError> {
+        let separator = if prefix.is_empty() || prefix.ends_with('_') { "" } else { "_" };
+        let var = |name: &str| {
+            let name = format!("{prefix}{separator}{name}");
+            env::var_os(&name).map(|value| (name, value.to_string_lossy().into_owned()))
+        };
+        // Put the variable's name in front of a parse error
+        let named = |name: &str, e: LoggerError| match e {
+            LoggerError::InvalidConfig(msg) => LoggerError::InvalidConfig(format!("{name} {msg}")),
+            e => e,
+        };
+        // An empty value turns an optional number off
+        let number = |name: &str, value: &str| match value {
+            "" => Ok(None),
+            value => value.parse().map(Some).map_err(|_| LoggerError::InvalidConfig(format!("{name} \"{value}\" is not a whole number"))),
+        };
+
+        if let Some((_, value)) = var("PATH").filter(|(_, value)| !value.is_empty()) {
+            self.path = PathBuf::from(value);
+        }
+        if let Some((_, value)) = var("FILE_NAME_FORMAT").filter(|(_, value)| !value.is_empty()) {
+            self.file_name_format = value;
+        }
+        if let Some((_, value)) = var("LINE_DATE_FORMAT").filter(|(_, value)| !value.is_empty()) {
+            self.line_date_format = value;
+        }
+        if let Some((name, value)) = var("LEVEL").filter(|(_, value)| !value.is_empty()) {
+            self.level = value.parse().map_err(|e| named(&name, e))?;
+        }
+        if let Some((name, value)) = var("CONSOLE").filter(|(_, value)| !value.is_empty()) {
+            self.console = value.parse().map_err(|e| named(&name, e))?;
+        }
+        if let Some((name, value)) = var("DAYS_KEEP") {
+            self.days_keep = number(&name, &value)?;
+        }
+        if let Some((name, value)) = var("MAX_FILE_SIZE") {
+            self.max_file_size = number(&name, &value)?;
+        }
+        Ok(self)
+    }
+
+    /// Validate the settings and create the logger, making sure the log file can be used.
+    /// Both formats are rendered once up front, the file name must not be empty or contain path separators
+    /// or characters the platform doesn't allow (e.g. ':' on Windows).
+    /// # Example
+    /// ```rust
+    /// use d_logger::{LoggerBuilder, LoggerError};
+    /// 
+    /// let path = std::env::temp_dir().join("d_logger_build");
+    /// for good in ["Log%d%m%y.log", "log_%Y-%m-%d.txt", "%j.log", "static.log"] {
+    ///     assert!(LoggerBuilder::new().path(&path).file_name_format(good).build().is_ok(), "{good}");
+    /// }
+    /// for bad in ["Log%Q.log", "", "%", "logs/%Y.log", "Log%d%m%y.log\n"] {
+    ///     let result = LoggerBuilder::new().path(&path).file_name_format(bad).build();
+    ///     assert!(matches!(result, Err(LoggerError::InvalidFormat(msg)) if msg.starts_with("file_name_format")), "{bad}");
+    /// }
+    /// # #[cfg(windows)]
+    /// # assert!(LoggerBuilder::new().path(&path).file_name_format("%H:%M.log").build().is_err());
+    /// for bad in ["%Y-%m-%d %", "%Y-%Q"] {
+    ///     let result = LoggerBuilder::new().path(&path).line_date_format(bad).build();
+    ///     assert!(matches!(result, Err(LoggerError::InvalidFormat(msg)) if msg.starts_with("line_date_format")), "{bad}");
+    /// }
+    /// ```
+    /// Missing directories are made along with their parents, but a path that's already a file is refused, as is an
+    /// empty one. A symlink to a directory is followed
+    /// ```rust
+    /// use d_logger::{LoggerBuilder, LoggerError};
+    /// 
+    /// let file = std::env::temp_dir().join("d_logger_build_not_a_dir");
+    /// std::fs::write(&file, "not a directory").unwrap();
+    /// let error = LoggerBuilder::new().path(&file).build().err().unwrap();
+    /// assert!(matches!(&error, LoggerError::NotADirectory { path } if *path == file));
+    /// assert_eq!(error.to_string(), format!("log path {} is not a directory", file.display()));
+    /// 
+    /// let error = LoggerBuilder::new().path("").build().err().unwrap();
+    /// assert!(matches!(&error, LoggerError::NotADirectory { path } if path.as_os_str().is_empty()));
+    /// assert_eq!(error.to_string(), "log path is empty");
+    /// 
+    /// #[cfg(unix)]
+    /// {
+    ///     let target = std::env::temp_dir().join("d_logger_build_link_target");
+    ///     let link = std::env::temp_dir().join("d_logger_build_link");
+    /// #   let _ = std::fs::remove_dir_all(&target);
+    /// #   let _ = std::fs::remove_file(&link);
+    ///     std::fs::create_dir(&target).unwrap();
+    ///     std::os::unix::fs::symlink(&target, &link).unwrap();
+    ///     let logger = LoggerBuilder::new().path(&link).file_name_format("linked.log").build().unwrap();
+    ///     assert!(logger.write_log("through the link"));
+    ///     assert!(std::fs::read_to_string(target.join("linked.log")).unwrap().contains("through the link"));
+    /// }
+    /// ```
+    /// # Returns
+    /// * `Err(LoggerError::InvalidFormat)` if either date format can't be rendered, or the file name is empty or contains invalid characters
+    /// * `Err(LoggerError::DirectoryCreate)` if the directory is missing and can't be created
+    /// * `Err(LoggerError::NotADirectory)` if the path is a file or is empty
+    /// * `Err(LoggerError::DirectoryNotWritable)` if the log file can't be created in the directory
+    /// * `Err(LoggerError::FileOpen)` if the log file can't be opened for appending
+    pub fn build(self) -> Result<Logger, LoggerError> {
+        Logger::from_builder(self)
+    }
+}
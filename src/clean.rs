@@ -0,0 +1,507 @@
+use std::{
+    ffi::OsStr,
+    fmt, fs, io,
+    str::FromStr,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use chrono::{
+    format::{Parsed, StrftimeItems},
+    FixedOffset, NaiveTime,
+};
+use regex::Regex;
+
+use crate::{LogEvent, LoggerError, SECS_1_DAY};
+
+/// What `log_clean` did, so callers can see what was removed and why everything else was left
+/// # Example
+/// ```rust
+/// use d_logger::{test_support::ManualClock, Logger, SkipReason};
+/// use chrono::TimeZone;
+/// use std::time::{Duration, SystemTime};
+/// 
+/// let path = std::env::temp_dir().join("d_logger_clean_report");
+/// # let _ = std::fs::remove_dir_all(&path);
+/// let start = chrono::Local.with_ymd_and_hms(2024, 5, 1, 12, 0, 0).unwrap();
+/// let logger = Logger::builder().path(&path).file_name_format("app_%Y%m%d.log").days_keep(Some(7)).clock(ManualClock::new(start)).build().unwrap();
+/// let current = start.format("app_%Y%m%d.log").to_string();
+/// 
+/// let old = SystemTime::from(start) - Duration::from_secs(86400 * 10);
+/// for (name, size) in [("app_20240101.log", 100), ("app_20240102.log", 250), ("app_20991231.log", 10), ("notes.txt", 5)] {
+///     std::fs::write(path.join(name), vec![b'x'; size]).unwrap();
+///     if name != "app_20991231.log" {
+///         std::fs::File::options().append(true).open(path.join(name)).unwrap().set_modified(old).unwrap();
+///     }
+/// }
+/// std::fs::create_dir(path.join("app_archive.log")).unwrap();
+/// 
+/// let mut report = logger.log_clean(Some(r"^app_")).unwrap();
+/// report.deleted.sort();
+/// report.skipped.sort_by(|a, b| a.0.cmp(&b.0));
+/// 
+/// assert_eq!(report.deleted, ["app_20240101.log", "app_20240102.log"]);
+/// assert_eq!(report.bytes_reclaimed, 350);
+/// let skipped: Vec<(&str, &SkipReason)> = report.skipped.iter().map(|(name, reason)| (name.as_str(), reason)).collect();
+/// assert_eq!(skipped.len(), 4);
+/// assert!(matches!(skipped[0], (name, SkipReason::Active) if name == current));
+/// assert!(matches!(skipped[1], ("app_20991231.log", SkipReason::TooNew)));
+/// assert!(matches!(skipped[2], ("app_archive.log", SkipReason::IsDir)));
+/// assert!(matches!(skipped[3], ("notes.txt", SkipReason::FilterMismatch)));
+/// ```
+#[derive(Debug, Default)]
+pub struct CleanReport {
+    /// Names of the files deleted, or moved into the archive by `log_clean_to`.
+    /// A name that isn't valid UTF-8 is reported as a lossy copy, here and in `skipped`
+    pub deleted: Vec<String>,
+    /// Names of the files left alone, with the reason
+    pub skipped: Vec<(String, SkipReason)>,
+    /// Total size of the deleted files
+    pub bytes_reclaimed: u64,
+    /// Subdirectories removed because cleaning left them empty, see `LoggerBuilder::clean_subdirectories`
+    pub removed_dirs: Vec<String>,
+    // The real paths behind `deleted`, which can differ from the names when they aren't UTF-8
+    pub(crate) deleted_paths: Vec<PathBuf>,
+    // A FileDeleted for each file actually deleted, for the logger to pass on
+    pub(crate) events: Vec<LogEvent>,
+}
+
+/// Why `log_clean` left a file where it was
+#[derive(Debug)]
+pub enum SkipReason {
+    /// The name didn't match the filter
+    FilterMismatch,
+    /// Newer than days_keep and within max_files and max_total_size
+    TooNew,
+    /// A directory, these are never cleaned
+    IsDir,
+    /// The file currently being written to
+    Active,
+    /// The size or modified time couldn't be read
+    Metadata(io::Error),
+    /// Cleaning by `CleanStrategy::ByFileNameDate` and the name doesn't hold a date in the file name format
+    NoDateInName,
+    /// Deleting or archiving the file failed
+    RemoveFailed(io::Error),
+}
+
+/// How `log_clean` decides how old a file is
+/// # Example
+/// Backup tools touching files don't matter when cleaning by the date in the name
+/// ```rust
+/// use d_logger::{test_support::ManualClock, CleanStrategy, Logger, SkipReason};
+/// use chrono::TimeZone;
+/// use std::time::{Duration, SystemTime};
+/// 
+/// let path = std::env::temp_dir().join("d_logger_clean_strategy");
+/// # let _ = std::fs::remove_dir_all(&path);
+/// let start = chrono::Local.with_ymd_and_hms(2024, 5, 1, 12, 0, 0).unwrap();
+/// let logger = Logger::builder()
+///     .path(&path)
+///     .file_name_format("app_%Y%m%d.log")
+///     .days_keep(Some(7))
+///     .clean_strategy(CleanStrategy::ByFileNameDate)
+///     .clock(ManualClock::new(start))
+///     .build()
+///     .unwrap();
+/// 
+/// let recent = (start - chrono::Duration::days(2)).format("app_%Y%m%d.log").to_string();
+/// let long_ago = SystemTime::from(start) - Duration::from_secs(86400 * 30);
+/// // Old by name but just touched, recent by name but with an old modified time, and a name with no date
+/// std::fs::write(path.join("app_20240101.log"), "old").unwrap();
+/// for name in [recent.as_str(), "app_latest.log"] {
+///     std::fs::write(path.join(name), "recent").unwrap();
+///     std::fs::File::options().append(true).open(path.join(name)).unwrap().set_modified(long_ago).unwrap();
+/// }
+/// 
+/// let report = logger.log_clean(Some("^app_")).unwrap();
+/// assert_eq!(report.deleted, ["app_20240101.log"]);
+/// assert!(path.join(&recent).exists());
+/// assert!(report.skipped.iter().any(|(name, reason)| name == "app_latest.log" && matches!(reason, SkipReason::NoDateInName)));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CleanStrategy {
+    /// Use the file's modified time
+    #[default]
+    ModifiedTime,
+    /// Read the date back out of the file name using `file_name_format` or a routed level's format, a file counts as written up to the end of that day.
+    /// Files whose names don't parse are left alone
+    ByFileNameDate,
+}
+
+impl fmt::Display for CleanStrategy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            CleanStrategy::ModifiedTime => "modified_time",
+            CleanStrategy::ByFileNameDate => "by_file_name_date",
+        })
+    }
+}
+
+/// A clean strategy by name in any case, with `-` allowed in place of `_`
+/// # Example
+/// ```rust
+/// use d_logger::CleanStrategy;
+/// 
+/// assert_eq!("By-File-Name-Date".parse::<CleanStrategy>().unwrap(), CleanStrategy::ByFileNameDate);
+/// for strategy in [CleanStrategy::ModifiedTime, CleanStrategy::ByFileNameDate] {
+///     assert_eq!(strategy.to_string().parse::<CleanStrategy>().unwrap(), strategy);
+/// }
+/// assert!("newest".parse::<CleanStrategy>().is_err());
+/// ```
+impl FromStr for CleanStrategy {
+    type Err = LoggerError;
+
+    fn from_str(s: &str) -> Result<CleanStrategy, LoggerError> {
+        match s.trim().to_ascii_lowercase().replace('-', "_").as_str() {
+            "modified_time" => Ok(CleanStrategy::ModifiedTime),
+            "by_file_name_date" => Ok(CleanStrategy::ByFileNameDate),
+            _ => Err(LoggerError::InvalidConfig(format!("\"{s}\" is not a clean strategy, expected modified_time or by_file_name_date"))),
+        }
+    }
+}
+
+// What the log cleaner removes and what it must leave alone
+pub(crate) struct CleanSettings {
+    pub(crate) days_keep: Option<u64>,
+    pub(crate) max_files: Option<usize>,
+    pub(crate) max_total_size: Option<u64>,
+    pub(crate) compress: bool,
+    // Size rotation is on, so "<name>.1" and so on are logs too
+    pub(crate) rotated: bool,
+    pub(crate) strategy: CleanStrategy,
+    pub(crate) file_name_formats: Vec<String>,
+    // The logger's current UTC offset, to turn dates in file names into times
+    pub(crate) offset: FixedOffset,
+    pub(crate) regex: Option<Regex>,
+    // Files being written to, never deleted
+    pub(crate) active: Vec<PathBuf>,
+    // Move files here instead of deleting them
+    pub(crate) archive: Option<PathBuf>,
+    // Only report what would be removed
+    pub(crate) dry_run: bool,
+    // How many levels of subdirectories to clean inside, and whether to remove those left empty
+    pub(crate) depth: usize,
+    pub(crate) remove_empty_dirs: bool,
+    // The time the clean runs at
+    pub(crate) now: SystemTime,
+    // When the current day started in the logger's time zone, in seconds since the epoch, for days_keep of 0
+    pub(crate) day_start: u64,
+}
+
+// Turn a chrono file name format into a regex matching the names it produces, e.g. "Log%d%m%y.log" to "^Log\d{2}\d{2}\d{2}\.log$".
+// Literal text is escaped, padding flags like "%-d" are allowed for and anything unrecognised matches any text
+pub(crate) fn format_to_regex(format: &str) -> String {
+    let mut pattern = String::from("^");
+    push_format_regex(&mut pattern, format);
+    pattern.push('$');
+    pattern
+}
+
+fn push_format_regex(pattern: &mut String, format: &str) {
+    let mut chars = format.chars().peekable();
+    let mut literal = String::new();
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            literal.push(c);
+            continue;
+        }
+        pattern.push_str(&regex::escape(&std::mem::take(&mut literal)));
+        // Padding flag, then the specifier itself
+        let pad = chars.next_if(|c| matches!(c, '-' | '_' | '0'));
+        let Some(spec) = chars.next() else {
+            break;
+        };
+        let digits = |n: usize| match pad {
+            Some('-') => format!(r"\d{{1,{n}}}"),
+            Some('_') => format!(r" *\d{{1,{n}}}"),
+            _ => format!(r"\d{{{n}}}"),
+        };
+        match spec {
+            '%' => pattern.push('%'),
+            'Y' | 'G' => pattern.push_str(&digits(4)),
+            'C' | 'y' | 'g' | 'm' | 'd' | 'H' | 'I' | 'M' | 'S' | 'U' | 'W' | 'V' => pattern.push_str(&digits(2)),
+            'e' | 'k' | 'l' => pattern.push_str(r" ?\d{1,2}"),
+            'j' => pattern.push_str(&digits(3)),
+            'u' | 'w' => pattern.push_str(r"\d"),
+            's' | 'f' => pattern.push_str(r"\d+"),
+            'b' | 'h' | 'a' => pattern.push_str("[A-Za-z]{3}"),
+            'B' | 'A' => pattern.push_str("[A-Za-z]+"),
+            'p' => pattern.push_str("(?:AM|PM)"),
+            'P' => pattern.push_str("(?:am|pm)"),
+            'z' => pattern.push_str(r"[+-]\d{4}"),
+            'F' => push_format_regex(pattern, "%Y-%m-%d"),
+            'D' | 'x' => push_format_regex(pattern, "%m/%d/%y"),
+            'T' | 'X' => push_format_regex(pattern, "%H:%M:%S"),
+            'R' => push_format_regex(pattern, "%H:%M"),
+            _ => pattern.push_str(".+?"),
+        }
+    }
+    pattern.push_str(&regex::escape(&literal));
+}
+
+// Compile the log cleaner's file name filter, an empty filter matches everything
+pub(crate) fn compile_filter(filter: Option<&str>) -> Result<Option<Regex>, LoggerError> {
+    match filter {
+        Some(file_filter) if !file_filter.is_empty() => Ok(Some(Regex::new(file_filter).map_err(LoggerError::InvalidFilter)?)),
+        _ => Ok(None),
+    }
+}
+
+// The log cleaner itself. Problems with single files are recorded in the report and cleaning carries on
+pub(crate) fn clean_directory(path: &Path, settings: &CleanSettings) -> Result<CleanReport, LoggerError> {
+    let paths = fs::read_dir(path).map_err(LoggerError::DirectoryRead)?;
+
+    if settings.days_keep.is_none() && settings.max_files.is_none() && settings.max_total_size.is_none() && !settings.compress {
+        return Ok(CleanReport::default());
+    }
+
+    let threshold = age_threshold(settings.now, settings.days_keep, settings.day_start);
+    let mut walk = Walk { report: CleanReport::default(), remaining: Vec::new(), dirs: Vec::new(), threshold };
+    collect(&mut walk, paths, path, None, settings.depth, settings);
+    let Walk { mut report, mut remaining, dirs, .. } = walk;
+
+    // Newest first, so the passes below remove from the end
+    remaining.sort_by_key(|file| std::cmp::Reverse(file.modified));
+
+    // Then keep only the newest max_files, the active files take their places first
+    if let Some(max_files) = settings.max_files
+        && remaining.len() > max_files {
+        let mut kept = remaining.iter().filter(|file| file.active).count();
+        remaining.retain(|file| {
+            if file.active || kept < max_files {
+                kept += usize::from(!file.active);
+                return true;
+            }
+            // Failures are already in the report, so the file leaves the list either way
+            file.dispose(settings, false, &mut report);
+            false
+        });
+    }
+
+    // Then remove the oldest until the total size is under the limit
+    if let Some(max_total_size) = settings.max_total_size {
+        let mut total: u64 = remaining.iter().map(|file| file.size).sum();
+        let mut removed = vec![false; remaining.len()];
+        for (i, file) in remaining.iter().enumerate().rev() {
+            if total <= max_total_size {
+                break;
+            }
+            if !file.active {
+                if file.dispose(settings, false, &mut report) {
+                    total -= file.size;
+                }
+                removed[i] = true;
+            }
+        }
+        let mut removed = removed.into_iter();
+        remaining.retain(|_| !removed.next().unwrap_or(false));
+    }
+
+    for file in remaining {
+        let reason = if file.active { SkipReason::Active } else { SkipReason::TooNew };
+        report.skipped.push((file.name, reason));
+    }
+
+    // Children come after their parents, so going backwards a parent emptied along with its children goes too.
+    // Only directories this clean took something out of are removed, and remove_dir refuses any with something left in
+    if settings.remove_empty_dirs && !settings.dry_run {
+        for (dir, name) in dirs.iter().rev() {
+            if report.deleted_paths.iter().any(|deleted| deleted.starts_with(dir)) && fs::remove_dir(dir).is_ok() {
+                report.removed_dirs.push(name.clone());
+            }
+        }
+    }
+    Ok(report)
+}
+
+// What a walk through the log directory has found so far
+struct Walk {
+    report: CleanReport,
+    // Matching files left after cleaning by age
+    remaining: Vec<CleanCandidate>,
+    // The subdirectories walked and their names in the report, parents before children
+    dirs: Vec<(PathBuf, String)>,
+    threshold: Option<u64>,
+}
+
+// Look at each file in a directory, deleting those too old straight away and keeping the rest for the passes by count and size.
+// Goes down into subdirectories while depth is left, names below the top are reported as "<dir>/<name>"
+fn collect(walk: &mut Walk, entries: fs::ReadDir, dir: &Path, prefix: Option<&str>, depth: usize, settings: &CleanSettings) {
+    for entry in entries.filter_map(Result::ok) {
+        // A name that isn't UTF-8 is matched and reported by its lossy form, the file itself is found by the real name
+        let os_name = entry.file_name();
+        let base = os_name.to_string_lossy().into_owned();
+        let file_name = match prefix {
+            Some(prefix) => format!("{prefix}/{base}"),
+            None => base.clone(),
+        };
+        let file_path = dir.join(&os_name);
+
+        // The file type doesn't follow symlinks, so a link to a directory is never walked and is skipped as a directory below
+        if depth > 0 && entry.file_type().is_ok_and(|file_type| file_type.is_dir()) {
+            match fs::read_dir(&file_path) {
+                Ok(entries) => {
+                    walk.dirs.push((file_path.clone(), file_name.clone()));
+                    collect(walk, entries, &file_path, Some(&file_name), depth - 1, settings);
+                }
+                Err(e) => walk.report.skipped.push((file_name, SkipReason::Metadata(e))),
+            }
+            continue;
+        }
+
+        // Compressed and rotated logs also match by the name they had before compressing or rotating
+        let mut original_name = base.as_str();
+        if settings.compress {
+            original_name = original_name.strip_suffix(".gz.tmp").or_else(|| original_name.strip_suffix(".gz")).unwrap_or(original_name);
+        }
+        if settings.rotated
+            && let Some((name, number)) = original_name.rsplit_once('.')
+            && !number.is_empty()
+            && number.bytes().all(|b| b.is_ascii_digit()) {
+            original_name = name;
+        }
+        if let Some(regex) = &settings.regex
+            && !regex.is_match(&base)
+            && !regex.is_match(original_name) {
+            walk.report.skipped.push((file_name, SkipReason::FilterMismatch));
+            continue;
+        }
+
+        if file_path.is_dir() {
+            walk.report.skipped.push((file_name, SkipReason::IsDir));
+            continue;
+        }
+
+        let metadata = match fs::metadata(&file_path) {
+            Ok(metadata) => metadata,
+            Err(e) => {
+                walk.report.skipped.push((file_name, SkipReason::Metadata(e)));
+                continue;
+            }
+        };
+
+        let modified_time = match settings.strategy {
+            CleanStrategy::ModifiedTime => match modified_secs(&metadata) {
+                Ok(modified_time) => modified_time,
+                Err(e) => {
+                    walk.report.skipped.push((file_name, SkipReason::Metadata(e)));
+                    continue;
+                }
+            },
+            CleanStrategy::ByFileNameDate => match settings.file_name_formats.iter().find_map(|format| name_date_end(original_name, format, settings.offset)) {
+                Some(date_end) => date_end,
+                None => {
+                    walk.report.skipped.push((file_name, SkipReason::NoDateInName));
+                    continue;
+                }
+            },
+        };
+
+        let candidate = CleanCandidate {
+            active: settings.active.contains(&file_path),
+            path: file_path,
+            name: file_name,
+            modified: modified_time,
+            size: metadata.len(),
+        };
+        // Half written by a compress that never finished, the original is still there
+        let unfinished = settings.compress && candidate.name.ends_with(".gz.tmp");
+        let expired = walk.threshold.is_some_and(|threshold| candidate.modified < threshold);
+        if (unfinished || expired) && !candidate.active {
+            candidate.dispose(settings, unfinished, &mut walk.report);
+            continue;
+        }
+        walk.remaining.push(candidate);
+    }
+}
+
+// A file's modified time in seconds since the epoch
+fn modified_secs(metadata: &fs::Metadata) -> io::Result<u64> {
+    let modified = metadata.modified()?;
+    Ok(modified.duration_since(UNIX_EPOCH).map_err(io::Error::other)?.as_secs())
+}
+
+// Parse the date back out of a log file name and give the end of that day in seconds since the epoch
+fn name_date_end(name: &str, file_name_format: &str, offset: FixedOffset) -> Option<u64> {
+    let mut parsed = Parsed::new();
+    chrono::format::parse(&mut parsed, name, StrftimeItems::new(file_name_format)).ok()?;
+    let date = parsed.to_naive_date().ok()?;
+    let end = date.succ_opt()?.and_time(NaiveTime::MIN).and_local_timezone(offset).single()?;
+    u64::try_from(end.timestamp()).ok()
+}
+
+// Files last modified before this many seconds since the epoch are too old to keep.
+// None keeps everything, including when days_keep reaches back before the epoch or the clock is set before 1970.
+// Keeping 0 days keeps only files written since the start of today
+fn age_threshold(now: SystemTime, days_keep: Option<u64>, day_start: u64) -> Option<u64> {
+    let current_time = now.duration_since(UNIX_EPOCH).ok()?.as_secs();
+    match days_keep? {
+        0 => Some(day_start),
+        days_keep => days_keep.checked_mul(SECS_1_DAY).and_then(|keep| current_time.checked_sub(keep)),
+    }
+}
+
+// A file the log cleaner may remove
+struct CleanCandidate {
+    path: PathBuf,
+    name: String,
+    modified: u64,
+    size: u64,
+    active: bool,
+}
+
+impl CleanCandidate {
+    // Delete the file, or move it into the archive directory if there is one.
+    // Records the result in the report, as deleted or as skipped with the error, and returns whether the file is gone
+    // Leftovers are always deleted, never archived. In a dry run nothing is touched and the file counts as removed
+    fn dispose(&self, settings: &CleanSettings, leftover: bool, report: &mut CleanReport) -> bool {
+        let (result, deleting) = match &settings.archive {
+            _ if settings.dry_run => (Ok(()), false),
+            Some(archive) if !leftover => (move_file(&self.path, &archive_path(archive, self.path.file_name().unwrap_or_default())), false),
+            _ => (fs::remove_file(&self.path), true),
+        };
+        match result {
+            Ok(()) => {
+                if deleting {
+                    let written = UNIX_EPOCH + Duration::from_secs(self.modified);
+                    let age = settings.now.duration_since(written).unwrap_or_default();
+                    report.events.push(LogEvent::FileDeleted { path: self.path.clone(), age });
+                }
+                report.deleted.push(self.name.clone());
+                report.deleted_paths.push(self.path.clone());
+                report.bytes_reclaimed += self.size;
+                true
+            }
+            Err(e) => {
+                report.skipped.push((self.name.clone(), SkipReason::RemoveFailed(e)));
+                false
+            }
+        }
+    }
+}
+
+// A free name for the file in the archive, adding ".1", ".2" and so on if it's already taken
+fn archive_path(archive: &Path, file_name: &OsStr) -> PathBuf {
+    let mut target = archive.join(file_name);
+    let mut number = 1;
+    while target.exists() {
+        let mut numbered = file_name.to_os_string();
+        numbered.push(format!(".{number}"));
+        target = archive.join(numbered);
+        number += 1;
+    }
+    target
+}
+
+// Move a file, copying it and deleting the original when a rename can't be done, e.g. across file systems.
+// The modified time is kept so the archived copy still shows when it was last written
+fn move_file(from: &Path, to: &Path) -> io::Result<()> {
+    if fs::rename(from, to).is_ok() {
+        return Ok(());
+    }
+    let modified = fs::metadata(from)?.modified()?;
+    fs::copy(from, to)?;
+    fs::File::options().write(true).open(to)?.set_modified(modified)?;
+    fs::remove_file(from)
+}
@@ -0,0 +1,29 @@
+use std::time::SystemTime;
+use chrono::{DateTime, Local};
+
+/// Where a logger gets the time from, for timestamps, dated file names and the ages `log_clean` compares.
+/// The default is `SystemClock`, set another with `LoggerBuilder::clock`, such as `test_support::ManualClock`.
+/// Sync intervals, rate limit windows and retry waits keep measuring real time
+pub trait Clock: Send + Sync {
+    /// The current time, which timestamps and file names are rendered from
+    fn now(&self) -> DateTime<Local>;
+
+    /// The current time for comparing with file modified times, the same moment as `now` by default
+    fn system_now(&self) -> SystemTime {
+        self.now().into()
+    }
+}
+
+/// The machine's own clock
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> DateTime<Local> {
+        Local::now()
+    }
+
+    fn system_now(&self) -> SystemTime {
+        SystemTime::now()
+    }
+}
@@ -0,0 +1,42 @@
+use std::{
+    ffi::OsString,
+    fs::{self, File},
+    io::{self, BufReader, BufWriter, Write},
+    path::{Path, PathBuf},
+};
+
+use flate2::{write::GzEncoder, Compression};
+
+use crate::{rotation::gzipped, LoggerError};
+
+// Gzip a finished log file next to itself and remove the original.
+// The data is written to "<name>.gz.tmp" first and only renamed once complete, so a crash never leaves a truncated ".gz".
+// The modified time is kept so cleaning by age still sees when the log was last written, and the permissions so it's no more readable
+pub(crate) fn compress(path: &Path) -> Result<PathBuf, LoggerError> {
+    let target = gzipped(path);
+    let mut temp = OsString::from(target.as_os_str());
+    temp.push(".tmp");
+    let temp = PathBuf::from(temp);
+
+    let result = (|| -> io::Result<()> {
+        let source = File::open(path)?;
+        let metadata = source.metadata()?;
+        let created = File::create(&temp)?;
+        created.set_permissions(metadata.permissions())?;
+        let mut encoder = GzEncoder::new(BufWriter::new(created), Compression::default());
+        io::copy(&mut BufReader::new(source), &mut encoder)?;
+        let mut out = encoder.finish()?;
+        out.flush()?;
+        let out = out.into_inner().map_err(|e| e.into_error())?;
+        out.sync_all()?;
+        out.set_modified(metadata.modified()?)?;
+        drop(out);
+        fs::rename(&temp, &target)
+    })();
+    if let Err(e) = result {
+        let _ = fs::remove_file(&temp);
+        return Err(LoggerError::Compress(e));
+    }
+    fs::remove_file(path).map_err(LoggerError::Compress)?;
+    Ok(target)
+}
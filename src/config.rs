@@ -0,0 +1,245 @@
+use std::{fmt, fs, path::{Path, PathBuf}, time::Duration};
+
+use serde::de::{self, Deserialize, Deserializer, MapAccess, Visitor};
+
+use crate::{toml, Level, Logger, LoggerBuilder, LoggerError, SyncPolicy};
+
+const FIELDS: &[&str] = &[
+    "path", "file_name_format", "line_date_format", "level", "days_keep", "max_file_size", "rotation_count", "sync_policy",
+];
+
+/// Logger settings kept outside the code, read from a TOML or JSON file with `Logger::from_config_file` or deserialized
+/// from any format serde supports and passed to `Logger::from_config`. Anything left out keeps the builder's default, and an unknown key is an
+/// error so typos don't go unnoticed. `level` is a level name such as `"info"`, and `sync_policy` is written as for
+/// `SyncPolicy::from_str` such as `"every_line"` or `"5m"`, or as `{ "every_n": 10 }` or `{ "interval_ms": 500 }`
+/// # Example
+/// ```rust
+/// use d_logger::{Level, Logger, LoggerConfig, LoggerError, SyncPolicy};
+/// 
+/// let dir = std::env::temp_dir().join("d_logger_config");
+/// # let _ = std::fs::remove_dir_all(&dir);
+/// # std::fs::create_dir_all(&dir).unwrap();
+/// let file = dir.join("logging.json");
+/// std::fs::write(&file, format!(r#"{{
+///     "path": {:?},
+///     "file_name_format": "app_%Y%m%d.log",
+///     "line_date_format": "%Y-%m-%d %H:%M:%S",
+///     "level": "info",
+///     "days_keep": 14,
+///     "max_file_size": 10485760,
+///     "rotation_count": 5,
+///     "sync_policy": {{ "every_n": 1 }}
+/// }}"#, dir.join("logs"))).unwrap();
+/// 
+/// let logger = Logger::from_config_file(&file).unwrap();
+/// assert_eq!(logger.days_keep(), Some(14));
+/// logger.info("configured");
+/// logger.debug("below the level");
+/// let contents = std::fs::read_to_string(logger.current_file_path()).unwrap();
+/// assert!(contents.contains("[INFO] configured") && !contents.contains("below the level"));
+/// 
+/// // A typo is caught rather than quietly ignored
+/// std::fs::write(&file, r#"{ "days_kept": 14 }"#).unwrap();
+/// let result = Logger::from_config_file(&file);
+/// assert!(matches!(result, Err(LoggerError::InvalidConfig(msg)) if msg.contains("unknown field `days_kept`")));
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LoggerConfig {
+    /// The log directory
+    pub path: Option<PathBuf>,
+    /// Date format for the file name
+    pub file_name_format: Option<String>,
+    /// Date format for lines
+    pub line_date_format: Option<String>,
+    /// Minimum level written by the level methods
+    pub level: Option<Level>,
+    /// Number of days log clean keeps
+    pub days_keep: Option<u64>,
+    /// Size in bytes a file can reach before it's rotated
+    pub max_file_size: Option<u64>,
+    /// Numbered copies kept when rotating by size
+    pub rotation_count: Option<usize>,
+    /// When entries are flushed and synced to disk
+    pub sync_policy: Option<SyncPolicy>,
+}
+
+impl From<LoggerConfig> for LoggerBuilder {
+    // The settings given, over the builder's defaults
+    fn from(config: LoggerConfig) -> LoggerBuilder {
+        let mut builder = LoggerBuilder::new().days_keep(config.days_keep);
+        if let Some(path) = config.path {
+            builder = builder.path(path);
+        }
+        if let Some(format) = config.file_name_format {
+            builder = builder.file_name_format(format);
+        }
+        if let Some(format) = config.line_date_format {
+            builder = builder.line_date_format(format);
+        }
+        if let Some(level) = config.level {
+            builder = builder.level(level);
+        }
+        if let Some(bytes) = config.max_file_size {
+            builder = builder.max_file_size(bytes);
+        }
+        if let Some(count) = config.rotation_count {
+            builder = builder.rotation_count(count);
+        }
+        if let Some(policy) = config.sync_policy {
+            builder = builder.sync_policy(policy);
+        }
+        builder
+    }
+}
+
+impl Logger {
+    /// Build a logger from settings read elsewhere, see `LoggerConfig`.
+    /// Use `LoggerBuilder::from(config)` instead to change anything else before building
+    pub fn from_config(config: LoggerConfig) -> Result<Logger, LoggerError> {
+        LoggerBuilder::from(config).build()
+    }
+
+    /// Build a logger from a file of settings, see `LoggerConfig` for the keys. A file ending in `.toml` is read as TOML
+    /// and anything else as JSON. The TOML reader is built in and covers what the settings need: strings, whole numbers,
+    /// booleans, comments, inline tables and a `[sync_policy]` table. Arrays, floats, dates, dotted keys and multi-line
+    /// strings are refused
+    /// # Returns
+    /// * `Err(LoggerError::ConfigRead)` if the file can't be read
+    /// * `Err(LoggerError::InvalidConfig)` if it isn't valid TOML or JSON, has an unknown key or a value of the wrong kind
+    /// * Any error `LoggerBuilder::build` gives
+    /// # Example
+    /// ```rust
+    /// use d_logger::{Logger, LoggerError};
+    /// 
+    /// let dir = std::env::temp_dir().join("d_logger_config_toml");
+    /// # let _ = std::fs::remove_dir_all(&dir);
+    /// # std::fs::create_dir_all(&dir).unwrap();
+    /// let file = dir.join("logging.toml");
+    /// std::fs::write(&file, format!(r#"
+    /// ## Logging for the staging servers
+    /// path = '{}'
+    /// file_name_format = "app_%Y%m%d.log"
+    /// line_date_format = "%Y-%m-%d %H:%M:%S"
+    /// level = "info"
+    /// days_keep = 14
+    /// max_file_size = 10_485_760
+    /// rotation_count = 5 # numbered copies
+    /// 
+    /// [sync_policy]
+    /// every_n = 1
+    /// "#, dir.join("logs").display())).unwrap();
+    /// 
+    /// let logger = Logger::from_config_file(&file).unwrap();
+    /// assert_eq!(logger.days_keep(), Some(14));
+    /// logger.info("configured from toml");
+    /// logger.debug("below the level");
+    /// let contents = std::fs::read_to_string(logger.current_file_path()).unwrap();
+    /// assert!(contents.contains("[INFO] configured from toml") && !contents.contains("below the level"));
+    /// 
+    /// // An inline table works as well as the [sync_policy] table
+    /// std::fs::write(&file, format!("path = '{}'\nsync_policy = {{ interval_ms = 500 }}", dir.join("logs").display())).unwrap();
+    /// assert!(Logger::from_config_file(&file).is_ok());
+    /// 
+    /// // Typos and syntax errors are caught, the latter with the line they're on
+    /// std::fs::write(&file, "days_kept = 14").unwrap();
+    /// let result = Logger::from_config_file(&file);
+    /// assert!(matches!(result, Err(LoggerError::InvalidConfig(msg)) if msg.contains("unknown field `days_kept`")));
+    /// std::fs::write(&file, "# retention\ndays_keep = [14]").unwrap();
+    /// let result = Logger::from_config_file(&file);
+    /// assert!(matches!(result, Err(LoggerError::InvalidConfig(msg)) if msg.contains("line 2 | arrays aren't supported")));
+    /// ```
+    pub fn from_config_file(path: impl AsRef<Path>) -> Result<Logger, LoggerError> {
+        let path = path.as_ref();
+        let text = fs::read_to_string(path).map_err(|e| LoggerError::ConfigRead { path: path.to_path_buf(), source: e })?;
+        let config = if path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("toml")) {
+            toml::parse(&text).and_then(|value| serde_json::from_value(value).map_err(|e| e.to_string()))
+        } else {
+            serde_json::from_str(&text).map_err(|e| e.to_string())
+        };
+        let config = config.map_err(|e| LoggerError::InvalidConfig(format!("{} | {e}", path.display())))?;
+        Logger::from_config(config)
+    }
+}
+
+// Written out rather than derived, so the feature doesn't need serde's proc macros
+impl<'de> Deserialize<'de> for LoggerConfig {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<LoggerConfig, D::Error> {
+        deserializer.deserialize_map(ConfigVisitor)
+    }
+}
+
+struct ConfigVisitor;
+
+impl<'de> Visitor<'de> for ConfigVisitor {
+    type Value = LoggerConfig;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("a table of logger settings")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<LoggerConfig, A::Error> {
+        let mut config = LoggerConfig::default();
+        while let Some(key) = map.next_key::<String>()? {
+            match key.as_str() {
+                "path" => config.path = Some(map.next_value()?),
+                "file_name_format" => config.file_name_format = Some(map.next_value()?),
+                "line_date_format" => config.line_date_format = Some(map.next_value()?),
+                "level" => config.level = Some(map.next_value::<LevelName>()?.0),
+                "days_keep" => config.days_keep = map.next_value()?,
+                "max_file_size" => config.max_file_size = Some(map.next_value()?),
+                "rotation_count" => config.rotation_count = Some(map.next_value()?),
+                "sync_policy" => config.sync_policy = Some(map.next_value::<SyncPolicyValue>()?.0),
+                other => return Err(de::Error::unknown_field(other, FIELDS)),
+            }
+        }
+        Ok(config)
+    }
+}
+
+// A level given by name, in any case
+struct LevelName(Level);
+
+impl<'de> Deserialize<'de> for LevelName {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<LevelName, D::Error> {
+        let name = String::deserialize(deserializer)?;
+        name.parse()
+            .map(LevelName)
+            .map_err(|_| de::Error::invalid_value(de::Unexpected::Str(&name), &"one of trace, debug, info, warn or error"))
+    }
+}
+
+// A sync policy given as a name, or a table for the ones that take a number
+struct SyncPolicyValue(SyncPolicy);
+
+impl<'de> Deserialize<'de> for SyncPolicyValue {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<SyncPolicyValue, D::Error> {
+        deserializer.deserialize_any(SyncPolicyVisitor)
+    }
+}
+
+struct SyncPolicyVisitor;
+
+impl<'de> Visitor<'de> for SyncPolicyVisitor {
+    type Value = SyncPolicyValue;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("a sync policy such as \"every_line\" or \"5m\", { \"every_n\": n } or { \"interval_ms\": n }")
+    }
+
+    fn visit_str<E: de::Error>(self, name: &str) -> Result<SyncPolicyValue, E> {
+        name.parse().map(SyncPolicyValue).map_err(|_| E::invalid_value(de::Unexpected::Str(name), &self))
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<SyncPolicyValue, A::Error> {
+        let policy = match map.next_key::<String>()?.as_deref() {
+            Some("every_n") => SyncPolicy::EveryN(map.next_value()?),
+            Some("interval_ms") => SyncPolicy::Interval(Duration::from_millis(map.next_value()?)),
+            Some(other) => return Err(de::Error::unknown_field(other, &["every_n", "interval_ms"])),
+            None => return Err(de::Error::invalid_length(0, &self)),
+        };
+        if let Some(other) = map.next_key::<String>()? {
+            return Err(de::Error::custom(format!("sync_policy takes one key, found `{other}` as well")));
+        }
+        Ok(SyncPolicyValue(policy))
+    }
+}
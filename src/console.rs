@@ -0,0 +1,77 @@
+use std::{fmt, io::{self, Write}, str::FromStr};
+
+use crate::{test_support::ConsoleCapture, LoggerError};
+
+/// Where each entry is echoed as well as the file, set with `.console()` on the builder or `set_console`.
+/// The echo is the same text that goes into the file, timestamp included
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum ConsoleMode {
+    /// Only write to the file
+    #[default]
+    Off,
+    /// Echo entries to standard output
+    Stdout,
+    /// Echo entries to standard error, which keeps them apart from a program's own output
+    Stderr,
+}
+
+impl ConsoleMode {
+    // Print a formatted entry, which already ends with a newline, or hand it to the capture standing in for the
+    // console. Failing to print is ignored, a closed console shouldn't stop the file being written
+    pub(crate) fn echo(self, entry: &str, capture: Option<&ConsoleCapture>) {
+        if let Some(capture) = capture {
+            capture.echo(self, entry);
+            return;
+        }
+        let _ = match self {
+            ConsoleMode::Off => Ok(()),
+            ConsoleMode::Stdout => io::stdout().lock().write_all(entry.as_bytes()),
+            ConsoleMode::Stderr => io::stderr().lock().write_all(entry.as_bytes()),
+        };
+    }
+}
+
+impl fmt::Display for ConsoleMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            ConsoleMode::Off => "off",
+            ConsoleMode::Stdout => "stdout",
+            ConsoleMode::Stderr => "stderr",
+        })
+    }
+}
+
+/// A console mode by name in any case
+/// # Example
+/// ```rust
+/// use d_logger::ConsoleMode;
+/// 
+/// assert_eq!("StdErr".parse::<ConsoleMode>().unwrap(), ConsoleMode::Stderr);
+/// for mode in [ConsoleMode::Off, ConsoleMode::Stdout, ConsoleMode::Stderr] {
+///     assert_eq!(mode.to_string().parse::<ConsoleMode>().unwrap(), mode);
+/// }
+/// assert!("screen".parse::<ConsoleMode>().unwrap_err().to_string().contains("off, stdout or stderr"));
+/// ```
+impl FromStr for ConsoleMode {
+    type Err = LoggerError;
+
+    fn from_str(s: &str) -> Result<ConsoleMode, LoggerError> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "off" => Ok(ConsoleMode::Off),
+            "stdout" => Ok(ConsoleMode::Stdout),
+            "stderr" => Ok(ConsoleMode::Stderr),
+            _ => Err(LoggerError::InvalidConfig(format!("\"{s}\" is not a console mode, expected one of off, stdout or stderr"))),
+        }
+    }
+}
+
+// Stored as a u8 so the logger can switch modes through a shared reference
+impl From<u8> for ConsoleMode {
+    fn from(value: u8) -> Self {
+        match value {
+            1 => ConsoleMode::Stdout,
+            2 => ConsoleMode::Stderr,
+            _ => ConsoleMode::Off,
+        }
+    }
+}
@@ -0,0 +1,85 @@
+use std::{sync::Mutex, time::{Duration, Instant}};
+
+use crate::Level;
+
+// Holds back repeats of the last message, set with LoggerBuilder::collapse_duplicates
+pub(crate) struct Dedup {
+    timeout: Duration,
+    last: Mutex<Option<Last>>,
+}
+
+struct Last {
+    level: Option<Level>,
+    line: String,
+    kvs: Vec<(String, String)>,
+    repeats: u64,
+    // When the repeats currently held back started
+    since: Instant,
+}
+
+impl Last {
+    fn is(&self, level: Option<Level>, line: &str, kvs: &[(&str, &str)]) -> bool {
+        self.level == level
+            && self.line == line
+            && self.kvs.len() == kvs.len()
+            && self.kvs.iter().zip(kvs).all(|((k, v), (key, value))| k == key && v == value)
+    }
+}
+
+impl Dedup {
+    pub(crate) fn new(timeout: Duration) -> Dedup {
+        Dedup { timeout, last: Mutex::new(None) }
+    }
+
+    // Gives back how many repeats to report before going on, if any, and whether the entry itself should be written
+    pub(crate) fn check(&self, level: Option<Level>, line: &str, kvs: &[(&str, &str)]) -> (Option<u64>, bool) {
+        let mut last = self.last.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(last) = last.as_mut()
+            && last.is(level, line, kvs) {
+            if last.repeats == 0 {
+                last.since = Instant::now();
+            }
+            last.repeats += 1;
+            // A message repeating for a long time is still reported every so often
+            if last.since.elapsed() >= self.timeout {
+                last.since = Instant::now();
+                return (Some(std::mem::take(&mut last.repeats)), false);
+            }
+            return (None, false);
+        }
+        let previous = last.replace(Last {
+            level,
+            line: line.to_string(),
+            kvs: kvs.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect(),
+            repeats: 0,
+            since: Instant::now(),
+        });
+        (previous.map(|p| p.repeats).filter(|&n| n > 0), true)
+    }
+
+    // Repeats held back that haven't been reported yet, forgetting the last message
+    pub(crate) fn take(&self) -> Option<u64> {
+        self.last.lock().unwrap_or_else(|e| e.into_inner()).take().map(|last| last.repeats).filter(|&n| n > 0)
+    }
+
+    // Repeats held back for at least the timeout, for writes that don't go through check.
+    // The message is kept, so it's still collapsed if it comes again
+    pub(crate) fn due(&self) -> Option<u64> {
+        let mut last = self.last.lock().unwrap_or_else(|e| e.into_inner());
+        let last = last.as_mut().filter(|last| last.repeats > 0 && last.since.elapsed() >= self.timeout)?;
+        Some(std::mem::take(&mut last.repeats))
+    }
+
+    // Repeats held back however long they've waited, keeping the message like due
+    pub(crate) fn pending(&self) -> Option<u64> {
+        let mut last = self.last.lock().unwrap_or_else(|e| e.into_inner());
+        let last = last.as_mut().filter(|last| last.repeats > 0)?;
+        Some(std::mem::take(&mut last.repeats))
+    }
+}
+
+// The line written in place of the repeats
+pub(crate) fn summary(repeats: u64) -> String {
+    let times = if repeats == 1 { "time" } else { "times" };
+    format!("last message repeated {repeats} {times}")
+}
@@ -0,0 +1,295 @@
+use std::sync::{atomic::{AtomicU8, Ordering}, Arc, Mutex};
+
+use chrono::{DateTime, Local};
+
+use crate::{format, CleanReport, Clock, Level, Logger, LoggerError, SystemClock};
+
+/// The logging calls application code makes, so a `Logger`, a `NoopLogger` or a `MemoryLogger` in tests can be passed
+/// in its place, e.g. as `&dyn DLog` or `Arc<dyn DLog>`. `Logger` keeps its own methods of the same names,
+/// so existing code doesn't need the trait in scope
+/// # Example
+/// ```rust
+/// use d_logger::{DLog, Level, Logger, MemoryLogger};
+/// use std::sync::Arc;
+/// 
+/// // Application code only knows it has something to log to
+/// fn process(orders: &[u32], log: &dyn DLog) {
+///     for order in orders {
+///         if *order == 0 {
+///             log.error("order with no id");
+///         } else {
+///             log.info(&format!("processed order {order}"));
+///         }
+///     }
+/// }
+/// 
+/// let memory = Arc::new(MemoryLogger::new());
+/// let log: Arc<dyn DLog> = memory.clone();
+/// process(&[7, 0], log.as_ref());
+/// let logged: Vec<(Option<Level>, String)> = memory.entries().into_iter().map(|e| (e.level, e.message)).collect();
+/// assert_eq!(logged, vec![
+///     (Some(Level::Info), "processed order 7".to_string()),
+///     (Some(Level::Error), "order with no id".to_string()),
+/// ]);
+/// 
+/// // The same code writes to a file in production
+/// let path = std::env::temp_dir().join("d_logger_dlog");
+/// let logger = Logger::builder().path(path).build().unwrap();
+/// process(&[7], &logger);
+/// ```
+pub trait DLog {
+    /// Write a line without a level, see `Logger::write_log`
+    fn write_log(&self, line: &str) -> bool;
+
+    /// Write a line followed by key=value pairs, see `Logger::write_log_kv`
+    fn write_log_kv(&self, msg: &str, kvs: &[(&str, &str)]) -> bool;
+
+    /// Write a line at a level, see `Logger::write_level`
+    fn write_level(&self, level: Level, line: &str) -> bool;
+
+    /// Write a line at Trace level
+    fn trace(&self, line: &str) -> bool {
+        self.write_level(Level::Trace, line)
+    }
+
+    /// Write a line at Debug level
+    fn debug(&self, line: &str) -> bool {
+        self.write_level(Level::Debug, line)
+    }
+
+    /// Write a line at Info level
+    fn info(&self, line: &str) -> bool {
+        self.write_level(Level::Info, line)
+    }
+
+    /// Write a line at Warn level
+    fn warn(&self, line: &str) -> bool {
+        self.write_level(Level::Warn, line)
+    }
+
+    /// Write a line at Error level
+    fn error(&self, line: &str) -> bool {
+        self.write_level(Level::Error, line)
+    }
+
+    /// Flush anything buffered, see `Logger::flush`
+    fn flush(&self) -> Result<(), LoggerError>;
+
+    /// Clean up old log files, see `Logger::log_clean`
+    fn log_clean(&self, filter: Option<&str>) -> Result<CleanReport, LoggerError>;
+}
+
+impl DLog for Logger {
+    fn write_log(&self, line: &str) -> bool {
+        Logger::write_log(self, line)
+    }
+
+    fn write_log_kv(&self, msg: &str, kvs: &[(&str, &str)]) -> bool {
+        Logger::write_log_kv(self, msg, kvs)
+    }
+
+    fn write_level(&self, level: Level, line: &str) -> bool {
+        Logger::write_level(self, level, line)
+    }
+
+    fn flush(&self) -> Result<(), LoggerError> {
+        Logger::flush(self)
+    }
+
+    fn log_clean(&self, filter: Option<&str>) -> Result<CleanReport, LoggerError> {
+        Logger::log_clean(self, filter)
+    }
+}
+
+/// Does nothing and always succeeds, for code that has to be given a logger when logging is off.
+/// `Logger::disabled` is the same for code that takes a `Logger`
+/// # Example
+/// ```rust
+/// use d_logger::{DLog, NoopLogger};
+/// 
+/// let log: &dyn DLog = &NoopLogger;
+/// assert!(log.write_log("nowhere"));
+/// assert!(log.error("nowhere"));
+/// log.flush().unwrap();
+/// assert!(log.log_clean(None).unwrap().deleted.is_empty());
+/// ```
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NoopLogger;
+
+impl DLog for NoopLogger {
+    fn write_log(&self, _line: &str) -> bool {
+        true
+    }
+
+    fn write_log_kv(&self, _msg: &str, _kvs: &[(&str, &str)]) -> bool {
+        true
+    }
+
+    fn write_level(&self, _level: Level, _line: &str) -> bool {
+        true
+    }
+
+    fn flush(&self) -> Result<(), LoggerError> {
+        Ok(())
+    }
+
+    fn log_clean(&self, _filter: Option<&str>) -> Result<CleanReport, LoggerError> {
+        Ok(CleanReport::default())
+    }
+}
+
+/// An entry kept by `MemoryLogger`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LogEntry {
+    /// When the entry was logged, from the logger's clock
+    pub ts: DateTime<Local>,
+    /// None for lines written without a level
+    pub level: Option<Level>,
+    /// The message, with any key value pairs added in logfmt style
+    pub message: String,
+}
+
+/// Keeps each entry in memory instead of writing a file, for asserting what code logged.
+/// Key value pairs are added to the message in logfmt style, as in the file. Lines below the minimum level are
+/// dropped the same way `Logger` drops them, and lines without a level are always kept. Safe to share between threads
+/// # Example
+/// ```rust
+/// use d_logger::{DLog, Level, MemoryLogger};
+/// 
+/// let log = MemoryLogger::new();
+/// log.write_log("started");
+/// log.warn("low disk");
+/// log.write_log_kv("login", &[("user", "bob")]);
+/// let entries = log.entries();
+/// assert_eq!(entries.len(), 3);
+/// assert_eq!((entries[0].level, entries[0].message.as_str()), (None, "started"));
+/// assert_eq!((entries[1].level, entries[1].message.as_str()), (Some(Level::Warn), "low disk"));
+/// assert_eq!((entries[2].level, entries[2].message.as_str()), (None, "login user=bob"));
+/// assert!(entries[0].ts <= entries[2].ts);
+/// assert!(log.contains("user=bob") && !log.contains("user=alice"));
+/// 
+/// // Below the minimum level is dropped, as in the file
+/// log.clear();
+/// log.set_level(Level::Warn);
+/// log.info("dropped");
+/// log.error("kept");
+/// log.write_log("no level, kept");
+/// let messages: Vec<String> = log.entries().into_iter().map(|e| e.message).collect();
+/// assert_eq!(messages, ["kept", "no level, kept"]);
+/// 
+/// // Several threads can log at once
+/// log.clear();
+/// std::thread::scope(|s| {
+///     for t in 0..4 {
+///         let log = &log;
+///         s.spawn(move || {
+///             for i in 0..25 {
+///                 log.error(&format!("thread {t} line {i}"));
+///             }
+///         });
+///     }
+/// });
+/// assert_eq!(log.entries().len(), 100);
+/// ```
+/// 
+/// With a `ManualClock` the timestamps can be checked exactly
+/// ```rust
+/// use d_logger::{test_support::ManualClock, DLog, MemoryLogger};
+/// use chrono::TimeZone;
+/// 
+/// let start = chrono::Local.with_ymd_and_hms(2024, 5, 1, 12, 0, 0).unwrap();
+/// let clock = ManualClock::new(start);
+/// let log = MemoryLogger::with_clock(clock.clone());
+/// log.info("first");
+/// clock.advance(std::time::Duration::from_secs(90));
+/// log.info("second");
+/// let entries = log.entries();
+/// assert_eq!(entries[0].ts, start);
+/// assert_eq!(entries[1].ts, chrono::Local.with_ymd_and_hms(2024, 5, 1, 12, 1, 30).unwrap());
+/// ```
+pub struct MemoryLogger {
+    entries: Mutex<Vec<LogEntry>>,
+    level: AtomicU8,
+    clock: Arc<dyn Clock>,
+}
+
+impl MemoryLogger {
+    pub fn new() -> MemoryLogger {
+        MemoryLogger::with_clock(SystemClock)
+    }
+
+    /// A memory logger that times its entries by the given clock
+    pub fn with_clock(clock: impl Clock + 'static) -> MemoryLogger {
+        MemoryLogger { entries: Mutex::new(Vec::new()), level: AtomicU8::new(Level::Trace as u8), clock: Arc::new(clock) }
+    }
+
+    /// A copy of the entries so far, oldest first
+    pub fn entries(&self) -> Vec<LogEntry> {
+        self.entries.lock().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    /// Forget the entries so far
+    pub fn clear(&self) {
+        self.entries.lock().unwrap_or_else(|e| e.into_inner()).clear();
+    }
+
+    /// Whether any entry's message contains the text
+    pub fn contains(&self, text: &str) -> bool {
+        self.entries.lock().unwrap_or_else(|e| e.into_inner()).iter().any(|entry| entry.message.contains(text))
+    }
+
+    /// Change the minimum level, Trace to start with, see `Logger::set_level`
+    pub fn set_level(&self, level: Level) {
+        self.level.store(level as u8, Ordering::Relaxed);
+    }
+
+    /// The minimum level entries are kept at
+    pub fn level(&self) -> Level {
+        Level::from(self.level.load(Ordering::Relaxed))
+    }
+
+    fn push(&self, level: Option<Level>, message: String) -> bool {
+        let entry = LogEntry { ts: self.clock.now(), level, message };
+        self.entries.lock().unwrap_or_else(|e| e.into_inner()).push(entry);
+        true
+    }
+}
+
+impl Default for MemoryLogger {
+    fn default() -> MemoryLogger {
+        MemoryLogger::new()
+    }
+}
+
+impl std::fmt::Debug for MemoryLogger {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("MemoryLogger").field("entries", &self.entries).field("level", &self.level()).finish_non_exhaustive()
+    }
+}
+
+impl DLog for MemoryLogger {
+    fn write_log(&self, line: &str) -> bool {
+        self.push(None, line.to_string())
+    }
+
+    fn write_log_kv(&self, msg: &str, kvs: &[(&str, &str)]) -> bool {
+        let mut message = msg.to_string();
+        format::push_logfmt(&mut message, kvs);
+        self.push(None, message)
+    }
+
+    fn write_level(&self, level: Level, line: &str) -> bool {
+        if level < self.level() {
+            return true;
+        }
+        self.push(Some(level), line.to_string())
+    }
+
+    fn flush(&self) -> Result<(), LoggerError> {
+        Ok(())
+    }
+
+    fn log_clean(&self, _filter: Option<&str>) -> Result<CleanReport, LoggerError> {
+        Ok(CleanReport::default())
+    }
+}
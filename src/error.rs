@@ -0,0 +1,126 @@
+use std::{error::Error, fmt, io, path::PathBuf};
+
+/// Errors returned by the logger.
+/// Each variant keeps the underlying error so the cause (permissions, disk full, bad path...) isn't lost.
+#[derive(Debug)]
+pub enum LoggerError {
+    /// The log directory did not exist and could not be created
+    DirectoryCreate(io::Error),
+    /// The log path exists but is not a directory, or is empty
+    NotADirectory { path: PathBuf },
+    /// The log directory exists but files can't be created in it
+    DirectoryNotWritable { path: PathBuf, source: io::Error },
+    /// The log directory could not be read while cleaning
+    DirectoryRead(io::Error),
+    /// The dated log file could not be opened for appending
+    FileOpen { path: PathBuf, source: io::Error },
+    /// Writing the entry to the log file failed
+    Write(io::Error),
+    /// Flushing or syncing the log file to disk failed
+    Flush(io::Error),
+    /// The lock on the log file asked for by `use_file_lock` could not be taken
+    FileLock(io::Error),
+    /// Renaming or removing files while rotating the log by size failed
+    Rotate(io::Error),
+    /// Gzipping a finished log file failed, the original is left in place
+    #[cfg(feature = "compress")]
+    Compress(io::Error),
+    /// The "latest" link to the current log file could not be updated
+    LatestLink(io::Error),
+    /// The regex filter given to the log cleaner did not compile
+    InvalidFilter(regex::Error),
+    /// The pattern given to `add_redaction` did not compile
+    InvalidRedaction(regex::Error),
+    /// The pattern given to `Logger::search` did not compile
+    InvalidSearch(regex::Error),
+    /// A date format string could not be used, the message says which one and why
+    InvalidFormat(String),
+    /// A configuration file could not be read
+    #[cfg(feature = "config")]
+    ConfigRead { path: PathBuf, source: io::Error },
+    /// Settings from a configuration file or the environment could not be used, the message says which and why
+    InvalidConfig(String),
+    /// The log path refers to an environment variable that isn't set, or to ~ with no home directory
+    MissingVariable(String),
+    /// The background writer thread has been shut down, so the entry was not queued
+    WriterStopped,
+    /// `init` was called when a global logger had already been installed
+    AlreadyInitialized,
+}
+
+impl LoggerError {
+    // A copy to hand out from last_error, io errors keep their kind and message but not their source
+    pub(crate) fn duplicate(&self) -> LoggerError {
+        let io = |e: &io::Error| io::Error::new(e.kind(), e.to_string());
+        match self {
+            LoggerError::DirectoryCreate(e) => LoggerError::DirectoryCreate(io(e)),
+            LoggerError::NotADirectory { path } => LoggerError::NotADirectory { path: path.clone() },
+            LoggerError::DirectoryNotWritable { path, source } => LoggerError::DirectoryNotWritable { path: path.clone(), source: io(source) },
+            LoggerError::DirectoryRead(e) => LoggerError::DirectoryRead(io(e)),
+            LoggerError::FileOpen { path, source } => LoggerError::FileOpen { path: path.clone(), source: io(source) },
+            LoggerError::Write(e) => LoggerError::Write(io(e)),
+            LoggerError::Flush(e) => LoggerError::Flush(io(e)),
+            LoggerError::FileLock(e) => LoggerError::FileLock(io(e)),
+            LoggerError::Rotate(e) => LoggerError::Rotate(io(e)),
+            #[cfg(feature = "compress")]
+            LoggerError::Compress(e) => LoggerError::Compress(io(e)),
+            LoggerError::LatestLink(e) => LoggerError::LatestLink(io(e)),
+            LoggerError::InvalidFilter(e) => LoggerError::InvalidFilter(e.clone()),
+            LoggerError::InvalidRedaction(e) => LoggerError::InvalidRedaction(e.clone()),
+            LoggerError::InvalidSearch(e) => LoggerError::InvalidSearch(e.clone()),
+            LoggerError::InvalidFormat(msg) => LoggerError::InvalidFormat(msg.clone()),
+            #[cfg(feature = "config")]
+            LoggerError::ConfigRead { path, source } => LoggerError::ConfigRead { path: path.clone(), source: io(source) },
+            LoggerError::InvalidConfig(msg) => LoggerError::InvalidConfig(msg.clone()),
+            LoggerError::MissingVariable(name) => LoggerError::MissingVariable(name.clone()),
+            LoggerError::WriterStopped => LoggerError::WriterStopped,
+            LoggerError::AlreadyInitialized => LoggerError::AlreadyInitialized,
+        }
+    }
+}
+
+impl fmt::Display for LoggerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LoggerError::DirectoryCreate(e) => write!(f, "could not create log directory | {e}"),
+            LoggerError::NotADirectory { path } if path.as_os_str().is_empty() => f.write_str("log path is empty"),
+            LoggerError::NotADirectory { path } => write!(f, "log path {} is not a directory", path.display()),
+            LoggerError::DirectoryNotWritable { path, source } => write!(f, "log directory {} is not writable | {source}", path.display()),
+            LoggerError::DirectoryRead(e) => write!(f, "could not read log directory | {e}"),
+            LoggerError::FileOpen { path, source } => write!(f, "could not open log file {} | {source}", path.display()),
+            LoggerError::Write(e) => write!(f, "could not write to log file | {e}"),
+            LoggerError::Flush(e) => write!(f, "could not flush log file | {e}"),
+            LoggerError::FileLock(e) => write!(f, "could not lock log file | {e}"),
+            LoggerError::Rotate(e) => write!(f, "could not rotate log file | {e}"),
+            #[cfg(feature = "compress")]
+            LoggerError::Compress(e) => write!(f, "could not compress log file | {e}"),
+            LoggerError::LatestLink(e) => write!(f, "could not update latest log link | {e}"),
+            LoggerError::InvalidFilter(e) => write!(f, "invalid log clean filter | {e}"),
+            LoggerError::InvalidRedaction(e) => write!(f, "invalid redaction pattern | {e}"),
+            LoggerError::InvalidSearch(e) => write!(f, "invalid search pattern | {e}"),
+            LoggerError::InvalidFormat(msg) => write!(f, "invalid format | {msg}"),
+            #[cfg(feature = "config")]
+            LoggerError::ConfigRead { path, source } => write!(f, "could not read logger config {} | {source}", path.display()),
+            LoggerError::InvalidConfig(msg) => write!(f, "invalid logger config | {msg}"),
+            LoggerError::MissingVariable(name) => write!(f, "log path uses {name} which is not set"),
+            LoggerError::WriterStopped => write!(f, "background writer has stopped"),
+            LoggerError::AlreadyInitialized => write!(f, "global logger is already initialized"),
+        }
+    }
+}
+
+impl Error for LoggerError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            LoggerError::DirectoryCreate(e) | LoggerError::DirectoryRead(e) => Some(e),
+            LoggerError::FileOpen { source, .. } | LoggerError::DirectoryNotWritable { source, .. } => Some(source),
+            LoggerError::Write(e) | LoggerError::Flush(e) | LoggerError::FileLock(e) | LoggerError::Rotate(e) | LoggerError::LatestLink(e) => Some(e),
+            #[cfg(feature = "compress")]
+            LoggerError::Compress(e) => Some(e),
+            #[cfg(feature = "config")]
+            LoggerError::ConfigRead { source, .. } => Some(source),
+            LoggerError::InvalidFilter(e) | LoggerError::InvalidRedaction(e) | LoggerError::InvalidSearch(e) => Some(e),
+            LoggerError::NotADirectory { .. } | LoggerError::InvalidFormat(_) | LoggerError::InvalidConfig(_) | LoggerError::MissingVariable(_) | LoggerError::WriterStopped | LoggerError::AlreadyInitialized => None,
+        }
+    }
+}
@@ -0,0 +1,135 @@
+use std::{
+    cell::Cell,
+    panic::{self, AssertUnwindSafe},
+    path::PathBuf,
+    sync::{atomic::{AtomicBool, AtomicU64, Ordering}, Arc, Mutex, RwLock},
+    time::Duration,
+};
+
+use crate::LoggerError;
+
+/// Something that happened to the log files, handed to the callbacks added with `Logger::on_event`.
+/// Events are collected while the logger holds its locks and handed out once the write or clean that caused them
+/// has let go, so a callback can use the logger itself
+/// # Example
+/// ```rust
+/// use d_logger::{test_support::ManualClock, LogEvent, Logger};
+/// use chrono::TimeZone;
+/// use std::sync::{Arc, Mutex};
+/// use std::time::{Duration, SystemTime};
+/// 
+/// let path = std::env::temp_dir().join("d_logger_log_event");
+/// # let _ = std::fs::remove_dir_all(&path);
+/// let start = chrono::Local.with_ymd_and_hms(2024, 5, 1, 12, 0, 0).unwrap();
+/// let logger = Logger::builder().path(&path).file_name_format("event.log").line_date_format("").max_file_size(10).rotation_count(1).days_keep(Some(7)).clock(ManualClock::new(start)).build().unwrap();
+/// let seen = Arc::new(Mutex::new(Vec::new()));
+/// let recorded = seen.clone();
+/// logger.on_event(move |event| recorded.lock().unwrap().push(event));
+/// 
+/// // The second entry goes over the size limit and rotates, the third drops the first copy off the end
+/// for word in ["first", "second", "third"] {
+///     logger.write_log(&format!("{word} line"));
+/// }
+/// let old = path.join("old.log");
+/// std::fs::write(&old, "old").unwrap();
+/// std::fs::File::options().append(true).open(&old).unwrap().set_modified(SystemTime::from(start) - Duration::from_secs(86400 * 30)).unwrap();
+/// logger.log_clean(Some(r"^old\.log$")).unwrap();
+/// 
+/// let events = seen.lock().unwrap();
+/// let (file, rotated) = (path.join("event.log"), path.join("event.log.1"));
+/// assert_eq!(events.len(), 4);
+/// assert!(matches!(&events[0], LogEvent::FileRotated { from, to } if *from == file && *to == rotated));
+/// assert!(matches!(&events[1], LogEvent::FileDeleted { path, .. } if *path == rotated));
+/// assert!(matches!(&events[2], LogEvent::FileRotated { from, to } if *from == file && *to == rotated));
+/// assert!(matches!(&events[3], LogEvent::FileDeleted { path, age } if *path == old && *age >= Duration::from_secs(86400 * 29)));
+/// ```
+#[derive(Debug)]
+pub enum LogEvent {
+    /// Size rotation moved the full file aside, `to` is "<name>.1"
+    FileRotated { from: PathBuf, to: PathBuf },
+    /// The log cleaner deleted a file, or rotation dropped the oldest numbered copy.
+    /// `age` is how long ago the file was last written, by the date in its name with `CleanStrategy::ByFileNameDate`
+    FileDeleted { path: PathBuf, age: Duration },
+    /// A finished file was gzipped, `path` is the new ".gz" file
+    FileCompressed { path: PathBuf },
+    /// A finished file could not be gzipped and is left as it was at `path`
+    #[cfg(feature = "compress")]
+    CompressFailed { path: PathBuf, error: LoggerError },
+    /// Entries could not be written to a file and were lost
+    WriteFailed { error: LoggerError },
+    /// The link from `LoggerBuilder::create_latest_link` could not be pointed at a new file.
+    /// The entries were written, the link is left as it was
+    LinkFailed { error: LoggerError },
+    /// Writes moved on to a differently named main file, at midnight or when the name format or directory changed.
+    /// Sent by the first write to the new file, not by the first write the logger makes
+    FileChanged { from: PathBuf, to: PathBuf },
+}
+
+impl Clone for LogEvent {
+    fn clone(&self) -> LogEvent {
+        match self {
+            LogEvent::FileRotated { from, to } => LogEvent::FileRotated { from: from.clone(), to: to.clone() },
+            LogEvent::FileDeleted { path, age } => LogEvent::FileDeleted { path: path.clone(), age: *age },
+            LogEvent::FileCompressed { path } => LogEvent::FileCompressed { path: path.clone() },
+            #[cfg(feature = "compress")]
+            LogEvent::CompressFailed { path, error } => LogEvent::CompressFailed { path: path.clone(), error: error.duplicate() },
+            LogEvent::WriteFailed { error } => LogEvent::WriteFailed { error: error.duplicate() },
+            LogEvent::LinkFailed { error } => LogEvent::LinkFailed { error: error.duplicate() },
+            LogEvent::FileChanged { from, to } => LogEvent::FileChanged { from: from.clone(), to: to.clone() },
+        }
+    }
+}
+
+pub(crate) type EventHandler = Arc<dyn Fn(LogEvent) + Send + Sync>;
+
+thread_local! {
+    // Set while this thread is running callbacks, so events from a callback's own writes wait for the next dispatch
+    static DISPATCHING: Cell<bool> = const { Cell::new(false) };
+}
+
+// The callbacks added with Logger::on_event and the events waiting for them
+#[derive(Default)]
+pub(crate) struct Events {
+    handlers: RwLock<Vec<EventHandler>>,
+    // Nothing is queued until there's a callback to take it
+    listening: AtomicBool,
+    queue: Mutex<Vec<LogEvent>>,
+    queued: AtomicBool,
+    // Callbacks that panicked, one for each event
+    pub(crate) panics: AtomicU64,
+}
+
+impl Events {
+    pub(crate) fn add(&self, handler: EventHandler) {
+        self.handlers.write().unwrap_or_else(|e| e.into_inner()).push(handler);
+        self.listening.store(true, Ordering::Release);
+    }
+
+    // Hold an event until dispatch, safe to call with the logger's locks held
+    pub(crate) fn emit(&self, event: LogEvent) {
+        if !self.listening.load(Ordering::Acquire) {
+            return;
+        }
+        self.queue.lock().unwrap_or_else(|e| e.into_inner()).push(event);
+        self.queued.store(true, Ordering::Release);
+    }
+
+    // Hand every queued event to each callback in the order they were added. Only call with none of the logger's locks held.
+    // A panicking callback is counted and the rest still run
+    pub(crate) fn dispatch(&self) {
+        if DISPATCHING.get() || !self.queued.swap(false, Ordering::AcqRel) {
+            return;
+        }
+        let events = std::mem::take(&mut *self.queue.lock().unwrap_or_else(|e| e.into_inner()));
+        let handlers = self.handlers.read().unwrap_or_else(|e| e.into_inner()).clone();
+        DISPATCHING.set(true);
+        for event in events {
+            for handler in &handlers {
+                if panic::catch_unwind(AssertUnwindSafe(|| handler(event.clone()))).is_err() {
+                    self.panics.fetch_add(1, Ordering::Relaxed);
+                }
+            }
+        }
+        DISPATCHING.set(false);
+    }
+}
@@ -0,0 +1,130 @@
+use std::{ffi::OsStr, io, iter, os::windows::ffi::OsStrExt, ptr};
+
+use windows_sys::Win32::System::EventLog::{
+    DeregisterEventSource, RegisterEventSourceW, ReportEventW, EVENTLOG_ERROR_TYPE, EVENTLOG_INFORMATION_TYPE,
+    EVENTLOG_WARNING_TYPE,
+};
+
+use crate::{format, sink::Record, Level, Logger, Sink};
+
+/// Forwards entries at or above a level to the Windows Event Log, Warn and above by default.
+/// Error entries become error events, Warn warnings and anything lower information events.
+/// Windows only, with the `windows-eventlog` feature
+/// # Example
+/// ```rust
+/// use d_logger::{EventLogSink, Level, Logger};
+/// 
+/// let path = std::env::temp_dir().join("d_logger_event_log");
+/// let logger = Logger::builder().path(&path).build().unwrap();
+/// let sink = EventLogSink::new("d_logger test").unwrap().min_level(Level::Error);
+/// logger.add_sink(Box::new(sink));
+/// 
+/// logger.error("written to the event log and the file");
+/// logger.warn("below the threshold, only in the file");
+/// assert_eq!(logger.sink_errors(), 0);
+/// ```
+#[derive(Debug)]
+pub struct EventLogSink {
+    handle: EventSource,
+    min_level: Level,
+}
+
+// The handle from RegisterEventSourceW, the event log functions can be called on it from any thread
+#[derive(Debug)]
+struct EventSource(windows_sys::Win32::Foundation::HANDLE);
+
+unsafe impl Send for EventSource {}
+unsafe impl Sync for EventSource {}
+
+impl Drop for EventSource {
+    fn drop(&mut self) {
+        unsafe {
+            DeregisterEventSource(self.0);
+        }
+    }
+}
+
+// Null terminated UTF-16 for the wide Windows functions
+fn wide(value: &str) -> Vec<u16> {
+    OsStr::new(value).encode_wide().chain(iter::once(0)).collect()
+}
+
+impl EventLogSink {
+    /// Register the event source on the local machine
+    /// # Arguments
+    /// * `source` - The source name events are shown under
+    /// # Returns
+    /// * `Err(io::Error)` if the source couldn't be registered
+    pub fn new(source: &str) -> io::Result<Self> {
+        let source = wide(source);
+        let handle = unsafe { RegisterEventSourceW(ptr::null(), source.as_ptr()) };
+        if handle.is_null() {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(EventLogSink { handle: EventSource(handle), min_level: Level::Warn })
+    }
+
+    /// The lowest level forwarded to the event log
+    pub fn min_level(mut self, level: Level) -> Self {
+        self.min_level = level;
+        self
+    }
+}
+
+impl Sink for EventLogSink {
+    fn write_entry(&self, formatted: &str) -> io::Result<()> {
+        self.report(EVENTLOG_INFORMATION_TYPE, formatted.trim_end())
+    }
+
+    fn write_record(&self, record: &Record) -> io::Result<()> {
+        // Lines without a level aren't forwarded, they're never critical
+        let Some(level) = record.level.filter(|level| *level >= self.min_level) else {
+            return Ok(());
+        };
+        let event_type = match level {
+            Level::Error => EVENTLOG_ERROR_TYPE,
+            Level::Warn => EVENTLOG_WARNING_TYPE,
+            _ => EVENTLOG_INFORMATION_TYPE,
+        };
+        let mut msg = record.message.to_string();
+        format::push_logfmt(&mut msg, record.kvs);
+        self.report(event_type, &msg)
+    }
+}
+
+impl EventLogSink {
+    fn report(&self, event_type: u16, msg: &str) -> io::Result<()> {
+        let msg = wide(msg);
+        let strings = [msg.as_ptr()];
+        let reported = unsafe {
+            ReportEventW(self.handle.0, event_type, 0, 0, ptr::null_mut(), 1, 0, strings.as_ptr(), ptr::null())
+        };
+        if reported == 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+}
+
+impl Logger {
+    /// Register an event source and add it as a sink, see `EventLogSink`.
+    /// If the source can't be registered the logger carries on writing only to the file,
+    /// and a single warning line saying why is written there
+    /// # Arguments
+    /// * `source` - The source name events are shown under
+    /// * `min_level` - The lowest level forwarded to the event log
+    /// # Returns
+    /// * `true` if the event log sink was added
+    pub fn add_event_log_sink(&self, source: &str, min_level: Level) -> bool {
+        match EventLogSink::new(source) {
+            Ok(sink) => {
+                self.add_sink(Box::new(sink.min_level(min_level)));
+                true
+            }
+            Err(e) => {
+                self.write_log(&format!("Warning = Event log, could not register source {source}, logging to file only | {e}"));
+                false
+            }
+        }
+    }
+}
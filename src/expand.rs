@@ -0,0 +1,36 @@
+use std::{env, path::{Path, PathBuf}};
+
+use crate::LoggerError;
+
+// Resolve a leading ~ to the home directory and ${VAR} or %VAR% to environment variables, set with LoggerBuilder::expand_path.
+// A path that isn't valid UTF-8 is left as it is, as is a $ or % that doesn't start a complete reference
+pub(crate) fn expand_path(path: &Path) -> Result<PathBuf, LoggerError> {
+    let Some(text) = path.to_str() else {
+        return Ok(path.to_path_buf());
+    };
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    if let Some(after) = rest.strip_prefix('~')
+        && (after.is_empty() || after.starts_with(['/', '\\'])) {
+        let home = env::home_dir().ok_or_else(|| LoggerError::MissingVariable("HOME".to_string()))?;
+        out.push_str(&home.to_string_lossy());
+        rest = after;
+    }
+    while let Some(start) = rest.find(['$', '%']) {
+        out.push_str(&rest[..start]);
+        let reference = &rest[start..];
+        let (name, len) = reference.strip_prefix("${").and_then(|r| r.find('}').map(|end| (&r[..end], end + 3)))
+            .or_else(|| reference.strip_prefix('%').and_then(|r| r.find('%').map(|end| (&r[..end], end + 2))))
+            .unwrap_or_default();
+        if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '(' || c == ')') {
+            out.push_str(&reference[..1]);
+            rest = &reference[1..];
+            continue;
+        }
+        let value = env::var(name).map_err(|_| LoggerError::MissingVariable(name.to_string()))?;
+        out.push_str(&value);
+        rest = &reference[len..];
+    }
+    out.push_str(rest);
+    Ok(PathBuf::from(out))
+}
@@ -0,0 +1,44 @@
+use std::{path::{Path, PathBuf}, sync::{atomic::{AtomicBool, Ordering}, Mutex}, time::{Duration, Instant}};
+
+// A second directory written to while the main one can't be, set with LoggerBuilder::fallback_path
+pub(crate) struct Fallback {
+    pub(crate) path: PathBuf,
+    retry_interval: Duration,
+    active: AtomicBool,
+    // When writing to the main directory was last tried
+    last_try: Mutex<Instant>,
+}
+
+impl Fallback {
+    pub(crate) fn new(path: PathBuf, retry_interval: Duration) -> Fallback {
+        Fallback { path, retry_interval, active: AtomicBool::new(false), last_try: Mutex::new(Instant::now()) }
+    }
+
+    // The same dated file name in the fallback directory
+    pub(crate) fn file_for(&self, log_file_name: &Path) -> PathBuf {
+        match log_file_name.file_name() {
+            Some(name) => self.path.join(name),
+            None => self.path.clone(),
+        }
+    }
+
+    pub(crate) fn is_active(&self) -> bool {
+        self.active.load(Ordering::Relaxed)
+    }
+
+    // Switch to or from the fallback, giving back whether that changed anything
+    pub(crate) fn set_active(&self, active: bool) -> bool {
+        *self.last_try.lock().unwrap_or_else(|e| e.into_inner()) = Instant::now();
+        self.active.swap(active, Ordering::Relaxed) != active
+    }
+
+    // Whether it's time to try the main directory again, claiming the try if so
+    pub(crate) fn retry_due(&self) -> bool {
+        let mut last_try = self.last_try.lock().unwrap_or_else(|e| e.into_inner());
+        if last_try.elapsed() < self.retry_interval {
+            return false;
+        }
+        *last_try = Instant::now();
+        true
+    }
+}
@@ -0,0 +1,458 @@
+// Formatting entries into text, built in formats and the formatter hook
+use std::{borrow::Cow, cell::Cell, fmt::{self, Write}};
+use chrono::{DateTime, FixedOffset, Local};
+use crate::{render_date, Facility, Level, LoggerError};
+
+/// Turns an entry into the text written to the file. The logger appends the newline itself.
+/// Set one on the builder with `formatter` to take full control of the line layout, it replaces the output format.
+/// Key value pairs from `write_log_kv` are already appended to `msg` in logfmt style,
+/// and if sequence numbers are on the message starts with the "#000123" token.
+pub trait LineFormatter {
+    /// Format a single entry
+    /// # Arguments
+    /// * `ts` - When the entry was written, in the logger's time zone
+    /// * `level` - The level, None for plain `write_log` calls
+    /// * `msg` - The message
+    fn format(&self, ts: &DateTime<FixedOffset>, level: Option<Level>, msg: &str) -> String;
+}
+
+/// The logger's built in layout with no separator, "<time><message>" or "<time>[LEVEL] <message>" for the level methods.
+/// Put any spacing wanted at the end of the date format
+/// # Example
+/// ```rust
+/// use d_logger::{DefaultFormatter, LineFormatter, Level};
+/// use chrono::TimeZone;
+/// 
+/// let ts = chrono::FixedOffset::east_opt(0).unwrap().with_ymd_and_hms(2024, 1, 1, 10, 0, 0).unwrap();
+/// let formatter = DefaultFormatter::new("%Y-%m-%d %H:%M:%S ").unwrap();
+/// assert_eq!(formatter.format(&ts, None, "message"), "2024-01-01 10:00:00 message");
+/// assert_eq!(formatter.format(&ts, Some(Level::Info), "message"), "2024-01-01 10:00:00 [INFO] message");
+/// ```
+#[derive(Debug, Clone)]
+pub struct DefaultFormatter {
+    line_date_format: String,
+}
+
+impl DefaultFormatter {
+    /// Create the formatter with the date format for lines
+    /// # Returns
+    /// * `Err(LoggerError::InvalidFormat)` if the date format can't be rendered
+    pub fn new(line_date_format: impl Into<String>) -> Result<Self, LoggerError> {
+        let line_date_format = line_date_format.into();
+        render_date(&Local::now(), &line_date_format, "line_date_format")?;
+        Ok(DefaultFormatter { line_date_format })
+    }
+}
+
+impl LineFormatter for DefaultFormatter {
+    fn format(&self, ts: &DateTime<FixedOffset>, level: Option<Level>, msg: &str) -> String {
+        plain(&render_date(ts, &self.line_date_format, "line_date_format").unwrap_or_default(), "", level, msg)
+    }
+}
+
+/// Alternative layout with the timestamp and level in brackets, "[<time>] [LEVEL] <message>"
+/// # Example
+/// ```rust
+/// use d_logger::{BracketFormatter, Level, Logger};
+/// 
+/// let path = std::env::temp_dir().join("d_logger_bracket_formatter");
+/// let logger = Logger::builder()
+///     .path(&path)
+///     .file_name_format("bracket_%Y%m%d.log")
+///     .formatter(Box::new(BracketFormatter::new("%H:%M:%S").unwrap()))
+///     .build()
+///     .unwrap();
+/// # let file = path.join(chrono::Local::now().format("bracket_%Y%m%d.log").to_string());
+/// # let _ = std::fs::remove_file(&file);
+/// logger.write_log("no level");
+/// logger.error("went wrong");
+/// 
+/// let contents = std::fs::read_to_string(&file).unwrap();
+/// let lines: Vec<&str> = contents.lines().collect();
+/// assert!(lines[0].starts_with('[') && lines[0].ends_with("] no level"));
+/// assert!(lines[1].starts_with('[') && lines[1].ends_with("] [ERROR] went wrong"));
+/// ```
+#[derive(Debug, Clone)]
+pub struct BracketFormatter {
+    line_date_format: String,
+}
+
+impl BracketFormatter {
+    /// Create the formatter with the date format for the bracketed timestamp
+    /// # Returns
+    /// * `Err(LoggerError::InvalidFormat)` if the date format can't be rendered
+    pub fn new(line_date_format: impl Into<String>) -> Result<Self, LoggerError> {
+        let line_date_format = line_date_format.into();
+        render_date(&Local::now(), &line_date_format, "line_date_format")?;
+        Ok(BracketFormatter { line_date_format })
+    }
+}
+
+impl LineFormatter for BracketFormatter {
+    fn format(&self, ts: &DateTime<FixedOffset>, level: Option<Level>, msg: &str) -> String {
+        let time = render_date(ts, &self.line_date_format, "line_date_format").unwrap_or_default();
+        match level {
+            Some(level) => format!("[{}] [{}] {}", time.trim(), level, msg),
+            None => format!("[{}] {}", time.trim(), msg),
+        }
+    }
+}
+
+// The plain layout, "<time><separator>[LEVEL] <message>" or "<time><separator><message>" without a level
+pub(crate) fn plain(time: &str, separator: &str, level: Option<Level>, msg: &str) -> String {
+    match level {
+        Some(level) => format!("{}{}[{}] {}", time, separator, level, msg),
+        None => format!("{}{}{}", time, separator, msg),
+    }
+}
+
+// Fill in the placeholders a banner line can use: {version} for this crate's version, {pid} and {timestamp} in RFC 3339
+pub(crate) fn banner(text: &str, now: &DateTime<FixedOffset>) -> String {
+    text.replace("{version}", env!("CARGO_PKG_VERSION"))
+        .replace("{pid}", &std::process::id().to_string())
+        .replace("{timestamp}", &now.to_rfc3339())
+}
+
+// Escape line breaks and other control characters so a message can't pass itself off as more than one entry.
+// "\n" and "\r" are written as the two characters, the rest as "\xNN". Tabs are left alone
+pub(crate) fn sanitize(msg: &str) -> Cow<'_, str> {
+    let escaped = |c: char| c != '\t' && c.is_control();
+    if !msg.chars().any(escaped) {
+        return Cow::Borrowed(msg);
+    }
+    let mut out = String::with_capacity(msg.len() + 8);
+    for c in msg.chars() {
+        match c {
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            c if escaped(c) => out.push_str(&format!("\\x{:02X}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    Cow::Owned(out)
+}
+
+// Cut a message down to at most max bytes without splitting a character, saying how much was dropped
+pub(crate) fn truncate(msg: Cow<'_, str>, max: Option<usize>) -> Cow<'_, str> {
+    match max {
+        Some(max) if msg.len() > max => {
+            // max is inside the message here, and 0 is always a boundary
+            let cut = (0..=max).rev().find(|&i| msg.is_char_boundary(i)).unwrap_or(0);
+            Cow::Owned(format!("{}…[truncated {} bytes]", &msg[..cut], msg.len() - cut))
+        }
+        _ => msg,
+    }
+}
+
+// Most capacity a thread's message buffer keeps between calls, so one huge message doesn't pin its memory
+const MAX_KEPT_CAPACITY: usize = 16 * 1024;
+
+thread_local! {
+    // Reused by each thread for messages given as format arguments
+    static MESSAGE: Cell<String> = const { Cell::new(String::new()) };
+    // The buffer of the thread's last entry, handed back once it was written
+    static ENTRY: Cell<String> = const { Cell::new(String::new()) };
+}
+
+// An empty buffer to build an entry in, reusing the thread's last one if it was handed back
+pub(crate) fn entry_buffer() -> String {
+    ENTRY.try_with(Cell::take).unwrap_or_default()
+}
+
+// Hand back an entry's buffer once it has been written, for the thread's next entry
+pub(crate) fn recycle(mut entry: String) {
+    entry.clear();
+    entry.shrink_to(MAX_KEPT_CAPACITY);
+    let _ = ENTRY.try_with(|kept| kept.set(entry));
+}
+
+// The host, process and thread details in front of a message, "[host web-1] [pid 4242] [tid 5] [worker-3] "
+pub(crate) fn push_context(out: &mut String, context: &[(&str, String)]) {
+    for (key, value) in context {
+        let _ = match *key {
+            "thread" => write!(out, "[{value}] "),
+            key => write!(out, "[{key} {value}] "),
+        };
+    }
+}
+
+// Render format arguments and hand the text to f, using the thread's buffer so repeated calls don't allocate.
+// Plain text with no arguments is passed straight through. The buffer is taken out while in use, so logging from
+// inside a Display impl or an event callback just starts a new one
+pub(crate) fn with_args<R>(args: fmt::Arguments<'_>, f: impl FnOnce(&str) -> R) -> R {
+    if let Some(text) = args.as_str() {
+        return f(text);
+    }
+    let mut buffer = MESSAGE.try_with(Cell::take).unwrap_or_default();
+    let _ = fmt::Write::write_fmt(&mut buffer, args);
+    let result = f(&buffer);
+    buffer.clear();
+    buffer.shrink_to(MAX_KEPT_CAPACITY);
+    let _ = MESSAGE.try_with(|kept| kept.set(buffer));
+    result
+}
+
+/// What ends each entry, set with `.line_ending()` on the builder. It's used for every line the logger writes,
+/// banners and its own warnings included
+/// # Example
+/// ```rust
+/// use d_logger::{LineEnding, Logger};
+/// 
+/// let path = std::env::temp_dir().join("d_logger_line_ending");
+/// # let _ = std::fs::remove_dir_all(&path);
+/// let native = if cfg!(windows) { "\r\n" } else { "\n" };
+/// for (ending, name, expected) in [(LineEnding::Native, "native.log", native), (LineEnding::Lf, "lf.log", "\n"), (LineEnding::CrLf, "crlf.log", "\r\n")] {
+///     let logger = Logger::builder().path(&path).file_name_format(name).line_date_format("TIME").line_ending(ending).build().unwrap();
+///     logger.write_log("one");
+///     logger.info("two");
+///     assert_eq!(std::fs::read(path.join(name)).unwrap(), format!("TIME one{expected}TIME [INFO] two{expected}").as_bytes());
+/// }
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LineEnding {
+    /// "\r\n" on Windows and "\n" everywhere else
+    #[default]
+    Native,
+    /// "\n" whatever the platform
+    Lf,
+    /// "\r\n" whatever the platform
+    CrLf,
+}
+
+impl LineEnding {
+    /// The characters written at the end of each entry
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            // Anything that isn't Windows is treated as Unix-like (Linux, macOS, BSDs...)
+            LineEnding::Native => if cfg!(windows) { "\r\n" } else { "\n" },
+            LineEnding::Lf => "\n",
+            LineEnding::CrLf => "\r\n",
+        }
+    }
+}
+
+/// How each entry is written to the file
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OutputFormat {
+    /// "<time><message>", or "<time> [LEVEL] <message>" for the level methods. Key value pairs are appended in logfmt style
+    #[default]
+    Plain,
+    /// One JSON object per line with "ts", "level" (for the level methods), "seq" (if sequence numbers are on) and "msg",
+    /// key value pairs are added as extra fields.
+    /// Only available with the `json` feature.
+    /// # Example
+    /// ```rust
+    /// use d_logger::{Logger, OutputFormat};
+    /// 
+    /// let path = std::env::temp_dir().join("d_logger_json");
+    /// let logger = Logger::builder().path(&path).file_name_format("json_%Y%m%d.log").output_format(OutputFormat::Json).build().unwrap();
+    /// # let file = path.join(chrono::Local::now().format("json_%Y%m%d.log").to_string());
+    /// # let _ = std::fs::remove_file(&file);
+    /// logger.write_log("plain \"quoted\"\nmulti line");
+    /// logger.warn("ünïcödé ✓");
+    /// logger.write_log_kv("login", &[("user", "bob"), ("from", "10.0.0.1")]);
+    /// 
+    /// let contents = std::fs::read_to_string(&file).unwrap();
+    /// let lines: Vec<serde_json::Value> = contents.lines().map(|l| serde_json::from_str(l).unwrap()).collect();
+    /// assert_eq!(lines.len(), 3);
+    /// assert_eq!(lines[0]["msg"], "plain \"quoted\"\nmulti line");
+    /// assert!(lines[0].get("level").is_none());
+    /// assert!(chrono::NaiveDateTime::parse_from_str(lines[0]["ts"].as_str().unwrap(), "%Y-%m-%d %H:%M:%S").is_ok());
+    /// assert_eq!(lines[1]["level"], "WARN");
+    /// assert_eq!(lines[1]["msg"], "ünïcödé ✓");
+    /// assert_eq!(lines[2]["user"], "bob");
+    /// assert_eq!(lines[2]["from"], "10.0.0.1");
+    /// ```
+    #[cfg(feature = "json")]
+    Json,
+    /// RFC 5424 syslog messages with this facility, see `SyslogFormatter`. The host name is the one from
+    /// `include_hostname` if that's on, or the machine's. Fractional seconds follow `subsec_precision`, at most six digits
+    /// # Example
+    /// ```rust
+    /// use d_logger::{Facility, Logger, OutputFormat};
+    /// 
+    /// let path = std::env::temp_dir().join("d_logger_syslog_format");
+    /// let logger = Logger::builder()
+    ///     .path(&path)
+    ///     .file_name_format("syslog_format_%Y%m%d.log")
+    ///     .output_format(OutputFormat::Syslog(Facility::Local0))
+    ///     .include_hostname(true)
+    ///     .hostname_provider(|| Some("web-1".to_string()))
+    ///     .build()
+    ///     .unwrap();
+    /// # let file = path.join(chrono::Local::now().format("syslog_format_%Y%m%d.log").to_string());
+    /// # let _ = std::fs::remove_file(&file);
+    /// logger.error("failed");
+    /// logger.write_log_kv("login", &[("user", "bob")]);
+    /// 
+    /// let contents = std::fs::read_to_string(&file).unwrap();
+    /// let lines: Vec<&str> = contents.lines().collect();
+    /// let pid = std::process::id();
+    /// assert!(lines[0].starts_with("<131>1 "));
+    /// assert!(lines[0].ends_with(&format!(" web-1 {} {pid} - - failed", lines[0].split(' ').nth(3).unwrap())));
+    /// let timestamp = lines[0].split(' ').nth(1).unwrap();
+    /// assert!(chrono::DateTime::parse_from_rfc3339(timestamp).is_ok());
+    /// assert!(lines[1].starts_with("<134>1 ") && lines[1].ends_with(" - - login user=bob"));
+    /// ```
+    Syslog(Facility),
+    /// One RFC 4180 record per entry with the columns timestamp, level, thread and message. The level is empty for
+    /// lines without one and the thread is the thread's name, or its number if it has none. Sequence tokens, host and
+    /// process details and key value pairs go in the message. Its line breaks are escaped like plain text unless
+    /// `LoggerBuilder::sanitize` is off, and it's quoted when it holds a comma, quote or line break.
+    /// `LoggerBuilder::csv_header` starts each new file with a header row
+    /// # Example
+    /// ```rust
+    /// use d_logger::{test_support::ManualClock, Logger, OutputFormat};
+    /// use chrono::TimeZone;
+    /// 
+    /// // Split RFC 4180 text into records of fields
+    /// fn parse_csv(text: &str) -> Vec<Vec<String>> {
+    ///     let (mut records, mut record, mut field) = (Vec::new(), Vec::new(), String::new());
+    ///     let (mut quoted, mut chars) = (false, text.chars().peekable());
+    ///     while let Some(c) = chars.next() {
+    ///         match (quoted, c) {
+    ///             (true, '"') if chars.peek() == Some(&'"') => { chars.next(); field.push('"'); }
+    ///             (true, '"') => quoted = false,
+    ///             (true, c) => field.push(c),
+    ///             (false, '"') => quoted = true,
+    ///             (false, ',') => record.push(std::mem::take(&mut field)),
+    ///             (false, '\r') => {}
+    ///             (false, '\n') => { record.push(std::mem::take(&mut field)); records.push(std::mem::take(&mut record)); }
+    ///             (false, c) => field.push(c),
+    ///         }
+    ///     }
+    ///     records
+    /// }
+    /// 
+    /// let path = std::env::temp_dir().join("d_logger_csv");
+    /// # let _ = std::fs::remove_dir_all(&path);
+    /// let clock = ManualClock::new(chrono::Local.with_ymd_and_hms(2024, 5, 1, 23, 59, 59).unwrap());
+    /// let logger = Logger::builder()
+    ///     .path(&path)
+    ///     .file_name_format("csv_%Y%m%d.csv")
+    ///     .output_format(OutputFormat::Csv)
+    ///     .csv_header(true)
+    ///     .max_file_size(300)
+    ///     .clock(clock.clone())
+    ///     .build()
+    ///     .unwrap();
+    /// let thread = std::thread::current().name().unwrap().to_string();
+    /// logger.warn("disk at 91%, \"cleanup\" started");
+    /// logger.write_log_kv("multi\nline", &[("user", "bob")]);
+    /// 
+    /// let records = parse_csv(&std::fs::read_to_string(path.join("csv_20240501.csv")).unwrap());
+    /// assert_eq!(records, [
+    ///     vec!["timestamp", "level", "thread", "message"],
+    ///     vec!["2024-05-01 23:59:59", "WARN", &thread, "disk at 91%, \"cleanup\" started"],
+    ///     vec!["2024-05-01 23:59:59", "", &thread, r"multi\nline user=bob"],
+    /// ]);
+    /// 
+    /// // Each new file gets the header once, whether it was started by the date or by rotation
+    /// clock.advance(std::time::Duration::from_secs(1));
+    /// for i in 0..10 {
+    ///     logger.info(&format!("entry {i}"));
+    /// }
+    /// let mut messages = Vec::new();
+    /// for file in ["csv_20240502.csv.1", "csv_20240502.csv"] {
+    ///     let records = parse_csv(&std::fs::read_to_string(path.join(file)).unwrap());
+    ///     assert_eq!(records.iter().filter(|r| r[0] == "timestamp").count(), 1);
+    ///     assert_eq!(records[0], ["timestamp", "level", "thread", "message"]);
+    ///     messages.extend(records.into_iter().skip(1).map(|r| r[3].clone()));
+    /// }
+    /// assert_eq!(messages.len(), 10);
+    /// assert_eq!(messages[9], "entry 9");
+    /// ```
+    Csv,
+}
+
+// The header row for OutputFormat::Csv, without the line ending
+pub(crate) const CSV_HEADER: &str = "timestamp,level,thread,message";
+
+// Append a CSV record, quoting the fields that hold a comma, quote or line break and doubling the quotes in them
+pub(crate) fn push_csv(out: &mut String, fields: &[&str]) {
+    for (i, field) in fields.iter().enumerate() {
+        if i > 0 {
+            out.push(',');
+        }
+        if field.contains([',', '"', '\r', '\n']) {
+            out.push('"');
+            out.push_str(&field.replace('"', "\"\""));
+            out.push('"');
+        } else {
+            out.push_str(field);
+        }
+    }
+}
+
+// Append key=value pairs in logfmt style, in the order given.
+// Values with spaces, '=', quotes or control characters are quoted and escaped so the entry stays on one line
+pub(crate) fn push_logfmt(out: &mut String, kvs: &[(&str, &str)]) {
+    for (key, value) in kvs {
+        out.push(' ');
+        out.push_str(key);
+        out.push('=');
+
+        let needs_quotes = value.is_empty() || value.chars().any(|c| c == ' ' || c == '=' || c == '"' || c == '\\' || c.is_control());
+        if !needs_quotes {
+            out.push_str(value);
+            continue;
+        }
+
+        out.push('"');
+        for c in value.chars() {
+            match c {
+                '"' => out.push_str("\\\""),
+                '\\' => out.push_str("\\\\"),
+                '\n' => out.push_str("\\n"),
+                '\r' => out.push_str("\\r"),
+                '\t' => out.push_str("\\t"),
+                c if c.is_control() => out.push_str(&format!("\\x{:02x}", c as u32)),
+                c => out.push(c),
+            }
+        }
+        out.push('"');
+    }
+}
+
+// Append a JSON object with ts, level, seq and msg first, followed by the key value pairs in the order given
+#[cfg(feature = "json")]
+pub(crate) fn push_json(out: &mut String, ts: &str, level: Option<Level>, seq: Option<u64>, msg: &str, kvs: &[(&str, &str)]) {
+    // Serialising a str can't fail
+    let quote = |s: &str| serde_json::to_string(s).unwrap();
+
+    out.push_str("{\"ts\":");
+    out.push_str(&quote(ts));
+    if let Some(level) = level {
+        out.push_str(",\"level\":");
+        out.push_str(&quote(level.as_str()));
+    }
+    if let Some(seq) = seq {
+        out.push_str(&format!(",\"seq\":{seq}"));
+    }
+    out.push_str(",\"msg\":");
+    out.push_str(&quote(msg));
+    for (key, value) in kvs {
+        out.push(',');
+        out.push_str(&quote(key));
+        out.push(':');
+        out.push_str(&quote(value));
+    }
+    out.push('}');
+}
+
+// Numeric id of a thread, ThreadId only exposes it through Debug as "ThreadId(5)"
+pub(crate) fn thread_id_number(id: std::thread::ThreadId) -> String {
+    format!("{id:?}").chars().filter(|c| c.is_ascii_digit()).collect()
+}
+
+// Look up the machine name, with the hostname feature this asks the OS, otherwise it falls back to the usual environment variables
+pub(crate) fn system_hostname() -> Option<String> {
+    #[cfg(feature = "hostname")]
+    {
+        gethostname::gethostname().into_string().ok().filter(|h| !h.is_empty())
+    }
+    #[cfg(not(feature = "hostname"))]
+    {
+        ["HOSTNAME", "COMPUTERNAME"].iter().find_map(|var| std::env::var(var).ok().filter(|h| !h.is_empty()))
+    }
+}
@@ -0,0 +1,60 @@
+// A process wide logger for the macros to fall back on
+use std::sync::OnceLock;
+
+use crate::{Logger, LoggerBuilder, LoggerError};
+
+static GLOBAL: OnceLock<Logger> = OnceLock::new();
+
+/// Build a logger and install it for the whole process, so `global()` and the `d_info!` style macros can reach it
+/// without a logger being passed around. The global logger is never dropped, so with a buffer or a sync policy
+/// other than `EveryLine` call `global().unwrap().flush()` before the program exits.
+/// # Arguments
+/// * `builder` - The settings for the global logger
+/// # Example
+/// ```rust
+/// use d_logger::{d_info, d_warn, Logger};
+/// 
+/// let path = std::env::temp_dir().join("d_logger_global_init");
+/// let file = path.join(chrono::Local::now().format("global_init_%Y%m%d.log").to_string());
+/// # let _ = std::fs::remove_file(&file);
+/// // Before init the macros without a logger do nothing and return false
+/// assert!(d_logger::global().is_none());
+/// assert!(!d_info!("too early"));
+/// 
+/// d_logger::init(Logger::builder().path(&path).file_name_format("global_init_%Y%m%d.log")).unwrap();
+/// assert!(matches!(d_logger::init(Logger::builder().path(&path)), Err(d_logger::LoggerError::AlreadyInitialized)));
+/// 
+/// std::thread::scope(|s| {
+///     for t in 0..8 {
+///         s.spawn(move || {
+///             for i in 0..50 {
+///                 d_info!("thread {} line {}", t, i);
+///             }
+///             d_warn!("thread {t} done");
+///         });
+///     }
+/// });
+/// d_logger::global().unwrap().write_log("direct");
+/// 
+/// let contents = std::fs::read_to_string(&file).unwrap();
+/// assert_eq!(contents.lines().filter(|l| l.contains("[INFO] thread ")).count(), 8 * 50);
+/// assert_eq!(contents.lines().filter(|l| l.contains("[WARN] thread ")).count(), 8);
+/// assert!(!contents.contains("too early"));
+/// assert!(contents.lines().any(|l| l.ends_with(" direct")));
+/// ```
+/// # Returns
+/// * `Err(LoggerError::AlreadyInitialized)` if a global logger has already been installed
+/// * `Err(LoggerError)` from `build` if the logger couldn't be created
+pub fn init(builder: LoggerBuilder) -> Result<(), LoggerError> {
+    if GLOBAL.get().is_some() {
+        return Err(LoggerError::AlreadyInitialized);
+    }
+    let logger = builder.build()?;
+    // Another thread may have got there while this one was building
+    GLOBAL.set(logger).map_err(|_| LoggerError::AlreadyInitialized)
+}
+
+/// The logger installed by `init`, None until then
+pub fn global() -> Option<&'static Logger> {
+    GLOBAL.get()
+}
@@ -0,0 +1,96 @@
+use std::{fs, io, path::{Path, PathBuf}};
+
+use crate::{writer, Logger, LoggerError};
+
+/// What `Logger::health_check` found
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct HealthReport {
+    /// The dated file entries are going to right now
+    pub path: PathBuf,
+    /// Size of that file in bytes
+    pub size: u64,
+    /// Bytes free for this process on the volume holding the log directory, None if it couldn't be found out
+    pub free_space: Option<u64>,
+}
+
+impl Logger {
+    /// Check the logger can actually write, e.g. for a readiness probe.
+    /// The log directory must exist and the current dated file must open for appending, nothing is written to it
+    /// and no other files are made. A logger made by `disabled` always passes with an empty report
+    /// # Example
+    /// ```rust
+    /// use d_logger::{Logger, LoggerError};
+    /// 
+    /// let path = std::env::temp_dir().join("d_logger_health_check");
+    /// let logger = Logger::builder().path(&path).file_name_format("health.log").build().unwrap();
+    /// logger.write_log("ready");
+    /// 
+    /// let before: Vec<_> = std::fs::read_dir(&path).unwrap().map(|e| e.unwrap().path()).collect();
+    /// let report = logger.health_check().unwrap();
+    /// assert_eq!(report.path, path.join("health.log"));
+    /// assert_eq!(report.size, std::fs::metadata(path.join("health.log")).unwrap().len());
+    /// assert!(report.free_space.unwrap() > 0);
+    /// let after: Vec<_> = std::fs::read_dir(&path).unwrap().map(|e| e.unwrap().path()).collect();
+    /// assert_eq!(before, after);
+    /// 
+    /// std::fs::remove_dir_all(&path).unwrap();
+    /// assert!(matches!(logger.health_check(), Err(LoggerError::DirectoryRead(_))));
+    /// ```
+    /// # Returns
+    /// * `Err(LoggerError::DirectoryRead)` if the log directory is missing or isn't a directory
+    /// * `Err(LoggerError::FileOpen)` if the current file can't be opened for appending
+    /// * `Err(LoggerError::InvalidFormat)` if the file name format can't be rendered
+    pub fn health_check(&self) -> Result<HealthReport, LoggerError> {
+        let inner = &self.inner;
+        if inner.disabled {
+            return Ok(HealthReport::default());
+        }
+        let dir = fs::metadata(&inner.path).map_err(LoggerError::DirectoryRead)?;
+        if !dir.is_dir() {
+            return Err(LoggerError::DirectoryRead(io::Error::other(format!("{} is not a directory", inner.path.display()))));
+        }
+        let now = inner.now();
+        let path = inner.file_for(&now)?;
+        // The real file rather than a probe, so there's nothing to clean up. It's made if this is its first use
+        let file = match writer::open_append(&path, inner.file_mode) {
+            Ok(file) => file,
+            Err(e) => return Err(LoggerError::FileOpen { path, source: e }),
+        };
+        let size = file.metadata().map_or(0, |m| m.len());
+        Ok(HealthReport { free_space: free_space(&inner.path), path, size })
+    }
+}
+
+// Bytes available to unprivileged users on the volume holding the path
+#[cfg(unix)]
+pub(crate) fn free_space(path: &Path) -> Option<u64> {
+    use std::{ffi::CString, os::unix::ffi::OsStrExt};
+    let path = CString::new(path.as_os_str().as_bytes()).ok()?;
+    let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+    // SAFETY: path is a valid C string and stat is a writable statvfs
+    if unsafe { libc::statvfs(path.as_ptr(), &mut stat) } != 0 {
+        return None;
+    }
+    Some(stat.f_bavail as u64 * stat.f_frsize as u64)
+}
+
+#[cfg(windows)]
+pub(crate) fn free_space(path: &Path) -> Option<u64> {
+    use std::os::windows::ffi::OsStrExt;
+    #[link(name = "kernel32")]
+    unsafe extern "system" {
+        fn GetDiskFreeSpaceExW(directory: *const u16, free_to_caller: *mut u64, total: *mut u64, total_free: *mut u64) -> i32;
+    }
+    let path: Vec<u16> = path.as_os_str().encode_wide().chain(std::iter::once(0)).collect();
+    let mut free = 0;
+    // SAFETY: path is nul terminated and the totals we don't want may be null
+    if unsafe { GetDiskFreeSpaceExW(path.as_ptr(), &mut free, std::ptr::null_mut(), std::ptr::null_mut()) } == 0 {
+        return None;
+    }
+    Some(free)
+}
+
+#[cfg(not(any(unix, windows)))]
+pub(crate) fn free_space(_path: &Path) -> Option<u64> {
+    None
+}
@@ -0,0 +1,130 @@
+use std::io::{self, Write};
+
+use crate::{Level, Logger};
+
+/// An `io::Write` that turns what's written into log lines, for libraries that take a writer for their output.
+/// Bytes are held until a '\n' and each line is then written with a timestamp, a trailing '\r' is dropped.
+/// `flush` writes out a partial line, and so does dropping the writer. A UTF-8 character split between
+/// writes is put back together, anything that still isn't valid UTF-8 is written with replacement characters.
+/// Made with `Logger::writer`
+/// # Example
+/// ```rust
+/// use d_logger::{Level, Logger};
+/// use std::io::Write;
+/// 
+/// let path = std::env::temp_dir().join("d_logger_writer");
+/// let logger = Logger::builder().path(&path).file_name_format("writer_%Y%m%d.log").build().unwrap();
+/// # let file = path.join(chrono::Local::now().format("writer_%Y%m%d.log").to_string());
+/// # let _ = std::fs::remove_file(&file);
+/// let mut writer = logger.writer();
+/// writer.write_all(b"first line\nsecond ").unwrap();
+/// writer.write_all(b"line\r\nsplit \xc3").unwrap();
+/// writer.write_all(b"\xa9 char\nno newline").unwrap();
+/// writer.flush().unwrap();
+/// write!(logger.writer().level(Level::Warn), "from write!\n").unwrap();
+/// 
+/// let contents = std::fs::read_to_string(&file).unwrap();
+/// let lines: Vec<&str> = contents.lines().collect();
+/// assert_eq!(lines.len(), 5);
+/// assert!(lines[0].ends_with(" first line"));
+/// assert!(lines[1].ends_with(" second line"));
+/// assert!(lines[2].ends_with(" split é char"));
+/// assert!(lines[3].ends_with(" no newline"));
+/// assert!(lines[4].ends_with(" [WARN] from write!"));
+/// ```
+pub struct LoggerWriter<'a> {
+    logger: &'a Logger,
+    level: Option<Level>,
+    buf: Vec<u8>,
+}
+
+impl<'a> LoggerWriter<'a> {
+    pub(crate) fn new(logger: &'a Logger) -> Self {
+        LoggerWriter { logger, level: None, buf: Vec::new() }
+    }
+
+    /// Write the lines at this level, they are plain `write_log` lines otherwise
+    pub fn level(mut self, level: Level) -> Self {
+        self.level = Some(level);
+        self
+    }
+
+    fn write_line(&self, bytes: &[u8]) -> io::Result<()> {
+        let bytes = bytes.strip_suffix(b"\r").unwrap_or(bytes);
+        let line = String::from_utf8_lossy(bytes);
+        let result = match self.level {
+            Some(level) => self.logger.try_write_level(level, &line),
+            None => self.logger.try_write_log(&line),
+        };
+        result.map_err(io::Error::other)
+    }
+}
+
+/// A line that can't be written stays in the writer. `write` only counts the bytes of lines that reached the log,
+/// or that are still waiting for their '\n', so an error means none of the bytes passed to it were taken
+/// # Example
+/// ```rust
+/// use d_logger::Logger;
+/// use std::io::Write;
+/// 
+/// let path = std::env::temp_dir().join("d_logger_writer_failed");
+/// # let _ = std::fs::remove_dir_all(&path);
+/// let logger = Logger::builder().path(&path).line_date_format("").separator("").build().unwrap();
+/// let mut writer = logger.writer();
+/// writer.write_all(b"partial ").unwrap();
+/// 
+/// // A file where the directory should be makes the write fail
+/// std::fs::remove_dir_all(&path).unwrap();
+/// std::fs::write(&path, "").unwrap();
+/// assert!(writer.write_all(b"line\n").is_err());
+/// 
+/// std::fs::remove_file(&path).unwrap();
+/// std::fs::create_dir(&path).unwrap();
+/// writer.write_all(b"line\n").unwrap();
+/// assert_eq!(std::fs::read_to_string(logger.current_file_path()).unwrap().lines().collect::<Vec<_>>(), ["partial line"]);
+/// ```
+impl Write for LoggerWriter<'_> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let held = self.buf.len();
+        self.buf.extend_from_slice(buf);
+        // Start of the first line not yet written
+        let mut start = 0;
+        while let Some(len) = self.buf[start..].iter().position(|b| *b == b'\n') {
+            if let Err(e) = self.write_line(&self.buf[start..start + len]) {
+                // Keep what was held before and give back the new bytes from the failed line on
+                let taken = start.saturating_sub(held);
+                self.buf.truncate(held.max(start));
+                self.buf.drain(..start);
+                return if taken > 0 { Ok(taken) } else { Err(e) };
+            }
+            start += len + 1;
+        }
+        self.buf.drain(..start);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        if !self.buf.is_empty() {
+            // Hold back the start of a character that hasn't finished arriving
+            let keep = match std::str::from_utf8(&self.buf) {
+                Err(e) if e.error_len().is_none() => self.buf.len() - e.valid_up_to(),
+                _ => 0,
+            };
+            let end = self.buf.len() - keep;
+            if end > 0 {
+                self.write_line(&self.buf[..end])?;
+                self.buf.drain(..end);
+            }
+        }
+        self.logger.flush().map_err(io::Error::other)
+    }
+}
+
+impl Drop for LoggerWriter<'_> {
+    fn drop(&mut self) {
+        if !self.buf.is_empty() {
+            let line = std::mem::take(&mut self.buf);
+            let _ = self.write_line(&line);
+        }
+    }
+}
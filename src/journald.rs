@@ -0,0 +1,120 @@
+use std::{io, os::unix::net::UnixDatagram, path::Path};
+
+use crate::{sink::Record, syslog, Sink};
+
+const JOURNAL_SOCKET: &str = "/run/systemd/journal/socket";
+
+/// Sends entries to the systemd journal using its native protocol, with the level as PRIORITY and the key value pairs
+/// from `write_log_kv` as extra fields, upper cased with anything but letters, digits and '_' replaced by '_'.
+/// MESSAGE is the message on its own, SYSLOG_IDENTIFIER the executable's name.
+/// If the journal's socket isn't there the sink does nothing, so the same code runs outside systemd.
+/// Linux only, with the `journald` feature
+/// # Example
+/// ```rust
+/// use d_logger::{JournaldSink, Logger};
+/// use std::os::unix::net::UnixDatagram;
+/// 
+/// let socket_path = std::env::temp_dir().join("d_logger_journald.sock");
+/// # let _ = std::fs::remove_file(&socket_path);
+/// let journal = UnixDatagram::bind(&socket_path).unwrap();
+/// let path = std::env::temp_dir().join("d_logger_journald");
+/// let logger = Logger::builder().path(&path).build().unwrap();
+/// let sink = JournaldSink::with_path(&socket_path);
+/// assert!(sink.is_enabled());
+/// logger.add_sink(Box::new(sink));
+/// 
+/// logger.write_log_kv("two\nlines", &[("user-id", "42")]);
+/// let mut buf = [0; 4096];
+/// let len = journal.recv(&mut buf).unwrap();
+/// let frame = &buf[..len];
+/// // Values with newlines are sent as the name, a newline, the length as a little endian u64, then the value
+/// let mut expected = b"MESSAGE\n".to_vec();
+/// expected.extend_from_slice(&9u64.to_le_bytes());
+/// expected.extend_from_slice(b"two\nlines\nPRIORITY=6\n");
+/// assert!(frame.starts_with(&expected));
+/// assert!(frame.ends_with(b"\nUSER_ID=42\n"));
+/// 
+/// // With no journal the sink is off and writes still succeed
+/// let missing = JournaldSink::with_path(std::env::temp_dir().join("d_logger_no_journal.sock"));
+/// assert!(!missing.is_enabled());
+/// logger.add_sink(Box::new(missing));
+/// logger.info("only in the file");
+/// assert_eq!(logger.sink_errors(), 0);
+/// ```
+#[derive(Debug)]
+pub struct JournaldSink {
+    socket: Option<UnixDatagram>,
+}
+
+impl JournaldSink {
+    /// Connect to the journal at /run/systemd/journal/socket
+    pub fn new() -> Self {
+        Self::with_path(JOURNAL_SOCKET)
+    }
+
+    /// Connect to a journal socket somewhere else
+    pub fn with_path(path: impl AsRef<Path>) -> Self {
+        let socket = UnixDatagram::unbound().and_then(|socket| socket.connect(path).map(|_| socket)).ok();
+        JournaldSink { socket }
+    }
+
+    /// Whether the journal's socket was found, entries are thrown away if not
+    pub fn is_enabled(&self) -> bool {
+        self.socket.is_some()
+    }
+}
+
+impl Default for JournaldSink {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Add a field, values containing a newline use the binary form the journal needs for them
+fn push_field(frame: &mut Vec<u8>, name: &str, value: &str) {
+    frame.extend_from_slice(name.as_bytes());
+    if value.contains('\n') {
+        frame.push(b'\n');
+        frame.extend_from_slice(&(value.len() as u64).to_le_bytes());
+    } else {
+        frame.push(b'=');
+    }
+    frame.extend_from_slice(value.as_bytes());
+    frame.push(b'\n');
+}
+
+// Journal field names are upper case letters, digits and '_', and can't start with '_' or a digit
+fn field_name(key: &str) -> String {
+    let name: String = key.chars().map(|c| if c.is_ascii_alphanumeric() { c.to_ascii_uppercase() } else { '_' }).collect();
+    let name = name.trim_start_matches('_');
+    if name.is_empty() || name.starts_with(|c: char| c.is_ascii_digit()) {
+        format!("FIELD_{name}")
+    } else {
+        name.to_string()
+    }
+}
+
+impl Sink for JournaldSink {
+    fn write_entry(&self, formatted: &str) -> io::Result<()> {
+        let Some(socket) = &self.socket else {
+            return Ok(());
+        };
+        let mut frame = Vec::new();
+        push_field(&mut frame, "MESSAGE", formatted.trim_end());
+        socket.send(&frame).map(|_| ())
+    }
+
+    fn write_record(&self, record: &Record) -> io::Result<()> {
+        let Some(socket) = &self.socket else {
+            return Ok(());
+        };
+        let mut frame = Vec::new();
+        push_field(&mut frame, "MESSAGE", record.message);
+        push_field(&mut frame, "PRIORITY", &syslog::severity(record.level).to_string());
+        push_field(&mut frame, "SYSLOG_IDENTIFIER", syslog::local_app_name());
+        for (key, value) in record.kvs {
+            push_field(&mut frame, &field_name(key), value);
+        }
+        socket.send(&frame).map(|_| ())
+    }
+}
@@ -0,0 +1,45 @@
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+// Where the plain text fallback goes, "latest.log" becomes "latest.txt"
+fn text_path(dir: &Path, link: &str) -> PathBuf {
+    dir.join(Path::new(link).with_extension("txt"))
+}
+
+// Everything update can leave in the directory, so log clean leaves them alone
+pub(crate) fn paths(dir: &Path, link: &str) -> [PathBuf; 2] {
+    [dir.join(link), text_path(dir, link)]
+}
+
+// Point the link at target, which must be in dir. The new link is made under a temporary name and renamed over the
+// old one, so anyone reading it sees either the old file or the new one, never a missing link
+#[cfg(unix)]
+pub(crate) fn update(dir: &Path, link: &str, target: &Path) -> io::Result<()> {
+    let temp = dir.join(format!(".{link}.tmp"));
+    remove_if_exists(&temp)?;
+    // Relative, so the link still works if the whole directory is moved
+    std::os::unix::fs::symlink(target.file_name().unwrap_or(target.as_os_str()), &temp)?;
+    fs::rename(&temp, dir.join(link))
+}
+
+// Symlinks need extra privileges on Windows, so use a hard link, or if that isn't possible write the target's name
+#[cfg(not(unix))]
+pub(crate) fn update(dir: &Path, link: &str, target: &Path) -> io::Result<()> {
+    let temp = dir.join(format!(".{link}.tmp"));
+    remove_if_exists(&temp)?;
+    if fs::hard_link(target, &temp).is_ok() {
+        return fs::rename(&temp, dir.join(link));
+    }
+    let name = target.file_name().unwrap_or(target.as_os_str()).to_string_lossy();
+    fs::write(&temp, name.as_bytes())?;
+    fs::rename(&temp, text_path(dir, link))
+}
+
+fn remove_if_exists(path: &Path) -> io::Result<()> {
+    match fs::remove_file(path) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+        _ => Ok(()),
+    }
+}
@@ -0,0 +1,73 @@
+use std::{fmt, str::FromStr};
+
+use crate::LoggerError;
+
+/// Severity of a log entry, ordered from least to most severe.
+/// The logger drops anything below its minimum level.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Level {
+    Trace,
+    Debug,
+    Info,
+    Warn,
+    Error,
+}
+
+impl Level {
+    /// Name of the level as written to the log, e.g. INFO
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Level::Trace => "TRACE",
+            Level::Debug => "DEBUG",
+            Level::Info => "INFO",
+            Level::Warn => "WARN",
+            Level::Error => "ERROR",
+        }
+    }
+}
+
+// Stored as a u8 so the logger can keep its minimum level in an atomic
+impl From<u8> for Level {
+    fn from(value: u8) -> Self {
+        match value {
+            0 => Level::Trace,
+            1 => Level::Debug,
+            2 => Level::Info,
+            3 => Level::Warn,
+            _ => Level::Error,
+        }
+    }
+}
+
+impl fmt::Display for Level {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// A level by name in any case, "warning" as well as "warn"
+/// # Example
+/// ```rust
+/// use d_logger::Level;
+/// 
+/// assert_eq!("info".parse::<Level>().unwrap(), Level::Info);
+/// assert_eq!("WARNING".parse::<Level>().unwrap(), Level::Warn);
+/// for level in [Level::Trace, Level::Debug, Level::Info, Level::Warn, Level::Error] {
+///     assert_eq!(level.to_string().parse::<Level>().unwrap(), level);
+/// }
+/// assert!("loud".parse::<Level>().unwrap_err().to_string().contains("trace, debug, info, warn or error"));
+/// ```
+impl FromStr for Level {
+    type Err = LoggerError;
+
+    fn from_str(s: &str) -> Result<Level, LoggerError> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "trace" => Ok(Level::Trace),
+            "debug" => Ok(Level::Debug),
+            "info" => Ok(Level::Info),
+            "warn" | "warning" => Ok(Level::Warn),
+            "error" => Ok(Level::Error),
+            _ => Err(LoggerError::InvalidConfig(format!("\"{s}\" is not a level, expected one of trace, debug, info, warn or error"))),
+        }
+    }
+}
@@ -1,23 +1,231 @@
-#![allow(dead_code)]
-use std::env::consts::OS;
-use std::io;
-use std::{fs::{self, OpenOptions}, io::Write, path::Path, time::{SystemTime, UNIX_EPOCH}};
+use std::{borrow::Cow, fmt, fs, io, path::{Path, PathBuf}, sync::{atomic::{AtomicBool, AtomicU64, AtomicU8, Ordering}, Arc, Mutex, RwLock}, time::{Duration, Instant}};
 use chrono::prelude::*;
-use regex::Regex;
+use clean::CleanSettings;
+use dedup::Dedup;
+use event::Events;
+use fallback::Fallback;
+use low_space::{Change, SpaceGuard};
+use rate_limit::RateLimiter;
+use read::{Entries, LineParser};
+use retry::Retry;
+use stats::Stats;
+use test_support::ConsoleCapture;
+use time::NameCache;
+use writer::OpenFile;
+pub use writer::SyncPolicy;
+
+// A dated file an entry is written to, None for the main file or the index of a routed level's file
+pub(crate) type Target = (Option<usize>, PathBuf);
+
+// A level written to its own dated file as well as, or instead of, the main one
+struct Route {
+    level: Level,
+    file_name_format: String,
+    exclusive: bool,
+    file: Mutex<Option<OpenFile>>,
+    name: NameCache,
+}
+
+// Formatted entries waiting to be appended to one file together, with how many of them are the caller's lines rather
+// than notes such as the low space warning. Each entry's time, message and end in the text are kept too when there's
+// a console or sink to hand them to once they're written
+struct Batch {
+    file: PathBuf,
+    entries: String,
+    count: u32,
+    lines: usize,
+    parts: Option<Vec<(DateTime<FixedOffset>, String, usize)>>,
+}
+
+impl Batch {
+    fn new(file: PathBuf, publish: bool) -> Batch {
+        Batch { file, entries: String::new(), count: 0, lines: 0, parts: publish.then(Vec::new) }
+    }
+
+    fn push_line(&mut self, time: &DateTime<FixedOffset>, line: &str, log_entry: String) {
+        self.lines += 1;
+        self.push(time, line, log_entry);
+    }
+
+    fn push(&mut self, time: &DateTime<FixedOffset>, line: &str, log_entry: String) {
+        if self.entries.is_empty() {
+            self.entries = log_entry;
+        } else {
+            self.entries.push_str(&log_entry);
+            format::recycle(log_entry);
+        }
+        self.count += 1;
+        if let Some(parts) = &mut self.parts {
+            parts.push((*time, line.to_string(), self.entries.len()));
+        }
+    }
+}
+
+mod background;
+mod builder;
+mod clean;
+mod clock;
+mod console;
+mod dedup;
+mod dlog;
+#[cfg(feature = "compress")]
+mod compress;
+#[cfg(feature = "config")]
+mod config;
+mod error;
+mod event;
+mod expand;
+mod fallback;
+#[cfg(all(windows, feature = "windows-eventlog"))]
+mod eventlog;
+mod format;
+mod global;
+mod health;
+mod io_writer;
+mod latest;
+#[cfg(all(target_os = "linux", feature = "journald"))]
+mod journald;
+mod level;
+mod location;
+mod long_path;
+mod low_space;
+mod macros;
+#[cfg(feature = "net")]
+mod net;
+mod panic_hook;
+mod rate_limit;
+mod read;
+mod retry;
+mod redact;
+mod rotation;
+mod scoped;
+mod stats;
+mod sink;
+mod syslog;
+pub mod test_support;
+mod time;
+#[cfg(feature = "config")]
+mod toml;
+mod writer;
+#[cfg(feature = "log")]
+mod log_facade;
+#[cfg(all(unix, feature = "signal"))]
+mod signal;
+#[cfg(feature = "tokio")]
+mod tokio_io;
+#[cfg(feature = "tracing")]
+mod tracing_layer;
+#[cfg(feature = "http")]
+mod webhook;
+pub use background::{AsyncLogger, OverflowPolicy};
+pub use builder::LoggerBuilder;
+pub use clean::{CleanReport, CleanStrategy, SkipReason};
+pub use clock::{Clock, SystemClock};
+pub use console::ConsoleMode;
+#[cfg(feature = "config")]
+pub use config::LoggerConfig;
+pub use dlog::{DLog, LogEntry, MemoryLogger, NoopLogger};
+pub use error::LoggerError;
+pub use event::LogEvent;
+#[cfg(all(windows, feature = "windows-eventlog"))]
+pub use eventlog::EventLogSink;
+pub use format::{BracketFormatter, DefaultFormatter, LineEnding, LineFormatter, OutputFormat};
+pub use global::{global, init};
+pub use health::HealthReport;
+pub use io_writer::LoggerWriter;
+#[cfg(all(target_os = "linux", feature = "journald"))]
+pub use journald::JournaldSink;
+pub use level::Level;
+pub use location::Location;
+pub use low_space::LowSpacePolicy;
+pub use read::{ExportSummary, ImportSummary, ParseError, SearchHit};
+pub use redact::Redaction;
+#[cfg(feature = "net")]
+pub use net::NetworkSink;
+pub use scoped::ScopedLogger;
+pub use stats::LoggerStats;
+pub use sink::{FileSink, MemorySink, Record, RingBufferSink, Sink};
+#[cfg(unix)]
+pub use syslog::SyslogSink;
+pub use syslog::{Facility, SyslogFormatter};
+pub use time::{SubsecPrecision, TimeZoneMode};
+#[cfg(feature = "tracing")]
+pub use tracing_layer::DLoggerLayer;
+#[cfg(feature = "http")]
+pub use webhook::WebhookSink;
 
 const SECS_1_DAY: u64 = 86400;
 
-const NEW_LINE_WINDOWS: &str = "\r\n";
-const NEW_LINE_LINUX: &str = "\n";
-const PATH_SPLIT_WINDOWS: &str = r"\";
-const PATH_SPLIT_LINUX: &str = "/";
+// Render a chrono format string, chrono panics on to_string() if the format is invalid so go through fmt::Write instead
+fn render_date<Tz: TimeZone>(date: &DateTime<Tz>, format: &str, field: &str) -> Result<String, LoggerError>
+where
+    Tz::Offset: std::fmt::Display,
+{
+    let mut out = String::new();
+    push_date(&mut out, date, format, field)?;
+    Ok(out)
+}
+
+// Render a chrono format string onto the end of a buffer
+fn push_date<Tz: TimeZone>(out: &mut String, date: &DateTime<Tz>, format: &str, field: &str) -> Result<(), LoggerError>
+where
+    Tz::Offset: std::fmt::Display,
+{
+    use std::fmt::Write;
+
+    write!(out, "{}", date.format(format)).map_err(|_| LoggerError::InvalidFormat(format!("{field} \"{format}\" is not a valid date format")))
+}
+
+// Characters that can't appear in a file name on the current platform
+const INVALID_FILE_NAME_CHARS: &[char] = if cfg!(windows) { &['<', '>', ':', '"', '/', '\\', '|', '?', '*'] } else { &['/'] };
 
-// Shorthand string maker and &str converter
-macro_rules! toString {
-    ($s:expr) => { $s.to_string() }
+// Least time between tries at making a deleted log directory again, so a volume that's gone isn't hammered
+const DIR_RECREATE_COOLDOWN: Duration = Duration::from_secs(10);
+
+// Make the log directory and any missing parents, then check it really is a directory
+fn create_log_dir(path: &Path, mode: Option<u32>) -> Result<(), LoggerError> {
+    if !path.exists() {
+        // The parents that are missing too, to be given the mode as well
+        let missing: Vec<&Path> = path.ancestors().take_while(|dir| !dir.as_os_str().is_empty() && !dir.exists()).collect();
+        let mut builder = fs::DirBuilder::new();
+        builder.recursive(true);
+        #[cfg(unix)]
+        if let Some(mode) = mode {
+            use std::os::unix::fs::DirBuilderExt;
+            builder.mode(mode);
+        }
+        builder.create(path).map_err(LoggerError::DirectoryCreate)?;
+        // Set again on each directory made, so the umask can't take bits away
+        #[cfg(unix)]
+        if let Some(mode) = mode {
+            use std::os::unix::fs::PermissionsExt;
+            for dir in missing {
+                fs::set_permissions(dir, fs::Permissions::from_mode(mode)).map_err(LoggerError::DirectoryCreate)?;
+            }
+        }
+        #[cfg(not(unix))]
+        let _ = (mode, missing);
+    }
+    if !path.is_dir() {
+        return Err(LoggerError::NotADirectory { path: path.to_path_buf() });
+    }
+    Ok(())
 }
-macro_rules! toAmpStr {
-    ($s:expr) => { toString!($s).as_str() }
+
+// Render both formats once against a fixed date so bad formats are caught up front rather than producing weird files later
+fn validate_formats(file_name_format: &str, line_date_format: &str) -> Result<(), LoggerError> {
+    let fixed = Local.from_utc_datetime(&NaiveDate::from_ymd_opt(2024, 12, 31).unwrap().and_hms_opt(23, 59, 59).unwrap());
+
+    let file_name = render_date(&fixed, file_name_format, "file_name_format")?;
+    if file_name.is_empty() || file_name == "." || file_name == ".." {
+        return Err(LoggerError::InvalidFormat(format!("file_name_format \"{file_name_format}\" does not produce a file name")));
+    }
+    if let Some(c) = file_name.chars().find(|c| INVALID_FILE_NAME_CHARS.contains(c) || c.is_control()) {
+        return Err(LoggerError::InvalidFormat(format!("file_name_format \"{file_name_format}\" produces the invalid file name character {c:?}")));
+    }
+
+    render_date(&fixed, line_date_format, "line_date_format")?;
+    Ok(())
 }
 
 /// Logger will write log entries to a file with date and time in the line, using dated logs.
@@ -26,206 +234,3009 @@ macro_rules! toAmpStr {
 /// ```rust
 /// use d_logger::Logger;
 /// 
-/// let logger = Logger::new("C:/logs/".to_string(), "Log%d%m%y.log".to_string(), "%Y-%m-%d %H:%M:%S".to_string(), Some(7)).unwrap();
+/// let path = std::env::temp_dir().join("d_logger_example");
+/// let logger = Logger::new(path, "Log%d%m%y.log".to_string(), "%Y-%m-%d %H:%M:%S".to_string(), Some(7)).unwrap();
 /// if !logger.write_log("This is a test log entry") {
 ///    panic!("Logger failed");
 /// }
-/// logger.log_clean(None);
+/// logger.log_clean(None).unwrap();
+/// ```
+/// Cloning is cheap, every clone writes through the same open file and settings, so a logger can be handed to
+/// other threads without wrapping it in an `Arc`
+/// ```rust
+/// use d_logger::{Level, Logger};
+/// 
+/// let path = std::env::temp_dir().join("d_logger_clone");
+/// # let _ = std::fs::remove_dir_all(&path);
+/// let logger = Logger::builder().path(&path).file_name_format("clone.log").build().unwrap();
+/// let handles: Vec<_> = (0..16).map(|t| {
+///     let logger = logger.clone();
+///     std::thread::spawn(move || {
+///         for i in 0..100 {
+///             assert!(logger.info(&format!("thread {t} line {i}")));
+///         }
+///     })
+/// }).collect();
+/// for handle in handles {
+///     handle.join().unwrap();
+/// }
+/// 
+/// // Clones share the level as well as the file
+/// let other = logger.clone();
+/// other.set_level(Level::Warn);
+/// assert_eq!(logger.level(), Level::Warn);
+/// 
+/// let contents = std::fs::read_to_string(path.join("clone.log")).unwrap();
+/// assert_eq!(contents.lines().filter(|l| l.contains("[INFO] thread")).count(), 16 * 100);
 /// ```
+#[derive(Clone)]
 pub struct Logger {
-    /// Path to log file, must end with a \ on windows or / on linux
-    path: String,
+    inner: Arc<LoggerInner>,
+}
+
+// Loggers are shared between threads, keep it that way
+const _: () = {
+    const fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<Logger>();
+};
+
+// Everything a logger and its clones share
+struct LoggerInner {
+    /// Path to the log directory
+    path: PathBuf,
     /// Date format for file name, must contain full file name. e.g. Log%d%m%y.log
-    file_name_format: String,
+    file_name_format: RwLock<String>,
     /// Date format for lines
-    line_date_format: String,
+    line_date_format: RwLock<String>,
     /// Number of days to keep if using log clean
-    days_keep: Option<u64>,
+    days_keep: RwLock<Option<u64>>,
+    /// Written between the timestamp and the message
+    separator: RwLock<String>,
+    /// Minimum level written by the level methods, anything below is dropped.
+    /// Atomic so it can be changed through a shared reference while other threads write
+    level: AtomicU8,
+    /// Where entries are echoed besides the file, a ConsoleMode stored as a u8
+    console: AtomicU8,
+    /// How entries are written to the file
+    output_format: OutputFormat,
+    /// Makes the text written at the start of each file, before its first entry
+    file_header: Option<Arc<dyn Fn() -> String + Send + Sync>>,
+    /// Whether the CSV header row goes at the start of each file, after any file header
+    csv_header: bool,
+    /// What ends each entry
+    line_ending: LineEnding,
+    /// Time zone timestamps and file names are rendered in
+    time_zone: TimeZoneMode,
+    /// Fractional seconds appended to line timestamps that don't already have them
+    subsec_precision: Option<SubsecPrecision>,
+    /// Next sequence number written with each line, if sequence numbers are on
+    sequence: Option<AtomicU64>,
+    /// Host name added to each line, resolved once when the logger is built
+    hostname: Option<String>,
+    /// Add the process id to each line
+    include_pid: bool,
+    /// Add the numeric thread id to each line
+    include_thread_id: bool,
+    /// Add the thread name to each line, falling back to the numeric id for unnamed threads
+    include_thread_name: bool,
+    /// Custom formatter, replaces the output format when set
+    formatter: Option<Arc<dyn LineFormatter + Send + Sync>>,
+    /// When written entries are flushed and synced to disk
+    sync_policy: SyncPolicy,
+    /// Sync the directory when a file is created in it
+    sync_directory: bool,
+    /// Size of the write buffer in front of the file, 0 for none
+    buffer_capacity: usize,
+    /// Lock the file around each write, for several processes sharing it
+    file_lock: bool,
+    /// Permission bits for log files the logger creates, Unix only
+    file_mode: Option<u32>,
+    /// Permission bits for directories the logger creates, Unix only
+    dir_mode: Option<u32>,
+    /// Line written when the logger closes, taken once it has been written
+    shutdown_marker: Mutex<Option<String>>,
+    /// Info line written when the logger closes, with its placeholders still to fill in
+    shutdown_banner: Mutex<Option<String>>,
+    /// Size in bytes past which the current file is rotated
+    max_file_size: Option<u64>,
+    /// How many numbered copies rotation keeps
+    rotation_count: usize,
+    /// Most files kept by rotation and the log cleaner
+    max_files: Option<usize>,
+    /// Most bytes of matching files kept by the log cleaner
+    max_total_size: Option<u64>,
+    /// Gzip files once they are finished with
+    compress: bool,
+    /// Clean only files matching file_name_format when no filter is given
+    derive_clean_filter: bool,
+    /// How the log cleaner works out each file's age
+    clean_strategy: CleanStrategy,
+    /// How many levels of subdirectories the log cleaner goes into, and whether it removes those it empties
+    clean_subdirectories: (usize, bool),
+    /// How often writes run log_clean, None to leave it to the caller
+    auto_clean: Option<Duration>,
+    /// Name of the link kept pointing at the current file
+    latest_link: Option<String>,
+    /// Extra places every entry is copied to
+    sinks: RwLock<Vec<Box<dyn Sink + Send + Sync>>>,
+    /// How many times writing to one of the sinks failed
+    sink_errors: AtomicU64,
+    /// Callbacks told about rotations, deletions, compression and failed writes
+    events: Events,
+    /// Counters returned by stats
+    stats: Stats,
+    /// The most recent failure and when it happened
+    last_error: Mutex<Option<(DateTime<FixedOffset>, LoggerError)>>,
+    /// When making a deleted log directory again was last tried
+    dir_recreated: Mutex<Option<Instant>>,
+    /// Patterns replaced in messages before they're written, in the order added
+    redactions: RwLock<Vec<Redaction>>,
+    /// When auto clean last ran, in milliseconds since the epoch
+    last_clean: AtomicU64,
+    /// Set while a writer is running the auto clean
+    cleaning: AtomicBool,
+    /// The dated file kept open between writes, replaced when the dated name changes or a write fails
+    file: Mutex<Option<OpenFile>>,
+    /// The last main file name rendered, reused until the time moves past it
+    file_name_cache: NameCache,
+    /// Levels copied or moved to their own dated files
+    routes: Vec<Route>,
+    /// Drops entries past the allowance for their message, if a rate limit is set
+    rate_limit: Option<RateLimiter>,
+    /// Tries failed writes again, if a retry is set
+    retry: Option<Retry>,
+    /// Holds back repeats of the last message, if collapsing duplicates is on
+    dedup: Option<Dedup>,
+    /// Escape control characters in messages
+    sanitize: bool,
+    /// Longest message written in full, in bytes
+    max_line_len: Option<usize>,
+    /// Watches free space on the log volume, if a minimum is set
+    low_space: Option<SpaceGuard>,
+    /// Written to instead of the log directory while it can't be
+    fallback: Option<Fallback>,
+    /// Made by Logger::disabled, nothing is written or cleaned
+    disabled: bool,
+    /// Where the time comes from
+    clock: Arc<dyn Clock>,
+    /// Takes console echoes in place of standard output and error when set
+    console_capture: Option<ConsoleCapture>,
+    /// Whether an entry written with its own timestamp goes in the file for that timestamp
+    file_follows_timestamp: bool,
+    /// Held while writing to a file for another time without the cached handle, so only one writer can start it
+    stamped: Mutex<()>,
 }
 
 impl Logger {
     // Create a new logger and make sure we can use the log file given
     /// # Arguments
-    /// * `path` - Path to log file, must end with a \ on windows or / on linux
+    /// * `path` - Path to the log directory, a trailing separator is optional
     /// * `file_name_format` - Date format for file name, must contain full file name. e.g. Log%d%m%y.log
-    /// * `line_date_format` - Date format for lines
+    /// * `line_date_format` - Date format for lines, the message follows straight after so include any spacing wanted
     /// * `days_keep` - Number of days to keep if using log clean, set to None to disable
     /// # Example
     /// ```rust
     /// use d_logger::Logger;
     /// 
-    /// let logger = Logger::new("C:/logs/".to_string(), "Log%d%m%y.log".to_string(), "%Y-%m-%d %H:%M:%S".to_string(), Some(7)).unwrap();
+    /// let path = std::env::temp_dir().join("d_logger_new");
+    /// let logger = Logger::new(path, "Log%d%m%y.log".to_string(), "%Y-%m-%d %H:%M:%S".to_string(), Some(7)).unwrap();
     /// if !logger.write_log("This is a test log entry") {
     ///     panic!("Logger failed");
     /// }
-    /// logger.log_clean(None);
+    /// logger.log_clean(None).unwrap();
     /// ```
-    pub fn new(path: String, file_name_format: String, line_date_format: String, days_keep: Option<u64>) -> Result<Logger, io::Error> {
-        let new_line = match OS {
-            "linux" => NEW_LINE_LINUX,
-            "windows" => NEW_LINE_WINDOWS,
-            _ => return Err(io::Error::new(io::ErrorKind::Unsupported, "Unsupported OS")),
-        };
+    /// The trailing separator doesn't matter, both of these write to the same file
+    /// ```rust
+    /// use d_logger::Logger;
+    /// 
+    /// let dir = std::env::temp_dir().join("d_logger_new_separator").to_string_lossy().to_string();
+    /// let with = Logger::new(format!("{dir}/"), "Log%d%m%y.log".to_string(), "%Y-%m-%d %H:%M:%S ".to_string(), None).unwrap();
+    /// let without = Logger::new(&dir, "Log%d%m%y.log".to_string(), "%Y-%m-%d %H:%M:%S ".to_string(), None).unwrap();
+    /// # #[cfg(windows)]
+    /// # let _ = Logger::new(format!("{dir}\\"), "Log%d%m%y.log".to_string(), "%Y-%m-%d %H:%M:%S ".to_string(), None).unwrap();
+    /// with.write_log("with separator");
+    /// without.write_log("without separator");
+    /// 
+    /// let name = chrono::Local::now().format("Log%d%m%y.log").to_string();
+    /// let contents = std::fs::read_to_string(std::path::Path::new(&dir).join(name)).unwrap();
+    /// assert!(contents.contains("with separator") && contents.contains("without separator"));
+    /// ```
+    /// Making a logger doesn't write anything, so restarting a service many times doesn't pad the file out
+    /// ```rust
+    /// use d_logger::Logger;
+    /// 
+    /// let path = std::env::temp_dir().join("d_logger_new_restarts");
+    /// # let _ = std::fs::remove_dir_all(&path);
+    /// for i in 0..10 {
+    ///     let logger = Logger::new(&path, "restarts.log".to_string(), "%Y-%m-%d %H:%M:%S ".to_string(), None).unwrap();
+    ///     logger.write_log(&format!("started {i}"));
+    /// }
+    /// let contents = std::fs::read_to_string(path.join("restarts.log")).unwrap();
+    /// assert_eq!(contents.lines().count(), 10);
+    /// assert!(contents.lines().all(|line| !line.is_empty()));
+    /// ```
+    /// # Returns
+    /// * `Err(LoggerError::InvalidFormat)` if either date format can't be rendered, or the file name is empty or contains invalid characters
+    /// * `Err(LoggerError::DirectoryCreate)` if the directory is missing and can't be created
+    /// * `Err(LoggerError::NotADirectory)` if the path is a file or is empty
+    /// * `Err(LoggerError::DirectoryNotWritable)` if the log file can't be created in the directory
+    /// * `Err(LoggerError::FileOpen)` if the log file can't be opened for appending
+    pub fn new(path: impl AsRef<Path>, file_name_format: String, line_date_format: String, days_keep: Option<u64>) -> Result<Logger, LoggerError> {
+        LoggerBuilder::new()
+            .path(path)
+            .file_name_format(file_name_format)
+            .line_date_format(line_date_format)
+            .days_keep(days_keep)
+            .separator("")
+            .build()
+    }
 
-        if !Path::new(path.as_str()).exists() { // Check if the dir exists
-            _ = match fs::create_dir(path.as_str()) { // Try to create it if it doesn't
-                Ok(x) => x, 
-                Err(e) => {return Err(e)}
-            };
-        }
+    /// Start building a logger, see `LoggerBuilder` for the defaults.
+    /// # Example
+    /// ```rust
+    /// use d_logger::Logger;
+    /// 
+    /// let path = std::env::temp_dir().join("d_logger_builder_fn");
+    /// let logger = Logger::builder().path(path).days_keep(Some(7)).build().unwrap();
+    /// ```
+    pub fn builder() -> LoggerBuilder {
+        LoggerBuilder::new()
+    }
 
-        let now: DateTime<Local> = Local::now();
-        let log_file_name = path.clone() + toAmpStr!(now.format(file_name_format.as_str())); // Get the log file date
+    /// A logger that does nothing, for tests and for configs that turn logging off, so code can always take a `Logger`.
+    /// It never touches the filesystem, every write reports success without writing anything and `log_clean` does nothing.
+    /// Sinks added to it are never written to either
+    /// # Example
+    /// ```rust
+    /// use d_logger::Logger;
+    /// 
+    /// let logger = Logger::disabled();
+    /// assert!(logger.is_disabled());
+    /// assert!(logger.write_log("nowhere"));
+    /// assert!(logger.error("still nowhere"));
+    /// assert_eq!(logger.write_lines(["a", "b"]).unwrap(), 2);
+    /// logger.flush().unwrap();
+    /// assert!(logger.log_clean(None).unwrap().deleted.is_empty());
+    /// logger.close().unwrap();
+    /// 
+    /// // Nothing was created in the current directory, where a default logger would write
+    /// let name = chrono::Local::now().format("log_%Y%m%d.log").to_string();
+    /// let existed = std::path::Path::new(&name).exists();
+    /// let logger = Logger::disabled();
+    /// logger.write_log("nowhere");
+    /// assert_eq!(std::path::Path::new(&name).exists(), existed);
+    /// ```
+    pub fn disabled() -> Logger {
+        let mut builder = LoggerBuilder::new();
+        builder.disabled = true;
+        Logger::from_builder(builder).expect("the default formats are valid")
+    }
+
+    /// Whether this is a logger made by `disabled`, which writes nothing
+    pub fn is_disabled(&self) -> bool {
+        self.inner.disabled
+    }
 
-        let mut file = match OpenOptions::new().append(true).create(true).open(log_file_name.as_str()) {
-            Ok(x) => x, // Open the log file
-            Err(e) => {return Err(e)}
+    // Validate the builder settings and make sure we can use the log file given
+    pub(crate) fn from_builder(builder: LoggerBuilder) -> Result<Logger, LoggerError> {
+        let LoggerBuilder {
+            path,
+            file_name_format,
+            line_date_format,
+            days_keep,
+            separator,
+            level,
+            console,
+            output_format,
+            csv_header,
+            file_header,
+            line_ending,
+            time_zone,
+            subsec_precision,
+            sequence_numbers,
+            include_hostname,
+            hostname_provider,
+            include_pid,
+            include_thread_id,
+            include_thread_name,
+            formatter,
+            sync_policy,
+            sync_directory,
+            buffer_capacity,
+            file_lock,
+            expand_path,
+            long_paths,
+            file_mode,
+            dir_mode,
+            shutdown_marker,
+            startup_banner,
+            shutdown_banner,
+            max_file_size,
+            rotation_count,
+            max_files,
+            max_total_size,
+            compress,
+            derive_clean_filter,
+            clean_strategy,
+            clean_subdirectories,
+            auto_clean,
+            latest_link,
+            routes,
+            rate_limit,
+            retry,
+            collapse_duplicates,
+            sanitize,
+            max_line_len,
+            min_free_space,
+            low_space_policy,
+            low_space_check_interval,
+            free_space_provider,
+            clock,
+            console_capture,
+            file_follows_timestamp,
+            fallback_path,
+            fallback_retry_interval,
+            disabled,
+        } = builder;
+        let (path, fallback_path) = if expand_path {
+            (expand::expand_path(&path)?, fallback_path.as_deref().map(expand::expand_path).transpose()?)
+        } else {
+            (path, fallback_path)
         };
-        _ = match file.write_all(new_line.as_bytes()) { // Write the new line
-            Ok(x) => x,
-            Err(e) => {return Err(e)}
+
+        validate_formats(&file_name_format, &line_date_format)?;
+        for (_, route_format, _) in &routes {
+            validate_formats(route_format, &line_date_format)?;
+        }
+
+        let clock = clock.unwrap_or_else(|| Arc::new(SystemClock));
+        let now = time_zone.convert(&clock.now());
+        let file_name = render_date(&now, &file_name_format, "file_name_format")?; // Get the log file date
+        let (path, fallback_path) = if long_paths {
+            let extend = |dir| long_path::extend(dir, &file_name).map_err(LoggerError::DirectoryCreate);
+            (extend(path)?, fallback_path.map(extend).transpose()?)
+        } else {
+            (path, fallback_path)
         };
+        let log_file_name = path.join(file_name);
+
+        // A disabled logger never writes, so there's no directory or file to check
+        if !disabled {
+            // An empty path would otherwise put the files wherever the process happens to be running from
+            if path.as_os_str().is_empty() {
+                return Err(LoggerError::NotADirectory { path });
+            }
+            create_log_dir(&path, dir_mode)?; // Create the dir and its parents if they don't exist
 
-        Ok(Logger {path, file_name_format, line_date_format, days_keep})
+            // A new file that can't be made means the directory isn't writable, rather than a problem with the file
+            let existed = log_file_name.exists();
+            let file = OpenFile::open(log_file_name.clone(), 0, file_lock, file_mode).map_err(|e| match e {
+                LoggerError::FileOpen { source, .. } if !existed && matches!(source.kind(), io::ErrorKind::PermissionDenied | io::ErrorKind::ReadOnlyFilesystem) => {
+                    LoggerError::DirectoryNotWritable { path: path.clone(), source }
+                }
+                e => e,
+            })?; // Opening the log file for appending shows it can be written, without adding anything to it
+            if sync_directory && file.created {
+                writer::sync_dir(&log_file_name).map_err(LoggerError::Flush)?;
+            }
+            if let Some(link) = &latest_link {
+                latest::update(&path, link, &log_file_name).map_err(LoggerError::LatestLink)?;
+            }
+        }
+
+        let logger = Logger { inner: Arc::new(LoggerInner {
+            path,
+            file_name_format: RwLock::new(file_name_format),
+            line_date_format: RwLock::new(line_date_format),
+            days_keep: RwLock::new(days_keep),
+            separator: RwLock::new(separator),
+            level: AtomicU8::new(level as u8),
+            console: AtomicU8::new(console as u8),
+            output_format,
+            file_header,
+            csv_header: csv_header && formatter.is_none() && output_format == OutputFormat::Csv,
+            line_ending,
+            time_zone,
+            subsec_precision,
+            sequence: sequence_numbers.then(|| AtomicU64::new(1)),
+            hostname: include_hostname.then(|| {
+                let hostname = match hostname_provider {
+                    Some(provider) => provider(),
+                    None => format::system_hostname(),
+                };
+                hostname.unwrap_or_else(|| "unknown-host".to_string())
+            }),
+            include_pid,
+            include_thread_id,
+            include_thread_name,
+            formatter,
+            sync_policy,
+            sync_directory,
+            buffer_capacity: buffer_capacity.unwrap_or(0),
+            file_lock,
+            file_mode,
+            dir_mode,
+            shutdown_marker: Mutex::new(shutdown_marker),
+            shutdown_banner: Mutex::new(shutdown_banner),
+            max_file_size,
+            rotation_count,
+            max_files,
+            max_total_size,
+            compress,
+            derive_clean_filter,
+            clean_strategy,
+            clean_subdirectories,
+            auto_clean,
+            latest_link,
+            sinks: RwLock::new(Vec::new()),
+            sink_errors: AtomicU64::new(0),
+            events: Events::default(),
+            stats: Stats::default(),
+            last_error: Mutex::new(None),
+            dir_recreated: Mutex::new(None),
+            redactions: RwLock::new(Vec::new()),
+            last_clean: AtomicU64::new(0),
+            cleaning: AtomicBool::new(false),
+            file: Mutex::new(None),
+            file_name_cache: NameCache::default(),
+            disabled,
+            clock,
+            console_capture,
+            file_follows_timestamp,
+            stamped: Mutex::new(()),
+            rate_limit: rate_limit.map(|(max, window)| RateLimiter::new(max, window)),
+            retry: retry.map(|(attempts, backoff)| Retry::new(attempts, backoff)),
+            dedup: collapse_duplicates.map(Dedup::new),
+            sanitize,
+            max_line_len,
+            fallback: fallback_path.map(|path| Fallback::new(path, fallback_retry_interval)),
+            low_space: min_free_space.map(|min| SpaceGuard::new(min, low_space_policy, low_space_check_interval, free_space_provider)),
+            routes: routes.into_iter().map(|(level, file_name_format, exclusive)| Route {
+                level,
+                file_name_format,
+                exclusive,
+                file: Mutex::new(None),
+                name: NameCache::default(),
+            }).collect(),
+        }) };
+        if let Some(banner) = startup_banner {
+            logger.inner.write_entry(Some(Level::Info), &format::banner(&banner, &now), &[])?;
+        }
+        Ok(logger)
     }
 
     /// Write a line to the log.
+    /// Use `try_write_log` if you need to know why a write failed.
+    /// The dated file is kept open between writes and only reopened when the rendered file name changes or a write fails.
     /// # Arguments
     /// * `line` - The line to write to the log
     /// # Example
     /// ```rust
     /// use d_logger::Logger;
+    /// # let path = std::env::temp_dir().join("d_logger_write_log");
+    /// # let logger = Logger::new(path, "Log%d%m%y.log".to_string(), "%Y-%m-%d %H:%M:%S ".to_string(), None).unwrap();
     /// 
     /// if !logger.write_log("This is a test log entry") {
     ///    panic!("Logger failed");
     /// }
     /// ```
+    /// When the rendered file name changes the next write switches to the new file
+    /// ```rust
+    /// use d_logger::{test_support::ManualClock, Logger};
+    /// use chrono::TimeZone;
+    /// 
+    /// let path = std::env::temp_dir().join("d_logger_rollover");
+    /// # let _ = std::fs::remove_dir_all(&path);
+    /// let clock = ManualClock::new(chrono::Local.with_ymd_and_hms(2024, 12, 31, 23, 59, 58).unwrap());
+    /// let logger = Logger::builder().path(&path).file_name_format("roll_%Y%m%d.log").clock(clock.clone()).build().unwrap();
+    /// logger.write_log("first");
+    /// clock.advance(std::time::Duration::from_secs(1));
+    /// logger.write_log("still the same day");
+    /// clock.advance(std::time::Duration::from_secs(1));
+    /// logger.write_log("second");
+    /// 
+    /// let first_contents = std::fs::read_to_string(path.join("roll_20241231.log")).unwrap();
+    /// assert_eq!(first_contents.lines().count(), 2);
+    /// assert!(first_contents.contains("first") && !first_contents.contains("second"));
+    /// let second_contents = std::fs::read_to_string(path.join("roll_20250101.log")).unwrap();
+    /// assert!(second_contents.contains("2025-01-01 00:00:00 second"));
+    /// ```
+    /// If the log directory is deleted while the logger is running it's made again on the next write, with a warning
+    /// line in front of that entry. It's only tried once every 10 seconds, so a volume that has really gone isn't hammered
+    /// ```rust
+    /// use d_logger::Logger;
+    /// use std::time::Duration;
+    /// 
+    /// let path = std::env::temp_dir().join("d_logger_dir_recreated");
+    /// # let _ = std::fs::remove_dir_all(&path);
+    /// let logger = Logger::builder().path(&path).file_name_format("recreated.log").line_date_format("").separator("").build().unwrap();
+    /// logger.write_log("before");
+    /// 
+    /// // The open file is noticed missing within a second
+    /// std::fs::remove_dir_all(&path).unwrap();
+    /// std::thread::sleep(Duration::from_millis(1100));
+    /// assert!(logger.write_log("after"));
+    /// let lines: Vec<String> = std::fs::read_to_string(path.join("recreated.log")).unwrap().lines().map(String::from).collect();
+    /// assert_eq!(lines, [format!("Warning = Log directory, log directory was recreated at {}", path.display()), "after".to_string()]);
+    /// 
+    /// // Gone again straight away, it isn't made again until the cooldown has passed
+    /// std::fs::remove_dir_all(&path).unwrap();
+    /// std::thread::sleep(Duration::from_millis(1100));
+    /// assert!(!logger.write_log("lost"));
+    /// assert!(!path.exists());
+    /// ```
     /// # Returns
     /// * `true` if the log was written successfully
     /// * `false` if the log could not be written
     pub fn write_log(&self, line: &str) -> bool {
-        let new_line = match OS {
-            "linux" => NEW_LINE_LINUX,
-            "windows" => NEW_LINE_WINDOWS,
-            _ => return false,
-        };
+        self.try_write_log(line).is_ok()
+    }
 
-        // Get time and format it 
-        let now: DateTime<Local> = Local::now();
-        let time = now.format(self.line_date_format.as_str()).to_string();
-        let log_file_name = self.path.clone() + &now.format(self.file_name_format.as_str()).to_string();
-        
-        // Open log file
-        let mut file = match OpenOptions::new().append(true).create(true).open(&log_file_name) {
-            Ok(x) => x,
-            Err(_) => return false,
-        };
+    /// Write a line given as format arguments, the same as `write_log(&format!(...))` but the message is rendered
+    /// into a buffer each thread keeps for reuse rather than a new String every call
+    /// # Arguments
+    /// * `args` - The line to write, from `format_args!`
+    /// # Example
+    /// ```rust
+    /// use d_logger::Logger;
+    /// use std::alloc::{GlobalAlloc, Layout, System};
+    /// use std::sync::atomic::{AtomicUsize, Ordering};
+    /// 
+    /// // Count every allocation the program makes
+    /// struct Counting;
+    /// static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+    /// unsafe impl GlobalAlloc for Counting {
+    ///     unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+    ///         ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+    ///         unsafe { System.alloc(layout) }
+    ///     }
+    ///     unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+    ///         unsafe { System.dealloc(ptr, layout) }
+    ///     }
+    /// }
+    /// #[global_allocator]
+    /// static GLOBAL: Counting = Counting;
+    /// 
+    /// let path = std::env::temp_dir().join("d_logger_write_log_fmt");
+    /// # let _ = std::fs::remove_dir_all(&path);
+    /// let logger = Logger::builder().path(&path).file_name_format("fmt.log").line_date_format("TIME").build().unwrap();
+    /// let counted = |write: &dyn Fn(u32)| {
+    ///     write(0);
+    ///     let before = ALLOCATIONS.load(Ordering::Relaxed);
+    ///     for i in 1..=100 {
+    ///         write(i);
+    ///     }
+    ///     ALLOCATIONS.load(Ordering::Relaxed) - before
+    /// };
+    /// let with_format = counted(&|i| assert!(logger.write_log(&format!("request {i} took {}ms", i * 2))));
+    /// let with_args = counted(&|i| assert!(logger.write_log_fmt(format_args!("request {i} took {}ms", i * 2))));
+    /// assert!(with_args + 100 <= with_format, "{with_args} allocations against {with_format}");
+    /// 
+    /// // Both write exactly the same lines
+    /// let contents = std::fs::read_to_string(path.join("fmt.log")).unwrap();
+    /// let lines: Vec<&str> = contents.lines().collect();
+    /// assert_eq!(lines.len(), 202);
+    /// assert_eq!(lines[..101], lines[101..]);
+    /// assert_eq!(lines[1], "TIME request 1 took 2ms");
+    /// ```
+    /// # Returns
+    /// * `true` if the log was written successfully
+    /// * `false` if the log could not be written
+    pub fn write_log_fmt(&self, args: fmt::Arguments<'_>) -> bool {
+        format::with_args(args, |line| self.write_log(line))
+    }
 
-        // Print to console if we are debugging
-        if cfg!(debug_assertions) {
-            println!("{line}");
-        }
+    /// Write a line to the log, returning the reason if it fails.
+    /// # Arguments
+    /// * `line` - The line to write to the log
+    /// # Example
+    /// ```rust
+    /// use d_logger::{Logger, LoggerError};
+    /// 
+    /// let path = std::env::temp_dir().join("d_logger_try_write_log");
+    /// # let _ = std::fs::remove_file(&path);
+    /// let logger = Logger::new(path.clone(), "Log%d%m%y.log".to_string(), "%Y-%m-%d %H:%M:%S ".to_string(), None).unwrap();
+    /// 
+    /// // Put a file where the directory was, the open will now fail
+    /// std::fs::remove_dir_all(&path).unwrap();
+    /// std::fs::write(&path, "").unwrap();
+    /// match logger.try_write_log("This will fail") {
+    ///     Err(LoggerError::FileOpen { .. }) => {}
+    ///     other => panic!("Expected an open failure, got {other:?}"),
+    /// }
+    /// # std::fs::remove_file(&path).unwrap();
+    /// ```
+    /// # Returns
+    /// * `Ok(())` if the log was written successfully
+    /// * `Err(LoggerError::FileOpen)` if the dated log file could not be opened
+    /// * `Err(LoggerError::Write)` if writing the line failed
+    /// * `Err(LoggerError::Flush)` if flushing or syncing the file failed
+    /// * `Err(LoggerError::InvalidFormat)` if a date format can't be rendered
+    pub fn try_write_log(&self, line: &str) -> Result<(), LoggerError> {
+        self.inner.write_entry(None, line, &[])
+    }
 
-        // Write everything to file
-        let log_entry = format!("{}{}{}", time, line, new_line);
-        if file.write_all(log_entry.as_bytes()).is_ok() && file.flush().is_ok() {
-            file.sync_all().unwrap();
-            true
-        } else {
-            false
-        }
+    /// Write a batch of lines to the log, each with its own timestamp, in one write and sync instead of one per line.
+    /// Lines that land in a different dated file are flushed to the old file before the new one is opened.
+    /// Each line goes through the low space guard, the duplicate check and the rate limit like a `write_log` line,
+    /// with any summary line those write going in the batch in front of it.
+    /// See `write_lines_shared_time` to give every line the same timestamp
+    /// # Arguments
+    /// * `lines` - The lines to write to the log
+    /// # Example
+    /// ```rust
+    /// use d_logger::Logger;
+    /// 
+    /// let path = std::env::temp_dir().join("d_logger_write_lines");
+    /// let logger = Logger::builder().path(&path).file_name_format("lines_%Y%m%d.log").build().unwrap();
+    /// # let file = path.join(chrono::Local::now().format("lines_%Y%m%d.log").to_string());
+    /// # let _ = std::fs::remove_file(&file);
+    /// let report = vec!["total: 12", "passed: 11", "failed: 1"];
+    /// assert_eq!(logger.write_lines(&report).unwrap(), 3);
+    /// 
+    /// let contents = std::fs::read_to_string(&file).unwrap();
+    /// let lines: Vec<&str> = contents.lines().collect();
+    /// assert_eq!(lines.len(), 3);
+    /// for (written, expected) in lines.iter().zip(&report) {
+    ///     assert!(written.ends_with(&format!(" {expected}")));
+    /// }
+    /// ```
+    /// A batch that runs past the point the file name changes is split between the two files
+    /// ```rust
+    /// use d_logger::{test_support::ManualClock, Logger};
+    /// use chrono::TimeZone;
+    /// 
+    /// let path = std::env::temp_dir().join("d_logger_write_lines_rollover");
+    /// # let _ = std::fs::remove_dir_all(&path);
+    /// let clock = ManualClock::new(chrono::Local.with_ymd_and_hms(2024, 5, 1, 23, 59, 59).unwrap());
+    /// let logger = Logger::builder().path(&path).file_name_format("lines_%Y%m%d.log").clock(clock.clone()).build().unwrap();
+    /// let lines = (0..10).map(|i| {
+    ///     if i == 5 {
+    ///         // Midnight half way through the batch
+    ///         clock.advance(std::time::Duration::from_secs(1));
+    ///     }
+    ///     format!("line {i}")
+    /// });
+    /// assert_eq!(logger.write_lines(lines).unwrap(), 10);
+    /// 
+    /// let before = std::fs::read_to_string(path.join("lines_20240501.log")).unwrap();
+    /// let after = std::fs::read_to_string(path.join("lines_20240502.log")).unwrap();
+    /// assert_eq!(before.lines().count(), 5);
+    /// assert!(before.contains("line 4") && !before.contains("line 5"));
+    /// assert_eq!(after.lines().count(), 5);
+    /// assert!(after.starts_with("2024-05-02 00:00:00 line 5"));
+    /// ```
+    /// # Returns
+    /// * `Ok(count)` the number of lines written, not counting lines the checks dropped
+    /// * `Err(LoggerError)` as for `try_write_log`, lines before the failing file may already be written
+    pub fn write_lines<I: IntoIterator<Item = impl AsRef<str>>>(&self, lines: I) -> Result<usize, LoggerError> {
+        self.inner.write_batch(lines, false)
     }
 
-    /// Clean up log path. Will not delete any log files if the days_keep is set to None.
-    /// Will only delete files older than the days to keep.
-    /// Provide Some(<regex>) to filter by name or None to delete any file older than date
+    /// Write a batch of lines to the log like `write_lines`, but with one timestamp taken at the start shared by every line,
+    /// so the whole batch goes into the same file
     /// # Arguments
-    /// * `filter` - Optional regex to filter by name, None to delete any file older than date
+    /// * `lines` - The lines to write to the log
     /// # Example
     /// ```rust
     /// use d_logger::Logger;
     /// 
-    /// logger.clean_log(None);
+    /// let path = std::env::temp_dir().join("d_logger_write_lines_shared_time");
+    /// let logger = Logger::builder()
+    ///     .path(&path)
+    ///     .file_name_format("shared_%Y%m%d.log")
+    ///     .subsec_precision(Some(d_logger::SubsecPrecision::Nanos))
+    ///     .build()
+    ///     .unwrap();
+    /// # let file = path.join(chrono::Local::now().format("shared_%Y%m%d.log").to_string());
+    /// # let _ = std::fs::remove_file(&file);
+    /// assert_eq!(logger.write_lines_shared_time(["one", "two", "three"]).unwrap(), 3);
     /// 
-    /// logger.clean_log(Some(r"example_test_\d{8}.log"));
+    /// let contents = std::fs::read_to_string(&file).unwrap();
+    /// let times: Vec<&str> = contents.lines().map(|l| l.split(' ').nth(1).unwrap()).collect();
+    /// assert!(times.iter().all(|t| *t == times[0]));
     /// ```
-    pub fn log_clean(&self, filter: Option<&str>) {
-        let path_split = match OS {
-            "linux" => PATH_SPLIT_LINUX,
-            "windows" => PATH_SPLIT_WINDOWS,
-            _ => return
-        };
+    /// # Returns
+    /// * `Ok(count)` the number of lines written, not counting lines the checks dropped
+    /// * `Err(LoggerError)` as for `try_write_log`
+    pub fn write_lines_shared_time<I: IntoIterator<Item = impl AsRef<str>>>(&self, lines: I) -> Result<usize, LoggerError> {
+        self.inner.write_batch(lines, true)
+    }
 
-        let paths = match fs::read_dir(&self.path) {
-            Ok(paths) => paths,
-            Err(e) => {
-                self.write_log(&format!("Error = Log cleaner, could not read directory: {e}"));
-                return;
-            }
-        };
+    /// Write a line to the log at the given level, e.g. "2024-01-01 10:00:00 [INFO] message".
+    /// Lines below the minimum level are dropped without touching the file.
+    /// `trace`, `debug`, `info`, `warn` and `error` are shorthands for this.
+    /// # Arguments
+    /// * `level` - The severity of the line
+    /// * `line` - The line to write to the log
+    /// # Example
+    /// ```rust
+    /// use d_logger::{Level, Logger};
+    /// 
+    /// let path = std::env::temp_dir().join("d_logger_write_level");
+    /// let logger = Logger::builder().path(&path).file_name_format("level_%Y%m%d.log").level(Level::Info).build().unwrap();
+    /// # let _ = std::fs::remove_file(path.join(chrono::Local::now().format("level_%Y%m%d.log").to_string()));
+    /// assert!(logger.write_level(Level::Warn, "disk nearly full"));
+    /// assert!(logger.info("started"));
+    /// assert!(logger.debug("dropped, below the minimum level"));
+    /// 
+    /// let contents = std::fs::read_to_string(path.join(chrono::Local::now().format("level_%Y%m%d.log").to_string())).unwrap();
+    /// let lines: Vec<&str> = contents.lines().filter(|l| !l.is_empty()).collect();
+    /// assert_eq!(lines.len(), 2);
+    /// // Timestamp, then the level token, then the message
+    /// let (time, rest) = lines[0].split_once(" [").unwrap();
+    /// assert!(chrono::NaiveDateTime::parse_from_str(time, "%Y-%m-%d %H:%M:%S").is_ok());
+    /// assert_eq!(rest, "WARN] disk nearly full");
+    /// assert!(lines[1].ends_with(" [INFO] started"));
+    /// ```
+    /// # Returns
+    /// * `true` if the line was written or dropped because of its level
+    /// * `false` if the log could not be written
+    pub fn write_level(&self, level: Level, line: &str) -> bool {
+        self.try_write_level(level, line).is_ok()
+    }
 
-        if self.days_keep.is_none() {
-            return;
+    /// Write a line to the log at the given level, returning the reason if it fails.
+    /// Lines below the minimum level are dropped and return `Ok(())`.
+    /// # Arguments
+    /// * `level` - The severity of the line
+    /// * `line` - The line to write to the log
+    /// # Example
+    /// ```rust
+    /// use d_logger::{Level, Logger};
+    /// # let path = std::env::temp_dir().join("d_logger_try_write_level");
+    /// # let logger = Logger::builder().path(path).build().unwrap();
+    /// 
+    /// logger.try_write_level(Level::Error, "This is a test log entry").unwrap();
+    /// ```
+    pub fn try_write_level(&self, level: Level, line: &str) -> Result<(), LoggerError> {
+        if level < self.level() {
+            return Ok(());
         }
+        self.inner.write_entry(Some(level), line, &[])
+    }
 
-        let file_filter = filter.unwrap_or("");
-        let now = SystemTime::now();
-        let current_time = now.duration_since(UNIX_EPOCH).unwrap().as_secs();
-        let threshold = current_time - (self.days_keep.unwrap() * 86400);
+    /// Write a line stamped with the time it happened rather than now, such as an event imported from another system.
+    /// The timestamp is rendered with the line date format in the logger's time zone. The entry goes in the file for now
+    /// unless `LoggerBuilder::file_follows_timestamp` is on. The rate limit and duplicate collapsing don't apply to it.
+    /// It isn't called `write_log_at` because that name already belongs to writing with a source `Location`
+    /// # Returns
+    /// * `true` if the line was written
+    /// # Example
+    /// ```rust
+    /// use d_logger::{test_support::ManualClock, Level, Logger};
+    /// use chrono::TimeZone;
+    /// 
+    /// let path = std::env::temp_dir().join("d_logger_write_log_stamped");
+    /// # let _ = std::fs::remove_dir_all(&path);
+    /// let now = chrono::Local.with_ymd_and_hms(2024, 5, 2, 10, 0, 0).unwrap();
+    /// let yesterday = chrono::Local.with_ymd_and_hms(2024, 5, 1, 18, 30, 0).unwrap();
+    /// let build = |follow| Logger::builder()
+    ///     .path(&path)
+    ///     .file_name_format("stamped_%Y%m%d.log")
+    ///     .days_keep(Some(7))
+    ///     .file_follows_timestamp(follow)
+    ///     .clock(ManualClock::new(now))
+    ///     .build()
+    ///     .unwrap();
+    /// let read = |name: &str| std::fs::read_to_string(path.join(name)).unwrap_or_default();
+    /// 
+    /// // By default the entry keeps its time but goes in today's file
+    /// let logger = build(false);
+    /// logger.write_log_stamped(yesterday, "imported into today");
+    /// assert!(read("stamped_20240502.log").contains("2024-05-01 18:30:00 imported into today"));
+    /// assert!(!path.join("stamped_20240501.log").exists());
+    /// 
+    /// // Following the timestamp it goes in yesterday's file, without disturbing today's
+    /// let logger = build(true);
+    /// logger.info("live");
+    /// logger.write_level_stamped(Level::Warn, yesterday, "imported into yesterday");
+    /// logger.info("still live");
+    /// assert_eq!(read("stamped_20240501.log").trim_end(), "2024-05-01 18:30:00 [WARN] imported into yesterday");
+    /// assert!(read("stamped_20240502.log").contains("[INFO] live") && read("stamped_20240502.log").contains("[INFO] still live"));
+    /// 
+    /// // Older than days_keep it stays in today's file, where log clean won't take it straight away
+    /// let long_ago = chrono::Local.with_ymd_and_hms(2024, 4, 1, 12, 0, 0).unwrap();
+    /// logger.write_log_stamped(long_ago, "too old for its own file");
+    /// assert!(!path.join("stamped_20240401.log").exists());
+    /// assert!(read("stamped_20240502.log").contains("2024-04-01 12:00:00 too old for its own file"));
+    /// ```
+    pub fn write_log_stamped(&self, ts: DateTime<Local>, line: &str) -> bool {
+        self.inner.write_stamped(None, &ts, line).is_ok()
+    }
 
-        let regex = if !file_filter.is_empty() {
-            Some(Regex::new(file_filter).unwrap())
-        } else {
-            None
-        };
+    /// `write_log_stamped` at a level, dropped if it's below the minimum level
+    pub fn write_level_stamped(&self, level: Level, ts: DateTime<Local>, line: &str) -> bool {
+        if level < self.level() {
+            return true;
+        }
+        self.inner.write_stamped(Some(level), &ts, line).is_ok()
+    }
 
-        for entry in paths.filter_map(Result::ok) {
-            let file_name = match entry.file_name().into_string() {
-                Ok(name) => name,
-                Err(_) => {
-                    self.write_log("Error = Log cleaner, could not convert file name");
-                    continue;
-                }
-            };
+    /// Write many entries stamped with their own times, each into the dated file for its timestamp, oldest first.
+    /// Entries are formatted as `write_log_stamped` would format them, and each file is opened once for all of its
+    /// entries. As with `LoggerBuilder::file_follows_timestamp`, an entry whose day is already past `days_keep` goes in
+    /// the file for now instead. Entries with the same timestamp keep the order they were given in
+    /// # Returns
+    /// * The number of entries written to each file, in the order the files were written
+    /// * `Err` with the first failure, the other files are still written
+    /// # Example
+    /// ```rust
+    /// use d_logger::{test_support::ManualClock, Logger};
+    /// use chrono::TimeZone;
+    /// 
+    /// let path = std::env::temp_dir().join("d_logger_import_entries");
+    /// # let _ = std::fs::remove_dir_all(&path);
+    /// let clock = ManualClock::new(chrono::Local.with_ymd_and_hms(2024, 5, 4, 8, 0, 0).unwrap());
+    /// let logger = Logger::builder().path(&path).file_name_format("import_%Y%m%d.log").clock(clock).build().unwrap();
+    /// let at = |day, hour| chrono::Local.with_ymd_and_hms(2024, 5, day, hour, 0, 0).unwrap();
+    /// let events = vec![
+    ///     (at(3, 9), "third day".to_string()),
+    ///     (at(1, 17), "first day, later".to_string()),
+    ///     (at(2, 12), "second day".to_string()),
+    ///     (at(1, 9), "first day, earlier".to_string()),
+    ///     (at(3, 9), "third day, same time".to_string()),
+    /// ];
+    /// let summary = logger.import_entries(events).unwrap();
+    /// assert_eq!(summary.entries, 5);
+    /// assert_eq!(summary.files, [
+    ///     (path.join("import_20240501.log"), 2),
+    ///     (path.join("import_20240502.log"), 1),
+    ///     (path.join("import_20240503.log"), 2),
+    /// ]);
+    /// 
+    /// let read = |name: &str| std::fs::read_to_string(path.join(name)).unwrap();
+    /// assert_eq!(read("import_20240501.log").lines().collect::<Vec<_>>(), ["2024-05-01 09:00:00 first day, earlier", "2024-05-01 17:00:00 first day, later"]);
+    /// assert_eq!(read("import_20240502.log").lines().collect::<Vec<_>>(), ["2024-05-02 12:00:00 second day"]);
+    /// assert_eq!(read("import_20240503.log").lines().collect::<Vec<_>>(), ["2024-05-03 09:00:00 third day", "2024-05-03 09:00:00 third day, same time"]);
+    /// 
+    /// // The same as writing them one at a time
+    /// logger.write_log_stamped(at(3, 9), "third day");
+    /// assert_eq!(read("import_20240504.log").trim_end(), read("import_20240503.log").lines().next().unwrap());
+    /// ```
+    pub fn import_entries(&self, entries: impl IntoIterator<Item = (DateTime<Local>, String)>) -> Result<ImportSummary, LoggerError> {
+        self.inner.import(entries)
+    }
 
-            if let Some(ref regex) = regex {
-                if !regex.is_match(&file_name) {
-                    continue;
-                }
-            }
+    /// Write a line at the given level with where it came from appended, e.g. "message (src/main.rs:42)".
+    /// The `d_trace!`, `d_debug!`, `d_info!`, `d_warn!` and `d_error!` macros fill in the location for you.
+    /// # Arguments
+    /// * `level` - The severity of the line
+    /// * `location` - Where in the source the line came from
+    /// * `line` - The line to write to the log
+    /// # Example
+    /// ```rust
+    /// use d_logger::{Level, Location, Logger};
+    /// # let path = std::env::temp_dir().join("d_logger_write_log_at");
+    /// # let logger = Logger::builder().path(path).build().unwrap();
+    /// 
+    /// let location = Location { file: "src/main.rs", line: 42, module_path: "my_app" };
+    /// logger.write_log_at(Level::Warn, &location, "low disk space");
+    /// ```
+    /// # Returns
+    /// * `true` if the line was written or dropped because of its level
+    /// * `false` if the log could not be written
+    pub fn write_log_at(&self, level: Level, location: &Location, line: &str) -> bool {
+        self.write_log_at_fmt(level, location, format_args!("{line}"))
+    }
 
-            let file_path = self.path.clone() + path_split + &file_name;
+    /// Write a line given as format arguments at the given level with where it came from appended, see `write_log_at`.
+    /// This is what the `d_info!` style macros call, nothing is formatted for a line below the minimum level
+    /// # Returns
+    /// * `true` if the line was written or dropped because of its level
+    /// * `false` if the log could not be written
+    pub fn write_log_at_fmt(&self, level: Level, location: &Location, args: fmt::Arguments<'_>) -> bool {
+        if level < self.level() {
+            return true;
+        }
+        format::with_args(format_args!("{args} ({location})"), |line| self.inner.write_entry(Some(level), line, &[]).is_ok())
+    }
 
-            if Path::new(&file_path).is_dir() {
-                continue;
-            }
+    /// Make a child logger that writes to the same file with a fixed prefix in front of every message, e.g. "[db] ".
+    /// The prefix is written exactly as given, so include any spacing wanted. Children can add further prefixes.
+    /// # Example
+    /// ```rust
+    /// use d_logger::Logger;
+    /// # let path = std::env::temp_dir().join("d_logger_with_prefix_fn");
+    /// # let logger = Logger::builder().path(path).build().unwrap();
+    /// 
+    /// let db = logger.with_prefix("[db] ");
+    /// db.info("connected"); // 2024-01-01 10:00:00 [INFO] [db] connected
+    /// ```
+    pub fn with_prefix(&self, prefix: &str) -> ScopedLogger<'_> {
+        ScopedLogger::new(self, prefix.to_string())
+    }
 
-            let metadata = match fs::metadata(&file_path) {
-                Ok(metadata) => metadata,
-                Err(e) => {
-                    self.write_log(&format!("Error = Log cleaner, could not read metadata from file {file_name} | {e}"));
-                    continue;
-                }
-            };
+    /// Get an `io::Write` that writes each line given to it as a log line, see `LoggerWriter`
+    /// # Example
+    /// ```rust
+    /// use d_logger::Logger;
+    /// use std::io::Write;
+    /// # let path = std::env::temp_dir().join("d_logger_writer_fn");
+    /// # let logger = Logger::builder().path(path).build().unwrap();
+    /// 
+    /// writeln!(logger.writer(), "{} workers started", 4).unwrap();
+    /// ```
+    pub fn writer(&self) -> LoggerWriter<'_> {
+        LoggerWriter::new(self)
+    }
 
-            let modified_time = match metadata.modified() {
-                Ok(modified) => match modified.duration_since(UNIX_EPOCH) {
-                    Ok(duration) => duration.as_secs(),
-                    Err(e) => {
-                        self.write_log(&format!("Error = Log cleaner, could not get modified time for file {file_name} | {e}"));
-                        continue;
-                    }
-                },
-                Err(e) => {
-                    self.write_log(&format!("Error = Log cleaner, could not read modified time from file {file_name} | {e}"));
-                    continue;
-                }
-            };
-            if modified_time < threshold {
-                if let Err(e) = fs::remove_file(&file_path) {
-                    self.write_log(&format!("Error = Log cleaner, could not delete file {file_name} | {e}"));
-                }
-            }
-        }
+    /// Write a line at Trace level, see `write_level`
+    pub fn trace(&self, line: &str) -> bool {
+        self.write_level(Level::Trace, line)
     }
-}
\ No newline at end of file
+
+    /// Write a line at Debug level, see `write_level`
+    pub fn debug(&self, line: &str) -> bool {
+        self.write_level(Level::Debug, line)
+    }
+
+    /// Write a line at Info level, see `write_level`
+    pub fn info(&self, line: &str) -> bool {
+        self.write_level(Level::Info, line)
+    }
+
+    /// Write a line at Warn level, see `write_level`
+    pub fn warn(&self, line: &str) -> bool {
+        self.write_level(Level::Warn, line)
+    }
+
+    /// Write a line at Error level, see `write_level`
+    pub fn error(&self, line: &str) -> bool {
+        self.write_level(Level::Error, line)
+    }
+
+    /// Set the minimum level written by the level methods.
+    /// Plain `write_log` calls are always written.
+    /// This works through a shared reference, so a logger shared between threads can be changed on the fly
+    /// and the new level is seen by the next write on any thread.
+    /// # Example
+    /// ```rust
+    /// use d_logger::{Level, Logger};
+    /// use std::sync::atomic::{AtomicBool, Ordering};
+    /// 
+    /// let path = std::env::temp_dir().join("d_logger_set_level");
+    /// let logger = Logger::builder().path(&path).file_name_format("set_level_%Y%m%d.log").level(Level::Debug).build().unwrap();
+    /// # let file = path.join(chrono::Local::now().format("set_level_%Y%m%d.log").to_string());
+    /// # let _ = std::fs::remove_file(&file);
+    /// let raised = AtomicBool::new(false);
+    /// 
+    /// std::thread::scope(|s| {
+    ///     for t in 0..4 {
+    ///         let (logger, raised) = (&logger, &raised);
+    ///         s.spawn(move || {
+    ///             let mut seen = false;
+    ///             for i in 0..200 {
+    ///                 // Once this thread has seen the new level none of its Debug lines may be written
+    ///                 seen = seen || logger.level() == Level::Info;
+    ///                 logger.debug(&format!("thread {t} line {i} {}", if seen { "after" } else { "before" }));
+    ///                 if i == 100 && t == 0 {
+    ///                     logger.set_level(Level::Info);
+    ///                     raised.store(true, Ordering::SeqCst);
+    ///                 }
+    ///             }
+    ///         });
+    ///     }
+    /// });
+    /// 
+    /// assert!(raised.load(Ordering::SeqCst));
+    /// let contents = std::fs::read_to_string(&file).unwrap();
+    /// assert!(contents.contains("[DEBUG]"));
+    /// assert!(!contents.contains("after"));
+    /// ```
+    pub fn set_level(&self, level: Level) {
+        self.inner.level.store(level as u8, Ordering::Relaxed);
+    }
+
+    /// The log directory, after any expansion asked for with `LoggerBuilder::expand_path`
+    /// # Example
+    /// ```rust
+    /// use d_logger::Logger;
+    /// 
+    /// let path = std::env::temp_dir().join("d_logger_path");
+    /// let logger = Logger::builder().path(&path).build().unwrap();
+    /// assert_eq!(logger.path(), path);
+    /// ```
+    pub fn path(&self) -> &Path {
+        &self.inner.path
+    }
+
+    /// The dated file a write made right now would go to, rendered the same way `write_log` does.
+    /// Entries for a routed level may go to their own file instead
+    /// # Example
+    /// ```rust
+    /// use d_logger::Logger;
+    /// 
+    /// let path = std::env::temp_dir().join("d_logger_current_file_path");
+    /// let logger = Logger::builder().path(&path).file_name_format("current_%Y%m%d.log").build().unwrap();
+    /// assert_eq!(logger.current_file_path(), path.join(chrono::Local::now().format("current_%Y%m%d.log").to_string()));
+    /// 
+    /// logger.write_log("where did this go");
+    /// assert!(std::fs::read_to_string(logger.current_file_path()).unwrap().contains("where did this go"));
+    /// ```
+    /// The name is only rendered again once the time leaves the period it was rendered for, the day for a format
+    /// holding only the date or the hour for one that goes down to hours. Changing the format renders it again
+    /// ```rust
+    /// use d_logger::{test_support::ManualClock, Logger};
+    /// use chrono::TimeZone;
+    /// use std::time::Duration;
+    /// 
+    /// let path = std::env::temp_dir().join("d_logger_current_file_path_changes");
+    /// # let _ = std::fs::remove_dir_all(&path);
+    /// let clock = ManualClock::new(chrono::Local.with_ymd_and_hms(2024, 2, 28, 23, 0, 0).unwrap());
+    /// let logger = Logger::builder().path(&path).file_name_format("changes_%Y%m%d.log").clock(clock.clone()).build().unwrap();
+    /// logger.write_log("first");
+    /// clock.advance(Duration::from_secs(3599));
+    /// assert_eq!(logger.current_file_path(), path.join("changes_20240228.log"));
+    /// clock.advance(Duration::from_secs(1));
+    /// assert_eq!(logger.current_file_path(), path.join("changes_20240229.log"));
+    /// logger.write_log("second");
+    /// assert!(!std::fs::read_to_string(path.join("changes_20240228.log")).unwrap().contains("second"));
+    /// assert!(std::fs::read_to_string(path.join("changes_20240229.log")).unwrap().contains("second"));
+    /// 
+    /// logger.set_file_name_format("changes_%Y%m%d_%H.log").unwrap();
+    /// assert_eq!(logger.current_file_path(), path.join("changes_20240229_00.log"));
+    /// clock.advance(Duration::from_secs(3600));
+    /// assert_eq!(logger.current_file_path(), path.join("changes_20240229_01.log"));
+    /// 
+    /// // Going back before the cached period renders the earlier name again
+    /// clock.set(chrono::Local.with_ymd_and_hms(2024, 2, 28, 12, 0, 0).unwrap());
+    /// assert_eq!(logger.current_file_path(), path.join("changes_20240228_12.log"));
+    /// ```
+    pub fn current_file_path(&self) -> PathBuf {
+        let now = self.inner.now();
+        self.inner.file_for(&now).expect("the file name format is checked before it's used")
+    }
+
+    /// Read back the entries in the file for a day, rendered from the file name format at the start of that day in
+    /// the logger's time zone. Each line's timestamp is parsed with the current line date format, taking the day from
+    /// `date` if the format has none, and the level comes from the "[LEVEL]" after it. Lines that can't be read that
+    /// way, such as the continuation lines of a multi-line message, come out as `Err` and reading carries on.
+    /// Only plain output can be read back, written with the same line date format, separator, `subsec_precision`
+    /// and `sequence_numbers` settings as the logger reading it
+    /// # Returns
+    /// * `Err(LoggerError::FileOpen)` if there is no file for the day or it can't be opened
+    /// # Example
+    /// ```rust
+    /// use d_logger::{test_support::ManualClock, Level, Logger};
+    /// use chrono::{NaiveDate, TimeZone};
+    /// use std::time::Duration;
+    /// 
+    /// let path = std::env::temp_dir().join("d_logger_read_entries");
+    /// # let _ = std::fs::remove_dir_all(&path);
+    /// let clock = ManualClock::new(chrono::Local.with_ymd_and_hms(2024, 5, 1, 23, 59, 0).unwrap());
+    /// let build = || Logger::builder().path(&path).file_name_format("read_%Y%m%d.log").clock(clock.clone()).build().unwrap();
+    /// let logger = build();
+    /// logger.info("first day");
+    /// logger.write_log_kv("login", &[("user", "bob")]);
+    /// clock.advance(Duration::from_secs(90));
+    /// logger.error("second day");
+    /// std::fs::write(path.join("read_20240501.log"), std::fs::read_to_string(path.join("read_20240501.log")).unwrap() + "not an entry\n").unwrap();
+    /// 
+    /// // Another logger with the same settings reads back what the first wrote
+    /// let reader = build();
+    /// let first: Vec<_> = reader.read_entries(NaiveDate::from_ymd_opt(2024, 5, 1).unwrap()).unwrap().collect();
+    /// assert_eq!(first.len(), 3);
+    /// let entry = first[0].as_ref().unwrap();
+    /// assert_eq!(entry.ts, chrono::Local.with_ymd_and_hms(2024, 5, 1, 23, 59, 0).unwrap());
+    /// assert_eq!((entry.level, entry.message.as_str()), (Some(Level::Info), "first day"));
+    /// let entry = first[1].as_ref().unwrap();
+    /// assert_eq!((entry.level, entry.message.as_str()), (None, "login user=bob"));
+    /// let error = first[2].as_ref().unwrap_err();
+    /// assert_eq!((error.line_number, error.line.as_str()), (3, "not an entry"));
+    /// 
+    /// let second: Vec<_> = reader.read_entries(NaiveDate::from_ymd_opt(2024, 5, 2).unwrap()).unwrap().map(Result::unwrap).collect();
+    /// assert_eq!(second.len(), 1);
+    /// assert_eq!(second[0].ts, chrono::Local.with_ymd_and_hms(2024, 5, 2, 0, 0, 30).unwrap());
+    /// assert_eq!((second[0].level, second[0].message.as_str()), (Some(Level::Error), "second day"));
+    /// 
+    /// assert!(reader.read_entries(NaiveDate::from_ymd_opt(2024, 5, 3).unwrap()).is_err());
+    /// ```
+    pub fn read_entries(&self, date: NaiveDate) -> Result<impl Iterator<Item = Result<LogEntry, ParseError>> + use<>, LoggerError> {
+        let path = self.inner.file_for_date(date)?;
+        let file = fs::File::open(&path).map_err(|source| LoggerError::FileOpen { path: path.clone(), source })?;
+        Ok(Entries { path, reader: io::BufReader::new(file), parser: self.inner.line_parser(), date, line_number: 0, buf: Vec::new(), failed: false })
+    }
+
+    /// Find the lines matching a regex in the files for the days from `from` to `to`, both included, oldest first.
+    /// Only the files named for those days by the file name format are opened, and days without a file are passed over.
+    /// The pattern is matched against the whole line, timestamp and level included, and the search stops once
+    /// `limit` lines have been found
+    /// # Returns
+    /// * `Err(LoggerError::InvalidSearch)` if the pattern did not compile
+    /// * `Err(LoggerError::FileOpen)` if a file for one of the days exists but can't be read
+    /// # Example
+    /// ```rust
+    /// use d_logger::{test_support::ManualClock, Logger};
+    /// use chrono::{NaiveDate, TimeZone};
+    /// 
+    /// let path = std::env::temp_dir().join("d_logger_search");
+    /// # let _ = std::fs::remove_dir_all(&path);
+    /// let clock = ManualClock::new(chrono::Local.with_ymd_and_hms(2024, 5, 1, 9, 0, 0).unwrap());
+    /// let logger = Logger::builder().path(&path).file_name_format("search_%Y%m%d.log").clock(clock.clone()).build().unwrap();
+    /// for day in 1..=3 {
+    ///     clock.set(chrono::Local.with_ymd_and_hms(2024, 5, day, 9, 0, 0).unwrap());
+    ///     logger.info(&format!("day {day} started"));
+    ///     logger.error(&format!("day {day} timeout talking to db"));
+    ///     logger.error(&format!("day {day} timeout talking to cache"));
+    /// }
+    /// let date = |day| NaiveDate::from_ymd_opt(2024, 5, day).unwrap();
+    /// 
+    /// // Only the days in the range
+    /// let hits = logger.search(r"ERROR\] .* timeout", date(2), date(3), None).unwrap();
+    /// assert_eq!(hits.len(), 4);
+    /// assert_eq!(hits[0].path, path.join("search_20240502.log"));
+    /// assert_eq!(hits[0].line_number, 2);
+    /// assert_eq!(hits[0].ts, Some(chrono::Local.with_ymd_and_hms(2024, 5, 2, 9, 0, 0).unwrap()));
+    /// assert!(hits[0].line.ends_with("[ERROR] day 2 timeout talking to db"));
+    /// assert!(hits[3].line.ends_with("[ERROR] day 3 timeout talking to cache"));
+    /// 
+    /// // Stopping at the limit, with days that have no file passed over
+    /// let hits = logger.search("timeout", date(1), date(30), Some(3)).unwrap();
+    /// let lines: Vec<_> = hits.iter().map(|hit| hit.line.rsplit("] ").next().unwrap()).collect();
+    /// assert_eq!(lines, ["day 1 timeout talking to db", "day 1 timeout talking to cache", "day 2 timeout talking to db"]);
+    /// 
+    /// assert!(logger.search("timeout", date(3), date(1), None).unwrap().is_empty());
+    /// assert!(logger.search("(", date(1), date(3), None).is_err());
+    /// ```
+    pub fn search(&self, pattern: &str, from: NaiveDate, to: NaiveDate, limit: Option<usize>) -> Result<Vec<SearchHit>, LoggerError> {
+        let regex = regex::Regex::new(pattern).map_err(LoggerError::InvalidSearch)?;
+        let parser = self.inner.line_parser();
+        let limit = limit.unwrap_or(usize::MAX);
+        let mut hits = Vec::new();
+        for (date, path) in self.inner.files_for_days(from, to)? {
+            if hits.len() >= limit {
+                break;
+            }
+            let file = match fs::File::open(&path) {
+                Ok(file) => file,
+                Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+                Err(source) => return Err(LoggerError::FileOpen { path, source }),
+            };
+            let mut reader = io::BufReader::new(file);
+            let mut buf = Vec::new();
+            let mut line_number = 0;
+            while hits.len() < limit {
+                line_number += 1;
+                let line = match read::next_line(&mut reader, &mut buf) {
+                    Ok(Some(line)) => String::from_utf8_lossy(line),
+                    Ok(None) => break,
+                    Err(source) => return Err(LoggerError::FileOpen { path, source }),
+                };
+                if regex.is_match(&line) {
+                    let ts = parser.parse(&line, date).ok().map(|entry| entry.ts);
+                    hits.push(SearchHit { path: path.clone(), line_number, ts, line: line.into_owned() });
+                }
+            }
+        }
+        Ok(hits)
+    }
+
+    /// The last `n` lines of the current file, oldest first, read back from the end of the file so a large file isn't
+    /// read through. A last line without a newline is included, and "\r\n" endings are taken off as well as "\n".
+    /// Lines are decoded lossily, and anything appended while the file is being read is left out.
+    /// Entries still held in the buffer set with `LoggerBuilder::buffer_capacity` aren't in the file until a flush
+    /// # Returns
+    /// * `Err(LoggerError::FileOpen)` if nothing has been written to today's file yet or it can't be read
+    /// # Example
+    /// ```rust
+    /// use d_logger::Logger;
+    /// 
+    /// let path = std::env::temp_dir().join("d_logger_tail_file");
+    /// # let _ = std::fs::remove_dir_all(&path);
+    /// let logger = Logger::builder().path(&path).file_name_format("tail.log").line_date_format("").separator("").build().unwrap();
+    /// for i in 1..=5 {
+    ///     logger.write_log(&format!("line {i}"));
+    /// }
+    /// assert_eq!(logger.tail_file(2).unwrap(), ["line 4", "line 5"]);
+    /// assert_eq!(logger.tail_file(5).unwrap(), ["line 1", "line 2", "line 3", "line 4", "line 5"]);
+    /// assert_eq!(logger.tail_file(50).unwrap().len(), 5);
+    /// assert!(logger.tail_file(0).unwrap().is_empty());
+    /// 
+    /// // Tailing while another thread appends sees whole lines, up to however far the file had got
+    /// std::thread::scope(|s| {
+    ///     s.spawn(|| for i in 6..2000 { logger.write_log(&format!("line {i}")); });
+    ///     for _ in 0..100 {
+    ///         let lines = logger.tail_file(10).unwrap();
+    ///         assert!(lines.len() >= 5);
+    ///         assert!(lines[..lines.len() - 1].iter().all(|line| line.starts_with("line ")));
+    ///     }
+    /// });
+    /// 
+    /// // A line still being written counts, and CRLF endings come off too
+    /// std::fs::write(path.join("tail.log"), "first\r\nsecond\r\nthird, half writ").unwrap();
+    /// assert_eq!(logger.tail_file(2).unwrap(), ["second", "third, half writ"]);
+    /// 
+    /// // Far more than one read from the end
+    /// let long: String = (0..5000).map(|i| format!("entry number {i}\n")).collect();
+    /// std::fs::write(path.join("tail.log"), long).unwrap();
+    /// assert_eq!(logger.tail_file(3).unwrap(), ["entry number 4997", "entry number 4998", "entry number 4999"]);
+    /// assert_eq!(logger.tail_file(4000).unwrap()[0], "entry number 1000");
+    /// 
+    /// // Bytes cut off in the middle of a character don't stop the read
+    /// std::fs::write(path.join("tail.log"), b"ok\nbroken \xe2\x82").unwrap();
+    /// assert_eq!(logger.tail_file(2).unwrap(), ["ok", "broken \u{fffd}"]);
+    /// ```
+    pub fn tail_file(&self, n: usize) -> Result<Vec<String>, LoggerError> {
+        let path = self.current_file_path();
+        let mut file = fs::File::open(&path).map_err(|source| LoggerError::FileOpen { path: path.clone(), source })?;
+        read::tail(&mut file, n).map_err(|source| LoggerError::FileOpen { path, source })
+    }
+
+    /// Copy the files for the days from `from` to `to`, both included, into `dest` one after the other, oldest first,
+    /// for a support bundle. Only the files named for those days by the file name format are read, and a file gzipped
+    /// with `LoggerBuilder::compress` is unzipped on the way. A file whose last line has no newline gets one so the
+    /// next file starts on a line of its own. Days without a file are passed over and listed in the summary.
+    /// Entries still held in the buffer set with `LoggerBuilder::buffer_capacity` aren't in the file until a flush
+    /// # Returns
+    /// * `Err(LoggerError::FileOpen)` if a file for one of the days exists but can't be read
+    /// * `Err(LoggerError::Write)` if writing to `dest` failed
+    /// # Example
+    /// ```rust
+    /// use d_logger::{test_support::ManualClock, ExportSummary, Logger};
+    /// use chrono::{NaiveDate, TimeZone};
+    /// 
+    /// let path = std::env::temp_dir().join("d_logger_export_range");
+    /// # let _ = std::fs::remove_dir_all(&path);
+    /// let clock = ManualClock::new(chrono::Local.with_ymd_and_hms(2024, 5, 1, 9, 0, 0).unwrap());
+    /// let logger = Logger::builder().path(&path).file_name_format("export_%Y%m%d.log").clock(clock.clone()).build().unwrap();
+    /// for day in [1, 2, 4] {
+    ///     clock.set(chrono::Local.with_ymd_and_hms(2024, 5, day, 9, 0, 0).unwrap());
+    ///     logger.info(&format!("day {day} first"));
+    ///     logger.info(&format!("day {day} second"));
+    /// }
+    /// let date = |day| NaiveDate::from_ymd_opt(2024, 5, day).unwrap();
+    /// 
+    /// let mut bundle = Vec::new();
+    /// let summary = logger.export_range(date(2), date(5), &mut bundle).unwrap();
+    /// let bundle = String::from_utf8(bundle).unwrap();
+    /// let messages: Vec<&str> = bundle.lines().map(|line| line.rsplit("] ").next().unwrap()).collect();
+    /// assert_eq!(messages, ["day 2 first", "day 2 second", "day 4 first", "day 4 second"]);
+    /// let expected = std::fs::metadata(path.join("export_20240502.log")).unwrap().len() + std::fs::metadata(path.join("export_20240504.log")).unwrap().len();
+    /// assert_eq!(summary, ExportSummary { files: 2, bytes: expected, missing_days: vec![date(3), date(5)] });
+    /// 
+    /// // A last line left without a newline doesn't run into the next file
+    /// std::fs::write(path.join("export_20240501.log"), "unfinished").unwrap();
+    /// let mut bundle = Vec::new();
+    /// logger.export_range(date(1), date(2), &mut bundle).unwrap();
+    /// assert!(String::from_utf8(bundle).unwrap().starts_with("unfinished\n2024-05-02 09:00:00 [INFO] day 2 first"));
+    /// ```
+    pub fn export_range(&self, from: NaiveDate, to: NaiveDate, dest: &mut impl io::Write) -> Result<ExportSummary, LoggerError> {
+        let mut summary = ExportSummary::default();
+        for (date, path) in self.inner.files_for_days(from, to)? {
+            match read::export_file(&path, dest)? {
+                Some(bytes) => {
+                    summary.files += 1;
+                    summary.bytes += bytes;
+                }
+                None => summary.missing_days.push(date),
+            }
+        }
+        Ok(summary)
+    }
+
+    /// `export_range` gzipping what it writes to `dest`. The byte count in the summary is before compression
+    /// # Example
+    /// ```rust
+    /// use d_logger::Logger;
+    /// use std::io::Read;
+    /// 
+    /// let path = std::env::temp_dir().join("d_logger_export_range_gz");
+    /// # let _ = std::fs::remove_dir_all(&path);
+    /// let logger = Logger::builder().path(&path).file_name_format("export_%Y%m%d.log").build().unwrap();
+    /// logger.write_log("in the bundle");
+    /// let today = chrono::Local::now().date_naive();
+    /// 
+    /// let mut gz = Vec::new();
+    /// let summary = logger.export_range_gz(today, today, &mut gz).unwrap();
+    /// let mut unzipped = String::new();
+    /// flate2::read::GzDecoder::new(gz.as_slice()).read_to_string(&mut unzipped).unwrap();
+    /// assert!(unzipped.ends_with(" in the bundle\n") || unzipped.ends_with(" in the bundle\r\n"));
+    /// assert_eq!(summary.bytes, unzipped.len() as u64);
+    /// ```
+    #[cfg(feature = "compress")]
+    pub fn export_range_gz(&self, from: NaiveDate, to: NaiveDate, dest: &mut impl io::Write) -> Result<ExportSummary, LoggerError> {
+        let mut encoder = flate2::write::GzEncoder::new(dest, flate2::Compression::default());
+        let summary = self.export_range(from, to, &mut encoder)?;
+        encoder.finish().map_err(LoggerError::Write)?;
+        Ok(summary)
+    }
+
+    /// Get the date format for the file name
+    pub fn file_name_format(&self) -> String {
+        self.inner.file_name_format.read().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    /// Change the date format for the file name, checked the same way as when building the logger.
+    /// The open file is flushed and let go, so the next write starts the file for the new name
+    /// # Example
+    /// ```rust
+    /// use d_logger::{Logger, LoggerError};
+    /// 
+    /// let path = std::env::temp_dir().join("d_logger_set_file_name_format");
+    /// let logger = Logger::builder().path(&path).file_name_format("before_%Y%m%d.log").build().unwrap();
+    /// logger.write_log("first");
+    /// 
+    /// logger.set_file_name_format("after_%Y%m%d.log").unwrap();
+    /// assert_eq!(logger.file_name_format(), "after_%Y%m%d.log");
+    /// # let _ = std::fs::remove_file(logger.current_file_path());
+    /// logger.write_log("second");
+    /// let contents = std::fs::read_to_string(logger.current_file_path()).unwrap();
+    /// assert!(contents.contains("second") && !contents.contains("first"));
+    /// 
+    /// assert!(matches!(logger.set_file_name_format("logs/%Y.log"), Err(LoggerError::InvalidFormat(_))));
+    /// assert_eq!(logger.file_name_format(), "after_%Y%m%d.log");
+    /// ```
+    /// # Returns
+    /// * `Err(LoggerError::InvalidFormat)` if the format can't be rendered or doesn't give a usable file name, nothing is changed
+    /// * `Err(LoggerError::Flush)` if what was pending for the old file could not be written out, the new format is still used
+    pub fn set_file_name_format(&self, format: &str) -> Result<(), LoggerError> {
+        let inner = &self.inner;
+        validate_formats(format, &inner.line_date_format.read().unwrap_or_else(|e| e.into_inner()))?;
+        let mut file_name_format = inner.file_name_format.write().unwrap_or_else(|e| e.into_inner());
+        *file_name_format = format.to_string();
+        // Cleared while the format is locked, so a name from the old format can't be cached again
+        inner.file_name_cache.clear();
+        drop(file_name_format);
+        match inner.file.lock().unwrap_or_else(|e| e.into_inner()).take() {
+            Some(mut file) => file.sync(),
+            None => Ok(()),
+        }
+    }
+
+    /// Get the date format for lines
+    pub fn line_date_format(&self) -> String {
+        self.inner.line_date_format.read().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    /// Change the date format for lines, checked the same way as when building the logger
+    /// # Example
+    /// ```rust
+    /// use d_logger::{Logger, LoggerError};
+    /// 
+    /// let path = std::env::temp_dir().join("d_logger_set_line_date_format");
+    /// let logger = Logger::builder().path(&path).file_name_format("line_format.log").build().unwrap();
+    /// # let _ = std::fs::remove_file(logger.current_file_path());
+    /// logger.set_line_date_format("[fixed]").unwrap();
+    /// logger.write_log("entry");
+    /// assert!(std::fs::read_to_string(logger.current_file_path()).unwrap().starts_with("[fixed] entry"));
+    /// 
+    /// assert!(matches!(logger.set_line_date_format("%Y-%Q"), Err(LoggerError::InvalidFormat(_))));
+    /// assert_eq!(logger.line_date_format(), "[fixed]");
+    /// ```
+    /// # Returns
+    /// * `Err(LoggerError::InvalidFormat)` if the format can't be rendered, nothing is changed
+    pub fn set_line_date_format(&self, format: &str) -> Result<(), LoggerError> {
+        let inner = &self.inner;
+        validate_formats(&inner.file_name_format.read().unwrap_or_else(|e| e.into_inner()), format)?;
+        *inner.line_date_format.write().unwrap_or_else(|e| e.into_inner()) = format.to_string();
+        Ok(())
+    }
+
+    /// Get the number of days log clean keeps, None if it's disabled
+    pub fn days_keep(&self) -> Option<u64> {
+        *self.inner.days_keep.read().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Change the number of days log clean keeps, None to disable it. Used from the next clean on
+    pub fn set_days_keep(&self, days_keep: Option<u64>) {
+        *self.inner.days_keep.write().unwrap_or_else(|e| e.into_inner()) = days_keep;
+    }
+
+    /// Get the minimum level written by the level methods
+    /// # Example
+    /// ```rust
+    /// use d_logger::{Level, Logger};
+    /// # let path = std::env::temp_dir().join("d_logger_level");
+    /// # let logger = Logger::builder().path(path).build().unwrap();
+    /// 
+    /// logger.set_level(Level::Warn);
+    /// assert_eq!(logger.level(), Level::Warn);
+    /// ```
+    pub fn level(&self) -> Level {
+        Level::from(self.inner.level.load(Ordering::Relaxed))
+    }
+
+    /// Change where entries are echoed besides the file, see `ConsoleMode`.
+    /// Like the level this can be changed through a shared reference while other threads are writing
+    /// # Example
+    /// ```rust
+    /// use d_logger::{test_support::ConsoleCapture, ConsoleMode, Logger};
+    /// let path = std::env::temp_dir().join("d_logger_console");
+    /// # let _ = std::fs::remove_dir_all(&path);
+    /// let capture = ConsoleCapture::new();
+    /// 
+    /// let logger = Logger::builder().path(&path).line_date_format("").separator("").console(ConsoleMode::Stderr).console_capture(capture.clone()).build().unwrap();
+    /// assert_eq!(logger.console(), ConsoleMode::Stderr);
+    /// logger.write_log("echoed to stderr");
+    /// 
+    /// logger.set_console(ConsoleMode::Stdout);
+    /// logger.write_log("echoed to stdout");
+    /// 
+    /// logger.set_console(ConsoleMode::Off);
+    /// assert_eq!(logger.console(), ConsoleMode::Off);
+    /// logger.write_log("only in the file");
+    /// 
+    /// assert_eq!(capture.stderr().lines().collect::<Vec<_>>(), ["echoed to stderr"]);
+    /// assert_eq!(capture.stdout().lines().collect::<Vec<_>>(), ["echoed to stdout"]);
+    /// let file = std::fs::read_dir(&path).unwrap().next().unwrap().unwrap().path();
+    /// assert_eq!(std::fs::read_to_string(file).unwrap().lines().count(), 3);
+    /// ```
+    pub fn set_console(&self, mode: ConsoleMode) {
+        self.inner.console.store(mode as u8, Ordering::Relaxed);
+    }
+
+    /// Get where entries are echoed besides the file
+    pub fn console(&self) -> ConsoleMode {
+        ConsoleMode::from(self.inner.console.load(Ordering::Relaxed))
+    }
+
+    /// Copy every entry to another sink as well as the dated file, which stays the primary.
+    /// Sinks get the same text as the file, newline included, once the entry has been written to it.
+    /// An entry that couldn't be written reaches no sink and isn't echoed to the console.
+    /// A sink failing doesn't make the write fail, it's counted by `sink_errors` instead
+    /// # Arguments
+    /// * `sink` - Where to copy entries, e.g. a `FileSink` or `MemorySink`
+    /// # Example
+    /// ```rust
+    /// use d_logger::{FileSink, Logger, MemorySink};
+    /// 
+    /// let path = std::env::temp_dir().join("d_logger_add_sink");
+    /// let logger = Logger::builder().path(&path).file_name_format("sink_%Y%m%d.log").build().unwrap();
+    /// let file = path.join(chrono::Local::now().format("sink_%Y%m%d.log").to_string());
+    /// let copy = path.join("copy.log");
+    /// # let _ = std::fs::remove_file(&file);
+    /// # let _ = std::fs::remove_file(&copy);
+    /// let memory = MemorySink::new();
+    /// logger.add_sink(Box::new(memory.clone()));
+    /// logger.add_sink(Box::new(FileSink::new(&copy).unwrap()));
+    /// 
+    /// logger.write_log("first");
+    /// logger.info("second");
+    /// 
+    /// let entries = memory.entries();
+    /// assert_eq!(entries.len(), 2);
+    /// assert!(entries[1].trim_end().ends_with("[INFO] second"));
+    /// assert_eq!(std::fs::read_to_string(&copy).unwrap(), entries.concat());
+    /// assert_eq!(std::fs::read_to_string(&file).unwrap(), entries.concat());
+    /// assert_eq!(logger.sink_errors(), 0);
+    /// ```
+    /// A write that fails leaves the sinks alone
+    /// ```rust
+    /// use d_logger::{Logger, MemorySink};
+    /// 
+    /// let path = std::env::temp_dir().join("d_logger_add_sink_failed");
+    /// # let _ = std::fs::remove_file(&path);
+    /// let logger = Logger::builder().path(&path).build().unwrap();
+    /// let memory = MemorySink::new();
+    /// logger.add_sink(Box::new(memory.clone()));
+    /// 
+    /// // Put a file where the directory was so the write fails
+    /// std::fs::remove_dir_all(&path).unwrap();
+    /// std::fs::write(&path, "").unwrap();
+    /// assert!(!logger.write_log("never written"));
+    /// assert!(memory.entries().is_empty());
+    /// 
+    /// std::fs::remove_file(&path).unwrap();
+    /// std::fs::create_dir(&path).unwrap();
+    /// assert!(logger.write_log("written"));
+    /// assert_eq!(memory.entries().len(), 1);
+    /// assert!(memory.entries()[0].trim_end().ends_with(" written"));
+    /// ```
+    pub fn add_sink(&self, sink: Box<dyn Sink + Send + Sync>) {
+        self.inner.sinks.write().unwrap_or_else(|e| e.into_inner()).push(sink);
+    }
+
+    /// The last `n` entries written, oldest first, from the first added sink that keeps them such as `RingBufferSink`.
+    /// See there for an example
+    /// # Returns
+    /// * `None` if no such sink has been added
+    pub fn tail(&self, n: usize) -> Option<Vec<String>> {
+        self.inner.sinks.read().unwrap_or_else(|e| e.into_inner()).iter().find_map(|sink| sink.tail(n))
+    }
+
+    /// How many times writing an entry to one of the added sinks has failed
+    /// # Example
+    /// ```rust
+    /// use d_logger::{Logger, Sink};
+    /// 
+    /// struct Broken;
+    /// impl Sink for Broken {
+    ///     fn write_entry(&self, _formatted: &str) -> std::io::Result<()> {
+    ///         Err(std::io::Error::other("unplugged"))
+    ///     }
+    /// }
+    /// 
+    /// let path = std::env::temp_dir().join("d_logger_sink_errors");
+    /// let logger = Logger::builder().path(path).build().unwrap();
+    /// logger.add_sink(Box::new(Broken));
+    /// assert!(logger.write_log("the file still gets this"));
+    /// assert_eq!(logger.sink_errors(), 1);
+    /// ```
+    pub fn sink_errors(&self) -> u64 {
+        self.inner.sink_errors.load(Ordering::Relaxed)
+    }
+
+    /// Call `callback` each time a file is rotated, deleted or compressed, or entries fail to be written, see `LogEvent`.
+    /// Callbacks run on the thread that caused the event once the logger has released its locks, in the order they
+    /// were added. A panic in one is caught and counted by `event_panics`
+    pub fn on_event(&self, callback: impl Fn(LogEvent) + Send + Sync + 'static) {
+        self.inner.events.add(Arc::new(callback));
+    }
+
+    /// Call `callback` with the old and new paths the first time a write goes to a differently named main file,
+    /// the `LogEvent::FileChanged` events from `on_event`. For following the file `current_file_path` names
+    /// # Example
+    /// ```rust
+    /// use d_logger::{test_support::ManualClock, Logger};
+    /// use chrono::TimeZone;
+    /// use std::sync::{Arc, Mutex};
+    /// use std::time::Duration;
+    /// 
+    /// let path = std::env::temp_dir().join("d_logger_on_file_change");
+    /// # let _ = std::fs::remove_dir_all(&path);
+    /// let clock = ManualClock::new(chrono::Local.with_ymd_and_hms(2024, 5, 1, 23, 59, 0).unwrap());
+    /// let logger = Logger::builder().path(&path).file_name_format("follow_%Y%m%d.log").clock(clock.clone()).build().unwrap();
+    /// let changes = Arc::new(Mutex::new(Vec::new()));
+    /// let seen = changes.clone();
+    /// logger.on_file_change(move |from, to| seen.lock().unwrap().push((from.to_path_buf(), to.to_path_buf())));
+    /// 
+    /// logger.write_log("before midnight");
+    /// logger.write_log("still before");
+    /// clock.advance(Duration::from_secs(120));
+    /// logger.write_log("after midnight");
+    /// logger.write_log("still after");
+    /// 
+    /// assert_eq!(*changes.lock().unwrap(), [(path.join("follow_20240501.log"), path.join("follow_20240502.log"))]);
+    /// assert_eq!(logger.current_file_path(), path.join("follow_20240502.log"));
+    /// ```
+    pub fn on_file_change(&self, callback: impl Fn(&Path, &Path) + Send + Sync + 'static) {
+        self.on_event(move |event| {
+            if let LogEvent::FileChanged { from, to } = event {
+                callback(&from, &to);
+            }
+        });
+    }
+
+    /// How many times a callback added with `on_event` has panicked
+    /// # Example
+    /// ```rust
+    /// use d_logger::Logger;
+    /// 
+    /// let path = std::env::temp_dir().join("d_logger_event_panics");
+    /// # let _ = std::fs::remove_file(&path);
+    /// # let _ = std::fs::remove_dir_all(&path);
+    /// let logger = Logger::builder().path(&path).file_name_format("panics.log").build().unwrap();
+    /// logger.on_event(|_| panic!("callback bug"));
+    /// 
+    /// // With a file in place of the directory the write fails, the panic is caught and writing works again afterwards
+    /// std::fs::remove_dir_all(&path).unwrap();
+    /// std::fs::write(&path, "").unwrap();
+    /// assert!(!logger.write_log("lost"));
+    /// assert_eq!(logger.event_panics(), 1);
+    /// std::fs::remove_file(&path).unwrap();
+    /// std::fs::create_dir(&path).unwrap();
+    /// assert!(logger.write_log("kept"));
+    /// ```
+    pub fn event_panics(&self) -> u64 {
+        self.inner.events.panics.load(Ordering::Relaxed)
+    }
+
+    /// Counts of what the logger has written, failed to write and cleaned, see `LoggerStats`
+    pub fn stats(&self) -> LoggerStats {
+        self.inner.stats.snapshot()
+    }
+
+    /// Set every count returned by `stats` back to zero
+    pub fn reset_stats(&self) {
+        self.inner.stats.reset();
+    }
+
+    /// The most recent thing to go wrong writing, rotating or cleaning, for finding out why `write_log` gave false.
+    /// This is a copy, an io error in it has the same kind and message as the original but not its source.
+    /// See `take_last_error` for the original
+    /// # Example
+    /// ```rust
+    /// use d_logger::{Logger, LoggerError};
+    /// 
+    /// let path = std::env::temp_dir().join("d_logger_last_error");
+    /// # let _ = std::fs::remove_file(&path);
+    /// let logger = Logger::builder().path(&path).file_name_format("last_error.log").build().unwrap();
+    /// assert!(logger.last_error().is_none());
+    /// 
+    /// // With a file in place of the directory the log file can't be opened
+    /// std::fs::remove_dir_all(&path).unwrap();
+    /// std::fs::write(&path, "").unwrap();
+    /// assert!(!logger.write_log("lost"));
+    /// match logger.last_error() {
+    ///     Some(LoggerError::FileOpen { path: file, source }) => {
+    ///         assert_eq!(file, path.join("last_error.log"));
+    ///         let kind = if cfg!(windows) { std::io::ErrorKind::NotFound } else { std::io::ErrorKind::NotADirectory };
+    ///         assert_eq!(source.kind(), kind);
+    ///     }
+    ///     other => panic!("expected an open failure, got {other:?}"),
+    /// }
+    /// assert!(logger.last_error().unwrap().to_string().starts_with("could not open log file"));
+    /// assert!(logger.last_error_time().unwrap() <= chrono::Local::now());
+    /// 
+    /// assert!(matches!(logger.take_last_error(), Some(LoggerError::FileOpen { .. })));
+    /// assert!(logger.last_error().is_none());
+    /// # std::fs::remove_file(&path).unwrap();
+    /// ```
+    pub fn last_error(&self) -> Option<LoggerError> {
+        self.inner.last_error.lock().unwrap_or_else(|e| e.into_inner()).as_ref().map(|(_, e)| e.duplicate())
+    }
+
+    /// When the error returned by `last_error` happened
+    pub fn last_error_time(&self) -> Option<DateTime<FixedOffset>> {
+        self.inner.last_error.lock().unwrap_or_else(|e| e.into_inner()).as_ref().map(|(time, _)| *time)
+    }
+
+    /// Take the most recent error, see `last_error`. Until something else fails `last_error` then gives None
+    pub fn take_last_error(&self) -> Option<LoggerError> {
+        self.inner.last_error.lock().unwrap_or_else(|e| e.into_inner()).take().map(|(_, e)| e)
+    }
+
+    /// Replace every match of a regex in messages and key value pair values before they're written, e.g. tokens or
+    /// email addresses. Redactions run in the order they were added, before the entry reaches the file,
+    /// the console or any sink. The timestamp and the rest of the line are left alone
+    /// # Arguments
+    /// * `pattern` - The regex to look for
+    /// * `replacement` - What to write instead, capture groups can be used as `$1`
+    /// # Example
+    /// ```rust
+    /// use d_logger::{Logger, MemorySink};
+    /// 
+    /// let path = std::env::temp_dir().join("d_logger_add_redaction");
+    /// let logger = Logger::builder().path(&path).file_name_format("redact_%Y%m%d.log").build().unwrap();
+    /// let file = path.join(chrono::Local::now().format("redact_%Y%m%d.log").to_string());
+    /// # let _ = std::fs::remove_file(&file);
+    /// let memory = MemorySink::new();
+    /// logger.add_sink(Box::new(memory.clone()));
+    /// logger.add_redaction(r"password=\S+", "password=***").unwrap();
+    /// assert!(logger.add_redaction("(unclosed", "").is_err());
+    /// 
+    /// logger.write_log("login password=hunter2 ok");
+    /// logger.write_log_kv("retry", &[("url", "https://host/?password=hunter2")]);
+    /// 
+    /// let contents = std::fs::read_to_string(&file).unwrap();
+    /// assert!(!contents.contains("hunter2"));
+    /// assert!(contents.contains("login password=*** ok"));
+    /// assert!(contents.contains(r#"url="https://host/?password=***""#));
+    /// assert!(memory.entries().iter().all(|entry| !entry.contains("hunter2")));
+    /// ```
+    /// # Returns
+    /// * `Err(LoggerError::InvalidRedaction)` if the pattern did not compile
+    pub fn add_redaction(&self, pattern: &str, replacement: &str) -> Result<(), LoggerError> {
+        self.add_redaction_rule(Redaction::new(pattern, replacement)?);
+        Ok(())
+    }
+
+    /// Add a redaction made beforehand, such as one of the built in `Redaction::credit_card`,
+    /// `Redaction::bearer_token` or `Redaction::email`. See `add_redaction`
+    pub fn add_redaction_rule(&self, redaction: Redaction) {
+        self.inner.redactions.write().unwrap_or_else(|e| e.into_inner()).push(redaction);
+    }
+
+    /// Write a line to the log followed by key=value pairs in logfmt style, in the order given.
+    /// Values containing spaces, '=', quotes or newlines are quoted and escaped, so every entry stays on one line.
+    /// # Arguments
+    /// * `msg` - The message to write to the log
+    /// * `kvs` - The key value pairs to append after the message
+    /// # Example
+    /// ```rust
+    /// use d_logger::Logger;
+    /// 
+    /// let path = std::env::temp_dir().join("d_logger_write_log_kv");
+    /// let logger = Logger::builder().path(&path).file_name_format("kv_%Y%m%d.log").build().unwrap();
+    /// # let file = path.join(chrono::Local::now().format("kv_%Y%m%d.log").to_string());
+    /// # let _ = std::fs::remove_file(&file);
+    /// assert!(logger.write_log_kv("user login", &[("user", "bob"), ("from", "10.0.0.1")]));
+    /// assert!(logger.write_log_kv("odd values", &[("name", "Bob Smith"), ("expr", "a=b"), ("quote", "say \"hi\""), ("multi", "one\ntwo"), ("empty", "")]));
+    /// 
+    /// let contents = std::fs::read_to_string(&file).unwrap();
+    /// let lines: Vec<&str> = contents.lines().collect();
+    /// assert_eq!(lines.len(), 2);
+    /// assert!(lines[0].ends_with(" user login user=bob from=10.0.0.1"));
+    /// assert!(lines[1].ends_with(r#" odd values name="Bob Smith" expr="a=b" quote="say \"hi\"" multi="one\ntwo" empty="""#));
+    /// ```
+    /// # Returns
+    /// * `true` if the log was written successfully
+    /// * `false` if the log could not be written
+    pub fn write_log_kv(&self, msg: &str, kvs: &[(&str, &str)]) -> bool {
+        self.inner.write_entry(None, msg, kvs).is_ok()
+    }
+
+    /// Write a line to the log, counted against the rate limit under `key` rather than the line itself.
+    /// Use it for messages that differ each time, e.g. with a retry count in them, but should be limited together.
+    /// Without a rate limit set on the builder this is the same as `write_log`
+    /// # Arguments
+    /// * `key` - What to count the line under
+    /// * `line` - The line to write to the log
+    /// # Example
+    /// ```rust
+    /// use d_logger::Logger;
+    /// use std::time::Duration;
+    /// 
+    /// let path = std::env::temp_dir().join("d_logger_write_log_limited");
+    /// let logger = Logger::builder().path(&path).file_name_format("limited_%Y%m%d.log").rate_limit(10, Duration::from_secs(60)).build().unwrap();
+    /// let file = path.join(chrono::Local::now().format("limited_%Y%m%d.log").to_string());
+    /// # let _ = std::fs::remove_file(&file);
+    /// for attempt in 0..100 {
+    ///     logger.write_log_limited("db retry", &format!("retrying database, attempt {attempt}"));
+    /// }
+    /// drop(logger);
+    /// 
+    /// let contents = std::fs::read_to_string(&file).unwrap();
+    /// let lines: Vec<&str> = contents.lines().filter(|l| !l.is_empty()).collect();
+    /// assert_eq!(lines.len(), 11);
+    /// assert!(lines[9].ends_with("attempt 9"));
+    /// // Anything still suppressed is summarised when the logger closes
+    /// assert!(lines[10].ends_with("suppressed 90 similar messages | db retry"));
+    /// ```
+    /// # Returns
+    /// * `true` if the line was written or dropped by the rate limit
+    /// * `false` if the log could not be written
+    pub fn write_log_limited(&self, key: &str, line: &str) -> bool {
+        self.inner.write_keyed(key, None, line, &[]).is_ok()
+    }
+
+    /// Set the text written between the timestamp and the message, can be empty.
+    /// Loggers made with `new` use an empty separator, the builder defaults to a single space.
+    /// # Example
+    /// ```rust
+    /// use d_logger::Logger;
+    /// 
+    /// let path = std::env::temp_dir().join("d_logger_set_separator");
+    /// // A date format with no specifiers renders the same every time, so the output is exact
+    /// let logger = Logger::builder().path(&path).file_name_format("separator.log").line_date_format("TIME").build().unwrap();
+    /// # let file = path.join("separator.log");
+    /// # let _ = std::fs::remove_file(&file);
+    /// logger.write_log("default");
+    /// logger.set_separator(" - ");
+    /// logger.write_log("dashed");
+    /// logger.info("level");
+    /// logger.set_separator("");
+    /// logger.write_log("glued");
+    /// 
+    /// let nl = if cfg!(windows) { "\r\n" } else { "\n" };
+    /// let expected = format!("TIME default{nl}TIME - dashed{nl}TIME - [INFO] level{nl}TIMEglued{nl}");
+    /// assert_eq!(std::fs::read(&file).unwrap(), expected.as_bytes());
+    /// ```
+    pub fn set_separator(&self, separator: &str) {
+        *self.inner.separator.write().unwrap_or_else(|e| e.into_inner()) = separator.to_string();
+    }
+
+    /// Get the text written between the timestamp and the message
+    pub fn separator(&self) -> String {
+        self.inner.separator.read().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    /// Flush any entries still held by the logger and sync the file to disk, whatever the sync policy.
+    /// With `collapse_duplicates` on, the count of repeats held back is written first
+    /// # Example
+    /// ```rust
+    /// use d_logger::{Logger, SyncPolicy};
+    /// # let path = std::env::temp_dir().join("d_logger_flush");
+    /// # let logger = Logger::builder().path(path).sync_policy(SyncPolicy::Never).build().unwrap();
+    /// 
+    /// logger.write_log("This is a test log entry");
+    /// logger.flush().unwrap();
+    /// ```
+    /// # Returns
+    /// * `Err(LoggerError::Flush)` if flushing or syncing the file failed
+    /// * Any error writing the count of repeats
+    pub fn flush(&self) -> Result<(), LoggerError> {
+        let mut result = Ok(());
+        if !self.inner.disabled
+            && let Some(repeats) = self.inner.dedup.as_ref().and_then(Dedup::pending) {
+            result = self.inner.write_unlimited(None, &dedup::summary(repeats), &[]);
+        }
+        for slot in self.inner.slots() {
+            if let Some(file) = slot.lock().unwrap_or_else(|e| e.into_inner()).as_mut() {
+                result = result.and(file.sync());
+            }
+        }
+        result
+    }
+
+    /// Move the logger onto a background thread so writes don't wait on file I/O, see `AsyncLogger`.
+    /// Up to `capacity` entries can be queued before writers have to wait for the thread to catch up
+    /// # Arguments
+    /// * `capacity` - How many entries the queue holds
+    pub fn into_background(self, capacity: usize) -> AsyncLogger {
+        AsyncLogger::spawn(self, capacity, None)
+    }
+
+    /// Like `into_background`, calling `on_error` on the writer thread for every entry that couldn't be written
+    /// # Arguments
+    /// * `capacity` - How many entries the queue holds
+    /// * `on_error` - Called with each write error
+    /// # Example
+    /// ```rust
+    /// use d_logger::{Logger, LoggerError};
+    /// use std::sync::{Arc, Mutex};
+    /// 
+    /// let path = std::env::temp_dir().join("d_logger_background_errors");
+    /// # let _ = std::fs::remove_file(&path);
+    /// let logger = Logger::builder().path(&path).build().unwrap();
+    /// let seen = Arc::new(Mutex::new(Vec::new()));
+    /// let handler_seen = Arc::clone(&seen);
+    /// let mut logger = logger.into_background_with_handler(16, move |e| handler_seen.lock().unwrap().push(e.to_string()));
+    /// 
+    /// // Writes are queued fine, opening the file fails later on the writer thread
+    /// std::fs::remove_dir_all(&path).unwrap();
+    /// std::fs::write(&path, "").unwrap();
+    /// assert!(logger.write_log("lost"));
+    /// logger.join();
+    /// assert_eq!(logger.errors(), 1);
+    /// assert!(seen.lock().unwrap()[0].starts_with("could not open log file"));
+    /// assert!(matches!(logger.try_write_log("too late"), Err(LoggerError::WriterStopped)));
+    /// # std::fs::remove_file(&path).unwrap();
+    /// ```
+    pub fn into_background_with_handler(self, capacity: usize, on_error: impl Fn(&LoggerError) + Send + 'static) -> AsyncLogger {
+        AsyncLogger::spawn(self, capacity, Some(Box::new(on_error)))
+    }
+
+    /// Close the cached file so the next write opens the current dated file again, e.g. after logrotate has moved it.
+    /// Anything still buffered is written to the old file first. A file renamed or deleted underneath the logger is
+    /// also noticed on its own within about a second
+    /// # Example
+    /// ```rust
+    /// use d_logger::Logger;
+    /// 
+    /// let path = std::env::temp_dir().join("d_logger_reopen");
+    /// let logger = Logger::builder().path(&path).file_name_format("reopen_%Y%m%d.log").build().unwrap();
+    /// let file = path.join(chrono::Local::now().format("reopen_%Y%m%d.log").to_string());
+    /// let rotated = path.join("reopen.log.1");
+    /// # let _ = std::fs::remove_file(&file);
+    /// # let _ = std::fs::remove_file(&rotated);
+    /// 
+    /// logger.write_log("before rotate");
+    /// std::fs::rename(&file, &rotated).unwrap();
+    /// logger.reopen().unwrap();
+    /// logger.write_log("after rotate");
+    /// assert!(std::fs::read_to_string(&rotated).unwrap().ends_with(&format!("before rotate{}", if cfg!(windows) { "\r\n" } else { "\n" })));
+    /// assert_eq!(std::fs::read_to_string(&file).unwrap().lines().count(), 1);
+    /// 
+    /// // Without calling reopen the move is picked up by a later write
+    /// std::fs::rename(&file, &rotated).unwrap();
+    /// std::thread::sleep(std::time::Duration::from_millis(1100));
+    /// logger.write_log("noticed the move");
+    /// assert!(std::fs::read_to_string(&file).unwrap().ends_with(&format!("noticed the move{}", if cfg!(windows) { "\r\n" } else { "\n" })));
+    /// assert!(std::fs::read_to_string(&rotated).unwrap().contains("after rotate"));
+    /// ```
+    /// # Returns
+    /// * `Err(LoggerError::Flush)` if the old file couldn't be flushed, it is closed anyway
+    pub fn reopen(&self) -> Result<(), LoggerError> {
+        let mut result = Ok(());
+        for slot in self.inner.slots() {
+            let cached = slot.lock().unwrap_or_else(|e| e.into_inner()).take();
+            if let Some(mut file) = cached {
+                result = result.and(file.sync());
+            }
+        }
+        result
+    }
+
+    /// Write the shutdown marker, flush everything still buffered and sync the file.
+    /// Dropping the last clone does the same but has to ignore any errors, close returns them.
+    /// Closing one clone shuts the shared file for all of them, a later write through another clone opens it again
+    /// # Example
+    /// ```rust
+    /// use d_logger::{Logger, SyncPolicy};
+    /// 
+    /// let path = std::env::temp_dir().join("d_logger_close");
+    /// let logger = Logger::builder()
+    ///     .path(&path)
+    ///     .file_name_format("close_%Y%m%d.log")
+    ///     .buffer_capacity(64 * 1024)
+    ///     .sync_policy(SyncPolicy::Never)
+    ///     .shutdown_marker("=== logger shutdown ===")
+    ///     .build()
+    ///     .unwrap();
+    /// let file = path.join(chrono::Local::now().format("close_%Y%m%d.log").to_string());
+    /// # let _ = std::fs::remove_file(&file);
+    /// 
+    /// for i in 0..100 {
+    ///     logger.write_log(&format!("line {i}"));
+    /// }
+    /// logger.close().unwrap();
+    /// 
+    /// let contents = std::fs::read_to_string(&file).unwrap();
+    /// let lines: Vec<&str> = contents.lines().collect();
+    /// assert_eq!(lines.len(), 101);
+    /// assert!(lines[99].ends_with("line 99"));
+    /// assert!(lines[100].ends_with("=== logger shutdown ==="));
+    /// ```
+    /// # Returns
+    /// * `Err(LoggerError)` if the marker couldn't be written or the file couldn't be flushed
+    pub fn close(self) -> Result<(), LoggerError> {
+        self.inner.shutdown()
+    }
+
+    /// Clean up log path. Will not delete any log files if the days_keep, max_files and max_total_size are all None.
+    /// Will only delete files older than the days to keep, then if max_files is set the oldest remaining by modified time
+    /// until only that many are left, then if max_total_size is set the oldest until their sizes add up to no more than it.
+    /// The file currently being written to is never deleted.
+    /// Provide Some(<regex>) to filter by name or None to delete any file older than date.
+    /// Problems with individual files are recorded in the returned `CleanReport` and cleaning carries on with the next file.
+    /// # Arguments
+    /// * `filter` - Optional regex to filter by name, None to delete any file older than date
+    /// # Example
+    /// ```rust
+    /// use d_logger::{Logger, LoggerError};
+    /// # let path = std::env::temp_dir().join("d_logger_log_clean");
+    /// # let logger = Logger::new(path, "Log%d%m%y.log".to_string(), "%Y-%m-%d %H:%M:%S ".to_string(), Some(7)).unwrap();
+    /// 
+    /// logger.log_clean(None).unwrap();
+    /// 
+    /// logger.log_clean(Some(r"example_test_\d{8}.log")).unwrap();
+    /// 
+    /// assert!(matches!(logger.log_clean(Some(r"example_test_[")), Err(LoggerError::InvalidFilter(_))));
+    /// ```
+    /// A days_keep reaching back before 1970 keeps everything rather than wrapping around
+    /// ```rust
+    /// use d_logger::Logger;
+    /// use std::time::{Duration, SystemTime};
+    /// 
+    /// let path = std::env::temp_dir().join("d_logger_log_clean_huge_days_keep");
+    /// # let _ = std::fs::remove_dir_all(&path);
+    /// let logger = Logger::builder().path(&path).days_keep(Some(u64::MAX)).build().unwrap();
+    /// std::fs::write(path.join("ancient.log"), "old").unwrap();
+    /// std::fs::File::options().append(true).open(path.join("ancient.log")).unwrap().set_modified(SystemTime::UNIX_EPOCH + Duration::from_secs(1)).unwrap();
+    /// 
+    /// let report = logger.log_clean(None).unwrap();
+    /// assert!(report.deleted.is_empty());
+    /// assert!(path.join("ancient.log").exists());
+    /// ```
+    /// File names don't have to be UTF-8, an old file with any name is cleaned like the rest
+    /// ```rust
+    /// use d_logger::{test_support::ManualClock, Logger};
+    /// use chrono::TimeZone;
+    /// use std::time::{Duration, SystemTime};
+    /// 
+    /// let path = std::env::temp_dir().join("d_logger_log_clean_non_utf8");
+    /// # let _ = std::fs::remove_dir_all(&path);
+    /// let start = chrono::Local.with_ymd_and_hms(2024, 5, 1, 12, 0, 0).unwrap();
+    /// let logger = Logger::builder().path(&path).days_keep(Some(7)).clock(ManualClock::new(start)).build().unwrap();
+    /// 
+    /// // Linux takes any bytes in a name, macOS and Windows refuse names like this one
+    /// # #[cfg(target_os = "linux")]
+    /// # {
+    /// use std::os::unix::ffi::OsStrExt;
+    /// let file = path.join(std::ffi::OsStr::from_bytes(b"old_\xff\xfe.log"));
+    /// std::fs::write(&file, "old").unwrap();
+    /// std::fs::File::options().append(true).open(&file).unwrap().set_modified(SystemTime::from(start) - Duration::from_secs(86400 * 30)).unwrap();
+    /// 
+    /// let report = logger.log_clean(Some(r"^old_.*\.log$")).unwrap();
+    /// assert_eq!(report.deleted, ["old_\u{FFFD}\u{FFFD}.log"]);
+    /// assert!(!file.exists());
+    /// # }
+    /// ```
+    /// # Returns
+    /// * `Ok(CleanReport)` what was deleted and what was left, empty if there is no days_keep, max_files or max_total_size
+    /// * `Err(LoggerError::InvalidFilter)` if the regex doesn't compile
+    /// * `Err(LoggerError::DirectoryRead)` if the log directory can't be read
+    pub fn log_clean(&self, filter: Option<&str>) -> Result<CleanReport, LoggerError> {
+        if self.inner.disabled {
+            return Ok(CleanReport::default());
+        }
+        let settings = self.inner.clean_settings(filter)?;
+        self.inner.clean(&settings)
+    }
+
+    /// Clean up the log path like `log_clean`, but move the files into an archive directory instead of deleting them.
+    /// The archive is created if it doesn't exist and can be on another file system, the files are then copied and the
+    /// originals deleted. A file whose name is already taken in the archive gets ".1", ".2" and so on added to the end
+    /// # Arguments
+    /// * `archive_dir` - Where to move the old files
+    /// * `filter` - Optional regex to filter by name, None to archive any file older than date
+    /// # Example
+    /// ```rust
+    /// use d_logger::{test_support::ManualClock, Logger};
+    /// use chrono::TimeZone;
+    /// use std::time::{Duration, SystemTime};
+    /// 
+    /// let path = std::env::temp_dir().join("d_logger_log_clean_to");
+    /// # let _ = std::fs::remove_dir_all(&path);
+    /// let start = chrono::Local.with_ymd_and_hms(2024, 5, 1, 12, 0, 0).unwrap();
+    /// let logger = Logger::builder().path(&path).days_keep(Some(7)).clock(ManualClock::new(start)).build().unwrap();
+    /// // On Linux /dev/shm is a separate file system, so the move falls back to copying
+    /// let archive = if std::path::Path::new("/dev/shm").is_dir() {
+    ///     std::path::PathBuf::from("/dev/shm/d_logger_archive")
+    /// } else {
+    ///     path.join("archive")
+    /// };
+    /// # let _ = std::fs::remove_dir_all(&archive);
+    /// 
+    /// let old = SystemTime::from(start) - Duration::from_secs(86400 * 10);
+    /// std::fs::create_dir_all(&archive).unwrap();
+    /// std::fs::write(archive.join("log_20240101.log"), "archived last time").unwrap();
+    /// for name in ["log_20240101.log", "log_20240102.log"] {
+    ///     std::fs::write(path.join(name), name).unwrap();
+    ///     std::fs::File::options().append(true).open(path.join(name)).unwrap().set_modified(old).unwrap();
+    /// }
+    /// 
+    /// logger.log_clean_to(&archive, Some(r"^log_\d{8}\.log$")).unwrap();
+    /// 
+    /// assert!(!path.join("log_20240101.log").exists());
+    /// assert_eq!(std::fs::read_to_string(archive.join("log_20240101.log")).unwrap(), "archived last time");
+    /// assert_eq!(std::fs::read_to_string(archive.join("log_20240101.log.1")).unwrap(), "log_20240101.log");
+    /// assert_eq!(std::fs::read_to_string(archive.join("log_20240102.log")).unwrap(), "log_20240102.log");
+    /// assert_eq!(std::fs::metadata(archive.join("log_20240102.log")).unwrap().modified().unwrap(), old);
+    /// ```
+    /// # Returns
+    /// * `Ok(CleanReport)` what was moved and what was left
+    /// * `Err(LoggerError::DirectoryCreate)` if the archive directory can't be created
+    /// * `Err(LoggerError::InvalidFilter)` if the regex doesn't compile
+    /// * `Err(LoggerError::DirectoryRead)` if the log directory can't be read
+    pub fn log_clean_to(&self, archive_dir: impl AsRef<Path>, filter: Option<&str>) -> Result<CleanReport, LoggerError> {
+        if self.inner.disabled {
+            return Ok(CleanReport::default());
+        }
+        let mut settings = self.inner.clean_settings(filter)?;
+        fs::create_dir_all(archive_dir.as_ref()).map_err(LoggerError::DirectoryCreate)?;
+        settings.archive = Some(archive_dir.as_ref().to_path_buf());
+        self.inner.clean(&settings)
+    }
+
+    /// A regex matching the names `file_name_format` produces, e.g. "Log%d%m%y.log" gives `^Log\d{2}\d{2}\d{2}\.log$`.
+    /// The formats of routed levels are added as alternatives, so their files match too.
+    /// This is the filter `log_clean` uses when given None, if `derive_clean_filter` is set on the builder
+    /// # Example
+    /// ```rust
+    /// use d_logger::Logger;
+    /// use regex::Regex;
+    /// 
+    /// # let path = std::env::temp_dir().join("d_logger_file_name_pattern");
+    /// let logger = Logger::builder().path(&path).file_name_format("Log%d%m%y.log").build().unwrap();
+    /// assert_eq!(logger.file_name_pattern(), r"^Log\d{2}\d{2}\d{2}\.log$");
+    /// 
+    /// let logger = Logger::builder().path(&path).file_name_format("[app] %Y.%j_%H.log").build().unwrap();
+    /// let pattern = Regex::new(&logger.file_name_pattern()).unwrap();
+    /// assert!(pattern.is_match("[app] 2024.366_23.log"));
+    /// assert!(!pattern.is_match("[app] 2024.36_23.log"));
+    /// assert!(!pattern.is_match("xapp] 2024.366_23.log"));
+    /// assert!(!pattern.is_match("[app] 2024x366_23.log"));
+    /// 
+    /// let logger = Logger::builder().path(&path).file_name_format("log-%Y-%m-%d.txt").build().unwrap();
+    /// let pattern = Regex::new(&logger.file_name_pattern()).unwrap();
+    /// assert!(pattern.is_match(&chrono::Local::now().format("log-%Y-%m-%d.txt").to_string()));
+    /// assert!(!pattern.is_match("log-2024-1-01.txt"));
+    /// assert!(!pattern.is_match("log-2024-01-01.txt.bak"));
+    /// ```
+    pub fn file_name_pattern(&self) -> String {
+        self.inner.file_name_pattern()
+    }
+
+    /// Check a `log_clean` filter up front, without cleaning anything.
+    /// An empty pattern is fine and matches every file, the same as passing None
+    /// # Arguments
+    /// * `filter` - The regex to check
+    /// # Example
+    /// ```rust
+    /// use d_logger::{Logger, LoggerError};
+    /// 
+    /// assert!(Logger::validate_filter(r"^log_\d{8}\.log$").is_ok());
+    /// assert!(Logger::validate_filter("").is_ok());
+    /// assert!(Logger::validate_filter(r"log_[0-9").is_err());
+    /// assert!(Logger::validate_filter(r"log_(\d+").is_err());
+    /// 
+    /// // log_clean gives the same error back rather than panicking
+    /// # let path = std::env::temp_dir().join("d_logger_validate_filter");
+    /// # let logger = Logger::builder().path(&path).days_keep(Some(7)).build().unwrap();
+    /// assert!(matches!(logger.log_clean(Some(r"log_[0-9")), Err(LoggerError::InvalidFilter(_))));
+    /// assert!(logger.log_clean(Some("")).is_ok());
+    /// ```
+    /// # Returns
+    /// * `Ok(())` if the filter compiles
+    /// * `Err(regex::Error)` saying what is wrong with it
+    pub fn validate_filter(filter: &str) -> Result<(), regex::Error> {
+        match clean::compile_filter(Some(filter)) {
+            Err(LoggerError::InvalidFilter(e)) => Err(e),
+            _ => Ok(()),
+        }
+    }
+
+    /// Work out what `log_clean` would delete without deleting anything.
+    /// It goes through exactly the same checks as the real clean, so the two always agree
+    /// # Arguments
+    /// * `filter` - Optional regex to filter by name, None to match any file older than date
+    /// # Example
+    /// ```rust
+    /// use d_logger::{test_support::ManualClock, Logger};
+    /// use chrono::TimeZone;
+    /// use std::time::{Duration, SystemTime};
+    /// 
+    /// let path = std::env::temp_dir().join("d_logger_log_clean_dry_run");
+    /// # let _ = std::fs::remove_dir_all(&path);
+    /// let start = chrono::Local.with_ymd_and_hms(2024, 5, 1, 12, 0, 0).unwrap();
+    /// let logger = Logger::builder().path(&path).days_keep(Some(7)).max_files(3).clock(ManualClock::new(start)).build().unwrap();
+    /// for i in 1..=6 {
+    ///     let name = path.join(format!("log_2024010{i}.log"));
+    ///     std::fs::write(&name, "old").unwrap();
+    ///     std::fs::File::options().append(true).open(&name).unwrap().set_modified(SystemTime::from(start) - Duration::from_secs(86400 * i)).unwrap();
+    /// }
+    /// 
+    /// let mut would_delete = logger.log_clean_dry_run(None).unwrap();
+    /// would_delete.sort();
+    /// assert_eq!(std::fs::read_dir(&path).unwrap().count(), 7);
+    /// 
+    /// let mut deleted: Vec<_> = logger.log_clean(None).unwrap().deleted.iter().map(|name| path.join(name)).collect();
+    /// deleted.sort();
+    /// assert_eq!(would_delete, deleted);
+    /// assert_eq!(deleted.len(), 4);
+    /// ```
+    /// # Returns
+    /// * `Ok(paths)` the files a real clean would delete right now
+    /// * `Err(LoggerError::InvalidFilter)` if the regex doesn't compile
+    /// * `Err(LoggerError::DirectoryRead)` if the log directory can't be read
+    pub fn log_clean_dry_run(&self, filter: Option<&str>) -> Result<Vec<PathBuf>, LoggerError> {
+        if self.inner.disabled {
+            return Ok(Vec::new());
+        }
+        let mut settings = self.inner.clean_settings(filter)?;
+        settings.dry_run = true;
+        let report = clean::clean_directory(&self.inner.path, &settings)?;
+        Ok(report.deleted_paths)
+    }
+
+}
+
+impl LoggerInner {
+    // Check each line as write_keyed would, collecting the formatted entries for the current file and appending them
+    // together when the batch ends or the file changes
+    fn write_batch<I: IntoIterator<Item = impl AsRef<str>>>(&self, lines: I, shared_time: bool) -> Result<usize, LoggerError> {
+        if self.disabled {
+            return Ok(lines.into_iter().count());
+        }
+        let start = self.now();
+        let publish = self.publishes();
+        let mut pending: Option<Batch> = None;
+        let mut written = 0;
+        for line in lines {
+            let line = line.as_ref();
+            let now = if shared_time { start } else { self.now() };
+            let (notes, write) = self.check_entry(line, None, line, &[]);
+            if notes.is_empty() && !write {
+                continue;
+            }
+            let log_file_name = self.file_for(&now)?;
+            if let Some(batch) = pending.take_if(|batch| batch.file != log_file_name) {
+                written += self.append_batch(batch)?;
+            }
+            let batch = pending.get_or_insert_with(|| Batch::new(log_file_name, publish));
+            for note in &notes {
+                batch.push(&now, note, self.format_entry(&now, None, note, &[])?);
+            }
+            if write {
+                batch.push_line(&now, line, self.format_entry(&now, None, line, &[])?);
+            }
+        }
+        if let Some(batch) = pending {
+            written += self.append_batch(batch)?;
+        }
+        Ok(written)
+    }
+
+    // Append a batch to the main file it's for, publishing its entries once they're written
+    fn append_batch(&self, batch: Batch) -> Result<usize, LoggerError> {
+        self.append(None, batch.file.clone(), batch.entries.as_bytes(), batch.count)?;
+        self.publish_batch(&batch);
+        Ok(batch.lines)
+    }
+
+    // Format and write a single entry, with the level token if there is one and any key value pairs after the message
+    pub(crate) fn write_entry(&self, level: Option<Level>, line: &str, kvs: &[(&str, &str)]) -> Result<(), LoggerError> {
+        self.write_keyed(line, level, line, kvs)
+    }
+
+    // Write an entry unless the rate limit has used up the key's allowance, summarising what it dropped before
+    fn write_keyed(&self, key: &str, level: Option<Level>, line: &str, kvs: &[(&str, &str)]) -> Result<(), LoggerError> {
+        self.write_keyed_at(&self.now(), key, level, line, kvs)
+    }
+
+    // The same for an entry timestamped earlier, such as one that waited in an AsyncLogger queue
+    pub(crate) fn write_keyed_at(&self, time: &DateTime<FixedOffset>, key: &str, level: Option<Level>, line: &str, kvs: &[(&str, &str)]) -> Result<(), LoggerError> {
+        if self.disabled {
+            return Ok(());
+        }
+        let (notes, write) = self.check_entry(key, level, line, kvs);
+        for note in &notes {
+            self.write_unlimited_at(time, None, note, &[])?;
+        }
+        if !write {
+            return Ok(());
+        }
+        self.write_unlimited_at(time, level, line, kvs)
+    }
+
+    // Run an entry past the low space guard, the duplicate check and the rate limit for its key, in that order, before
+    // it's written. Gives the lines to write in front of it, such as a repeat count or a suppression summary, and whether to write it
+    fn check_entry(&self, key: &str, level: Option<Level>, line: &str, kvs: &[(&str, &str)]) -> (Vec<String>, bool) {
+        let mut notes = Vec::new();
+        if let Some(guard) = &self.low_space {
+            notes.extend(self.space_change(guard));
+            if guard.drops(level) {
+                self.stats.entries_dropped.fetch_add(1, Ordering::Relaxed);
+                return (notes, false);
+            }
+        }
+        if let Some(dedup) = &self.dedup {
+            let (repeats, write) = dedup.check(level, line, kvs);
+            notes.extend(repeats.map(dedup::summary));
+            if !write {
+                return (notes, false);
+            }
+        }
+        if let Some(limiter) = &self.rate_limit {
+            let Some(summaries) = limiter.check(key) else {
+                self.stats.entries_dropped.fetch_add(1, Ordering::Relaxed);
+                return (notes, false);
+            };
+            notes.extend(summaries.into_iter().map(|(key, suppressed)| rate_limit::summary(&key, suppressed)));
+        }
+        (notes, true)
+    }
+
+    // Look at the free space when it's due, writing a line when the logger goes into or comes out of low space
+    fn check_space(&self, guard: &SpaceGuard) {
+        if let Some(line) = self.space_change(guard) {
+            let _ = self.write_unlimited(None, &line, &[]);
+        }
+    }
+
+    // Look at the free space when it's due, giving the line to write if the logger went into or came out of low space
+    fn space_change(&self, guard: &SpaceGuard) -> Option<String> {
+        let (checked, change) = guard.poll(&self.path);
+        let line = match change {
+            Some(Change::Entered(free)) => {
+                let action = match guard.policy {
+                    LowSpacePolicy::Degrade => "only Warn and Error will be written",
+                    LowSpacePolicy::Clean => "cleaning old files",
+                    LowSpacePolicy::Stop => "nothing more will be written",
+                };
+                Some(format!("Warning = Log low space, {free} bytes free is below {}, {action}", guard.min))
+            }
+            Some(Change::Left(free)) => Some(format!("Warning = Log low space, {free} bytes free again, writing normally")),
+            None => None,
+        };
+        if checked && guard.is_low() && guard.policy == LowSpacePolicy::Clean
+            && let Ok(settings) = self.clean_settings(None) {
+            let _ = self.clean(&settings);
+        }
+        line
+    }
+
+    // Write an entry stamped with another time, in its own file if the logger follows the timestamp and the file
+    // won't be cleaned away at once. That file is written without the cached handle, which stays on the current file
+    fn write_stamped(&self, level: Option<Level>, ts: &DateTime<Local>, line: &str) -> Result<(), LoggerError> {
+        if self.disabled {
+            return Ok(());
+        }
+        if let Some(guard) = &self.low_space {
+            self.check_space(guard);
+            if guard.drops(level) {
+                self.stats.entries_dropped.fetch_add(1, Ordering::Relaxed);
+                return Ok(());
+            }
+        }
+        // Stamped entries aren't collapsed, but a count that has waited out the timeout is written ahead of one
+        if let Some(repeats) = self.dedup.as_ref().and_then(Dedup::due) {
+            let _ = self.write_unlimited(None, &dedup::summary(repeats), &[]);
+        }
+        let stamp = self.time_zone.convert(ts);
+        let now = self.now();
+        let file_time = if self.file_follows_timestamp && self.file_kept(&stamp, &now) { stamp } else { now };
+        let (targets, log_entry) = self.prepare_entry_at(&stamp, &file_time, level, line, &[]).inspect_err(|e| self.record_error(e.duplicate()))?;
+        let mut result = Ok(());
+        for (route, log_file_name) in targets {
+            let current = match route {
+                Some(i) => self.routes[i].name.get(&self.path, &now, &self.routes[i].file_name_format),
+                None => self.file_for(&now),
+            };
+            let appended = match current {
+                Ok(current) if current != log_file_name => self.append_detached(log_file_name, log_entry.as_bytes(), 1),
+                _ => self.append(route, log_file_name, log_entry.as_bytes(), 1),
+            };
+            result = result.and(appended);
+        }
+        if result.is_ok() {
+            self.publish(&stamp, level, line, &[], &log_entry);
+        }
+        format::recycle(log_entry);
+        result
+    }
+
+    // Open a log file for appending, syncing the directory as well if that made the file
+    pub(crate) fn open_file(&self, log_file_name: PathBuf, buffer_capacity: usize) -> Result<OpenFile, LoggerError> {
+        let file = OpenFile::open(log_file_name, buffer_capacity, self.file_lock, self.file_mode)?;
+        if self.sync_directory && file.created {
+            writer::sync_dir(&file.path).map_err(LoggerError::Flush)?;
+        }
+        Ok(file)
+    }
+
+    // Append entries to a file other than the one its cached handle is for, opening it just for this write
+    fn append_detached(&self, log_file_name: PathBuf, log_entry: &[u8], count: u32) -> Result<(), LoggerError> {
+        let result = (|| {
+            let _held = self.stamped.lock().unwrap_or_else(|e| e.into_inner());
+            let mut file = self.open_file(log_file_name, 0)?;
+            let mut with_header = Vec::new();
+            let log_entry = self.with_file_header(&file, log_entry, &mut with_header);
+            file.write_entries(log_entry, count, self.sync_policy)
+        })();
+        self.finish_append(result, count, log_entry.len())
+    }
+
+    // Whether an entry stamped with this time can go in its own file, rather than one log clean would delete at once
+    fn file_kept(&self, stamp: &DateTime<FixedOffset>, now: &DateTime<FixedOffset>) -> bool {
+        let days_keep = *self.days_keep.read().unwrap_or_else(|e| e.into_inner());
+        let start = read::start_of_day(&self.time_zone, stamp.date_naive()).unwrap_or(*stamp);
+        days_keep
+            .and_then(|days| i64::try_from(days).ok().and_then(chrono::TimeDelta::try_days))
+            .is_none_or(|keep| now.signed_duration_since(start) < keep)
+    }
+
+    // Sort stamped entries into their dated files and write each file's entries together
+    fn import(&self, entries: impl IntoIterator<Item = (DateTime<Local>, String)>) -> Result<ImportSummary, LoggerError> {
+        if self.disabled {
+            return Ok(ImportSummary::default());
+        }
+        let mut entries: Vec<(DateTime<Local>, String)> = entries.into_iter().collect();
+        if let Some(guard) = &self.low_space {
+            self.check_space(guard);
+            if guard.drops(None) {
+                self.stats.entries_dropped.fetch_add(entries.len() as u64, Ordering::Relaxed);
+                return Ok(ImportSummary::default());
+            }
+        }
+        entries.sort_by_key(|(ts, _)| *ts);
+        let now = self.now();
+        let current = self.file_for(&now)?;
+        let publish = self.publishes();
+        let mut files: Vec<Batch> = Vec::new();
+        for (ts, line) in &entries {
+            let stamp = self.time_zone.convert(ts);
+            let file_time = if self.file_kept(&stamp, &now) { stamp } else { now };
+            let log_file_name = self.file_for(&file_time)?;
+            let log_entry = self.format_entry(&stamp, None, line, &[]).inspect_err(|e| self.record_error(e.duplicate()))?;
+            let index = match files.iter().position(|batch| batch.file == log_file_name) {
+                Some(index) => index,
+                None => {
+                    files.push(Batch::new(log_file_name, publish));
+                    files.len() - 1
+                }
+            };
+            files[index].push_line(&stamp, line, log_entry);
+        }
+        let mut result = Ok(());
+        let mut summary = ImportSummary { entries: entries.len(), files: Vec::new() };
+        for batch in files {
+            summary.files.push((batch.file.clone(), batch.count as usize));
+            let appended = if batch.file == current {
+                self.append(None, batch.file.clone(), batch.entries.as_bytes(), batch.count)
+            } else {
+                self.append_detached(batch.file.clone(), batch.entries.as_bytes(), batch.count)
+            };
+            if appended.is_ok() {
+                self.publish_batch(&batch);
+            }
+            result = result.and(appended);
+        }
+        result.map(|_| summary)
+    }
+
+    fn write_unlimited(&self, level: Option<Level>, line: &str, kvs: &[(&str, &str)]) -> Result<(), LoggerError> {
+        self.write_unlimited_at(&self.now(), level, line, kvs)
+    }
+
+    fn write_unlimited_at(&self, time: &DateTime<FixedOffset>, level: Option<Level>, line: &str, kvs: &[(&str, &str)]) -> Result<(), LoggerError> {
+        let (targets, log_entry) = self.prepare_entry_at(time, time, level, line, kvs).inspect_err(|e| self.record_error(e.duplicate()))?;
+        let result = self.append_all(targets, log_entry.as_bytes());
+        if result.is_ok() {
+            self.publish(time, level, line, kvs, &log_entry);
+        }
+        format::recycle(log_entry);
+        result
+    }
+
+    // Whether written entries go anywhere besides the files, the console or a sink
+    fn publishes(&self) -> bool {
+        ConsoleMode::from(self.console.load(Ordering::Relaxed)) != ConsoleMode::Off
+            || !self.sinks.read().unwrap_or_else(|e| e.into_inner()).is_empty()
+    }
+
+    // Echo an entry that is now in the log to the console and copy it to the sinks, once however many files it went in.
+    // Lines the logger writes about its own files, such as the fallback and recreated directory warnings, don't come here
+    fn publish(&self, time: &DateTime<FixedOffset>, level: Option<Level>, line: &str, kvs: &[(&str, &str)], formatted: &str) {
+        ConsoleMode::from(self.console.load(Ordering::Relaxed)).echo(formatted, self.console_capture.as_ref());
+        let sinks = self.sinks.read().unwrap_or_else(|e| e.into_inner());
+        if sinks.is_empty() {
+            return;
+        }
+        // Sinks get the message and values redacted just as the file did
+        let redactions = self.redactions.read().unwrap_or_else(|e| e.into_inner());
+        let line = redact::apply(&redactions, line);
+        let values: Vec<Cow<'_, str>> = kvs.iter().map(|(_, value)| redact::apply(&redactions, value)).collect();
+        drop(redactions);
+        let kvs: Vec<(&str, &str)> = kvs.iter().zip(&values).map(|((key, _), value)| (*key, value.as_ref())).collect();
+        let record = Record { formatted, time, level, message: &line, kvs: &kvs };
+        for sink in sinks.iter() {
+            let written = match &self.retry {
+                Some(retry) => retry.run(retry::is_transient, || sink.write_record(&record)),
+                None => sink.write_record(&record),
+            };
+            if written.is_err() {
+                self.sink_errors.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+    }
+
+    // Publish each entry of a batch that has been written
+    fn publish_batch(&self, batch: &Batch) {
+        let mut start = 0;
+        for (time, line, end) in batch.parts.iter().flatten() {
+            self.publish(time, None, line, &[], &batch.entries[start..*end]);
+            start = *end;
+        }
+    }
+
+    // The time right now in the logger's time zone, from its clock
+    pub(crate) fn now(&self) -> DateTime<FixedOffset> {
+        self.time_zone.convert(&self.clock.now())
+    }
+
+    // The main dated file for a moment
+    pub(crate) fn file_for(&self, now: &DateTime<FixedOffset>) -> Result<PathBuf, LoggerError> {
+        let format = self.file_name_format.read().unwrap_or_else(|e| e.into_inner());
+        self.file_name_cache.get(&self.path, now, &format)
+    }
+
+    // The main file for a day, going by the name at the start of it
+    pub(crate) fn file_for_date(&self, date: NaiveDate) -> Result<PathBuf, LoggerError> {
+        let start = read::start_of_day(&self.time_zone, date).ok_or_else(|| LoggerError::InvalidFormat(format!("{date} has no start of day")))?;
+        let format = self.file_name_format.read().unwrap_or_else(|e| e.into_inner());
+        Ok(self.path.join(render_date(&start, &format, "file_name_format")?))
+    }
+
+    // The main file for each day from `from` to `to`, with the first day it's named for.
+    // A format without the day in it names the same file for several days in a row, that file comes up once
+    pub(crate) fn files_for_days(&self, from: NaiveDate, to: NaiveDate) -> Result<Vec<(NaiveDate, PathBuf)>, LoggerError> {
+        let mut files: Vec<(NaiveDate, PathBuf)> = Vec::new();
+        for date in from.iter_days().take_while(|date| *date <= to) {
+            let path = self.file_for_date(date)?;
+            if files.last().is_none_or(|(_, last)| *last != path) {
+                files.push((date, path));
+            }
+        }
+        Ok(files)
+    }
+
+    // Something to take apart lines written with the current settings
+    pub(crate) fn line_parser(&self) -> LineParser {
+        let line_date_format = self.line_date_format.read().unwrap_or_else(|e| e.into_inner()).clone();
+        LineParser {
+            subsec: self.subsec_precision.filter(|_| !time::has_subsec(&line_date_format)).map(|precision| precision.specifier()),
+            line_date_format,
+            sequence: self.sequence.is_some(),
+            separator: self.separator.read().unwrap_or_else(|e| e.into_inner()).clone(),
+            time_zone: self.time_zone,
+        }
+    }
+
+    // Timestamp and format an entry with the time written in it and the time its files are named for, giving the files it belongs in and the text to write
+    fn prepare_entry_at(&self, time: &DateTime<FixedOffset>, file_time: &DateTime<FixedOffset>, level: Option<Level>, line: &str, kvs: &[(&str, &str)]) -> Result<(Vec<Target>, String), LoggerError> {
+        let mut targets = Vec::new();
+        let mut exclusive = false;
+        if let Some(level) = level {
+            for (i, route) in self.routes.iter().enumerate().filter(|(_, route)| route.level == level) {
+                targets.push((Some(i), route.name.get(&self.path, file_time, &route.file_name_format)?));
+                exclusive |= route.exclusive;
+            }
+        }
+        if !exclusive {
+            targets.insert(0, (None, self.file_for(file_time)?));
+        }
+        let log_entry = self.format_entry(time, level, line, kvs)?;
+        Ok((targets, log_entry))
+    }
+
+    // Append an entry to each of its files, trying them all even if one fails and giving back the first error
+    pub(crate) fn append_all(&self, targets: Vec<Target>, log_entry: &[u8]) -> Result<(), LoggerError> {
+        let mut result = Ok(());
+        for (route, log_file_name) in targets {
+            let appended = self.append(route, log_file_name, log_entry, 1);
+            result = result.and(appended);
+        }
+        result
+    }
+
+    // The cached handle for the main file, or for one of the routed files
+    fn slot(&self, route: Option<usize>) -> &Mutex<Option<OpenFile>> {
+        match route {
+            Some(i) => &self.routes[i].file,
+            None => &self.file,
+        }
+    }
+
+    // Append complete entries to a dated file and count how it went in the stats
+    pub(crate) fn append(&self, route: Option<usize>, log_file_name: PathBuf, log_entry: &[u8], count: u32) -> Result<(), LoggerError> {
+        let result = match &self.fallback {
+            Some(fallback) => self.append_or_fall_back(fallback, route, log_file_name, log_entry, count),
+            None => self.append_file(route, log_file_name, log_entry, count),
+        };
+        self.finish_append(result, count, log_entry.len())
+    }
+
+    // Count how an append went in the stats, reporting a failure, and hand out any events it caused
+    fn finish_append(&self, result: Result<(), LoggerError>, count: u32, bytes: usize) -> Result<(), LoggerError> {
+        self.stats.written(&result, count, bytes);
+        if let Err(e) = &result {
+            self.record_error(e.duplicate());
+            self.events.emit(LogEvent::WriteFailed { error: e.duplicate() });
+        }
+        self.events.dispatch();
+        result
+    }
+
+    // Append to the log directory, or to the fallback while the log directory can't be written
+    fn append_or_fall_back(&self, fallback: &Fallback, route: Option<usize>, log_file_name: PathBuf, log_entry: &[u8], count: u32) -> Result<(), LoggerError> {
+        let spare = fallback.file_for(&log_file_name);
+        if fallback.is_active() {
+            if !fallback.retry_due() {
+                return self.append_file(route, spare, log_entry, count);
+            }
+            // The switch back line doubles as the test of whether the log directory works again
+            let back = self.format_entry(&self.now(), None, &format!("Warning = Log fallback, writing to {} again", self.path.display()), &[])?;
+            if self.append_file(route, log_file_name.clone(), back.as_bytes(), 1).is_err() {
+                return self.append_file(route, spare, log_entry, count);
+            }
+            fallback.set_active(false);
+            return self.append_file(route, log_file_name, log_entry, count);
+        }
+        let Err(e) = self.append_file(route, log_file_name, log_entry, count) else {
+            return Ok(());
+        };
+        let line = format!("Warning = Log fallback, could not write to {} so writing to {} | {e}", self.path.display(), fallback.path.display());
+        self.record_error(e);
+        if fallback.set_active(true) {
+            let _ = create_log_dir(&fallback.path, self.dir_mode);
+            let switched = self.format_entry(&self.now(), None, &line, &[])?;
+            let _ = self.append_file(route, spare.clone(), switched.as_bytes(), 1);
+        }
+        self.append_file(route, spare, log_entry, count)
+    }
+
+    // Keep an error for last_error
+    pub(crate) fn record_error(&self, error: LoggerError) {
+        let now = self.now();
+        *self.last_error.lock().unwrap_or_else(|e| e.into_inner()) = Some((now, error));
+    }
+
+    // Write to the file, trying again if a retry is set. If the directory has been deleted it's made again, at most once
+    // per cooldown, and a warning line goes in front of the entries
+    fn append_file(&self, route: Option<usize>, log_file_name: PathBuf, log_entry: &[u8], count: u32) -> Result<(), LoggerError> {
+        let result = self.append_retried(route, log_file_name.clone(), log_entry, count);
+        let (Err(LoggerError::FileOpen { source, .. }), Some(dir)) = (&result, log_file_name.parent()) else {
+            return result;
+        };
+        if source.kind() != io::ErrorKind::NotFound || dir.exists() || !self.dir_recreate_due() || create_log_dir(dir, self.dir_mode).is_err() {
+            return result;
+        }
+        let marker = self.format_entry(&self.now(), None, &format!("Warning = Log directory, log directory was recreated at {}", dir.display()), &[])?;
+        let mut entries = marker.into_bytes();
+        entries.extend_from_slice(log_entry);
+        self.append_retried(route, log_file_name, &entries, count + 1)
+    }
+
+    // Claim a try at making the log directory again if the cooldown since the last one has passed
+    fn dir_recreate_due(&self) -> bool {
+        let mut last = self.dir_recreated.lock().unwrap_or_else(|e| e.into_inner());
+        if last.is_some_and(|last| last.elapsed() < DIR_RECREATE_COOLDOWN) {
+            return false;
+        }
+        *last = Some(Instant::now());
+        true
+    }
+
+    fn append_retried(&self, route: Option<usize>, log_file_name: PathBuf, log_entry: &[u8], count: u32) -> Result<(), LoggerError> {
+        match &self.retry {
+            // A failed write drops the cached handle, so each try opens the file afresh
+            Some(retry) => retry.run(retry::is_transient_error, || self.append_once(route, log_file_name.clone(), log_entry, count)),
+            None => self.append_once(route, log_file_name, log_entry, count),
+        }
+    }
+
+    // Reuses the open handle unless the file name has changed.
+    // If the write fails the handle is dropped so the next write reopens the file
+    fn append_once(&self, route: Option<usize>, log_file_name: PathBuf, log_entry: &[u8], count: u32) -> Result<(), LoggerError> {
+        let mut cached = self.slot(route).lock().unwrap_or_else(|e| e.into_inner());
+        // A file finished with by the date changing or by rotation, to compress once the lock is released
+        let mut retired = None;
+        let mut opened = true;
+        let file = match cached.take() {
+            Some(mut file) if file.is_for(&log_file_name) => {
+                if file.moved() {
+                    // Renamed or deleted underneath us, finish with it and start the file again
+                    let _ = file.sync();
+                    self.open_file(log_file_name, self.buffer_capacity)?
+                } else {
+                    opened = false;
+                    file
+                }
+            }
+            Some(mut old) => {
+                // Get anything still pending into the old file before moving on
+                old.sync()?;
+                retired = Some(old.path.clone());
+                let file = self.open_file(log_file_name, self.buffer_capacity)?;
+                if route.is_none() {
+                    self.events.emit(LogEvent::FileChanged { from: old.path.clone(), to: file.path.clone() });
+                }
+                file
+            }
+            None => self.open_file(log_file_name, self.buffer_capacity)?,
+        };
+        // Rotate first if these entries would take the file over the size limit, they then start the fresh file
+        let file = match self.max_file_size {
+            Some(max) if file.size > 0 && file.size + log_entry.len() as u64 > max => {
+                let mut full = file;
+                full.sync()?;
+                let log_file_name = full.path.clone();
+                drop(full);
+                // max_files counts the fresh file as well as the numbered copies, though one copy is always kept
+                let keep = match self.max_files {
+                    Some(max_files) => self.rotation_count.min(max_files.saturating_sub(1)),
+                    None => self.rotation_count,
+                };
+                let removed = rotation::rotate(&log_file_name, keep)?;
+                for (path, age) in removed {
+                    self.events.emit(LogEvent::FileDeleted { path, age });
+                }
+                let rotated = rotation::numbered(&log_file_name, 1);
+                self.events.emit(LogEvent::FileRotated { from: log_file_name.clone(), to: rotated.clone() });
+                retired = Some(rotated);
+                self.open_file(log_file_name, self.buffer_capacity)?
+            }
+            _ => file,
+        };
+        let file = cached.insert(file);
+        // Deciding under the lock on the handle means only one thread writes the header
+        let mut with_header = Vec::new();
+        let log_entry = self.with_file_header(file, log_entry, &mut with_header);
+
+        // Updated while the lock is held, so the link can't go back to an older file
+        let link_result = match &self.latest_link {
+            Some(link) if opened && route.is_none() => latest::update(&self.path, link, &file.path),
+            _ => Ok(()),
+        };
+        let result = file.write_entries(log_entry, count, self.sync_policy);
+        if result.is_err() {
+            *cached = None;
+        }
+        drop(cached);
+
+        if result.is_ok() && let Err(e) = link_result {
+            // Reported rather than written, the entries made it into the file and the old link still works
+            let error = LoggerError::LatestLink(e);
+            self.record_error(error.duplicate());
+            self.events.emit(LogEvent::LinkFailed { error });
+        }
+
+        #[cfg(feature = "compress")]
+        if self.compress
+            && let Some(retired) = retired {
+            match compress::compress(&retired) {
+                Ok(path) => self.events.emit(LogEvent::FileCompressed { path }),
+                Err(e) => {
+                    // Reported rather than written, the file is left as it was
+                    self.record_error(e.duplicate());
+                    self.events.emit(LogEvent::CompressFailed { path: retired, error: e });
+                }
+            }
+        }
+        #[cfg(not(feature = "compress"))]
+        let _ = retired;
+        self.auto_clean();
+        result
+    }
+
+    // Run log_clean from the write path once the auto clean interval has passed.
+    // The first writer to claim the slot does the cleaning, the rest carry on without waiting
+    fn auto_clean(&self) {
+        let Some(interval) = self.auto_clean else {
+            return;
+        };
+        let now = self.clock.system_now().duration_since(std::time::UNIX_EPOCH).map_or(0, |d| d.as_millis() as u64);
+        let last = self.last_clean.load(Ordering::Relaxed);
+        if now.saturating_sub(last) < interval.as_millis() as u64 {
+            return;
+        }
+        if self.cleaning.compare_exchange(false, true, Ordering::Acquire, Ordering::Relaxed).is_err() {
+            return;
+        }
+        // Check again now the slot is held, another writer may have just finished a clean
+        if self.last_clean.load(Ordering::Relaxed) == last {
+            self.last_clean.store(now, Ordering::Relaxed);
+            if let Ok(settings) = self.clean_settings(None) {
+                let _ = self.clean(&settings);
+            }
+        }
+        self.cleaning.store(false, Ordering::Release);
+    }
+
+    // Turn an entry into the text written to the file, including the newline
+    fn format_entry(&self, now: &DateTime<FixedOffset>, level: Option<Level>, line: &str, kvs: &[(&str, &str)]) -> Result<String, LoggerError> {
+        // Redact before anything else sees the message
+        let redactions = self.redactions.read().unwrap_or_else(|e| e.into_inner());
+        let line = redact::apply(&redactions, line);
+        let values: Vec<Cow<'_, str>> = kvs.iter().map(|(_, value)| redact::apply(&redactions, value)).collect();
+        drop(redactions);
+        let kvs: Vec<(&str, &str)> = kvs.iter().zip(&values).map(|((key, _), value)| (*key, value.as_ref())).collect();
+        let (line, kvs) = (line.as_ref(), kvs.as_slice());
+
+        // Get time and format it, straight into the buffer the entry is built in
+        let mut log_entry = format::entry_buffer();
+        {
+            let line_date_format = self.line_date_format.read().unwrap_or_else(|e| e.into_inner());
+            push_date(&mut log_entry, now, &line_date_format, "line_date_format")?;
+            if let Some(precision) = self.subsec_precision
+                && !time::has_subsec(&line_date_format) {
+                // Add the fraction straight after the seconds, keeping any trailing spacing from the format
+                let trimmed = log_entry.trim_end().len();
+                log_entry.insert_str(trimmed, &render_date(now, precision.specifier(), "subsec_precision")?);
+            }
+        }
+        let seq = self.sequence.as_ref().map(|s| s.fetch_add(1, Ordering::Relaxed));
+
+        // Host, process and thread details, written as "[host web-1] [pid 4242] [tid 5] [worker-3] " before the message
+        let mut context: Vec<(&str, String)> = Vec::new();
+        if let Some(hostname) = &self.hostname {
+            context.push(("host", hostname.clone()));
+        }
+        if self.include_pid {
+            context.push(("pid", std::process::id().to_string()));
+        }
+        if self.include_thread_id || self.include_thread_name {
+            let thread = std::thread::current();
+            let id = format::thread_id_number(thread.id());
+            if self.include_thread_id {
+                context.push(("tid", id.clone()));
+            }
+            if self.include_thread_name {
+                context.push(("thread", thread.name().map(str::to_string).unwrap_or(id)));
+            }
+        }
+        let text = if self.sanitize { format::sanitize(line) } else { Cow::Borrowed(line) };
+        let text = format::truncate(text, self.max_line_len);
+
+        // Write everything to file
+        match (&self.formatter, self.output_format) {
+            (Some(formatter), _) => {
+                let mut msg = String::new();
+                if let Some(seq) = seq {
+                    msg.push_str(&format!("#{seq:06} "));
+                }
+                format::push_context(&mut msg, &context);
+                msg.push_str(&text);
+                format::push_logfmt(&mut msg, kvs);
+                log_entry = formatter.format(now, level, &msg);
+            }
+            (None, OutputFormat::Plain) => {
+                if let Some(seq) = seq {
+                    // The sequence token goes straight after the timestamp
+                    let trimmed = log_entry.trim_end().len();
+                    log_entry.insert_str(trimmed, &format!(" #{seq:06}"));
+                }
+                log_entry.push_str(&self.separator.read().unwrap_or_else(|e| e.into_inner()));
+                if let Some(level) = level {
+                    log_entry.push('[');
+                    log_entry.push_str(level.as_str());
+                    log_entry.push_str("] ");
+                }
+                format::push_context(&mut log_entry, &context);
+                log_entry.push_str(&text);
+                format::push_logfmt(&mut log_entry, kvs);
+            }
+            (None, OutputFormat::Syslog(facility)) => {
+                // Host and process id go in the header, anything else about the thread stays in the message
+                let mut entry = String::new();
+                if let Some(seq) = seq {
+                    entry.push_str(&format!("#{seq:06} "));
+                }
+                let thread: Vec<(&str, String)> = context.into_iter().filter(|(key, _)| *key != "host" && *key != "pid").collect();
+                format::push_context(&mut entry, &thread);
+                entry.push_str(&text);
+                format::push_logfmt(&mut entry, kvs);
+                let seconds = match self.subsec_precision {
+                    None => SecondsFormat::Secs,
+                    Some(SubsecPrecision::Millis) => SecondsFormat::Millis,
+                    Some(SubsecPrecision::Micros | SubsecPrecision::Nanos) => SecondsFormat::Micros,
+                };
+                let mut formatter = SyslogFormatter::new(facility).seconds(seconds);
+                if let Some(hostname) = &self.hostname {
+                    formatter = formatter.hostname(hostname);
+                }
+                log_entry = formatter.format(now, level, &entry);
+            }
+            #[cfg(feature = "json")]
+            (None, OutputFormat::Json) => {
+                // Host, process and thread details become fields in front of the caller's key value pairs
+                let mut fields: Vec<(&str, &str)> = context.iter().map(|(k, v)| (*k, v.as_str())).collect();
+                fields.extend_from_slice(kvs);
+                let mut entry = String::new();
+                // The message as given, JSON escaping already keeps it on one line
+                let line = format::truncate(Cow::Borrowed(line), self.max_line_len);
+                format::push_json(&mut entry, log_entry.trim(), level, seq, &line, &fields);
+                log_entry = entry;
+            }
+            (None, OutputFormat::Csv) => {
+                // The thread has a column of its own, host and process details stay in the message
+                let thread = std::thread::current();
+                let thread_name = thread.name().map_or_else(|| format::thread_id_number(thread.id()), str::to_string);
+                let mut msg = String::new();
+                if let Some(seq) = seq {
+                    msg.push_str(&format!("#{seq:06} "));
+                }
+                let others: Vec<(&str, String)> = context.into_iter().filter(|(key, _)| *key != "tid" && *key != "thread").collect();
+                format::push_context(&mut msg, &others);
+                // Sanitized like plain text, so a line break doesn't turn one row into a quoted field over several lines
+                msg.push_str(&text);
+                format::push_logfmt(&mut msg, kvs);
+                let mut entry = String::new();
+                format::push_csv(&mut entry, &[log_entry.trim(), level.map_or("", |level| level.as_str()), &thread_name, &msg]);
+                log_entry = entry;
+            }
+        }
+        log_entry.push_str(self.line_ending.as_str());
+        Ok(log_entry)
+    }
+
+    // The entries to write to a file, after the header if the file is empty and there is one
+    fn with_file_header<'a>(&self, file: &OpenFile, log_entry: &'a [u8], buf: &'a mut Vec<u8>) -> &'a [u8] {
+        if file.size > 0 {
+            return log_entry;
+        }
+        match self.file_header_text() {
+            Some(header) => {
+                buf.extend_from_slice(header.as_bytes());
+                buf.extend_from_slice(log_entry);
+                buf
+            }
+            None => log_entry,
+        }
+    }
+
+    // The text a new file starts with, each line ended with the logger's line ending. None if there isn't any
+    fn file_header_text(&self) -> Option<String> {
+        if self.file_header.is_none() && !self.csv_header {
+            return None;
+        }
+        let mut text = String::new();
+        if let Some(header) = &self.file_header {
+            for line in header().lines() {
+                text.push_str(line);
+                text.push_str(self.line_ending.as_str());
+            }
+        }
+        if self.csv_header {
+            text.push_str(format::CSV_HEADER);
+            text.push_str(self.line_ending.as_str());
+        }
+        Some(text)
+    }
+
+    // The main file's cached handle followed by each routed file's
+    fn slots(&self) -> impl Iterator<Item = &Mutex<Option<OpenFile>>> {
+        std::iter::once(&self.file).chain(self.routes.iter().map(|route| &route.file))
+    }
+
+    // Shared by close and drop, everything is taken so a second call does nothing
+    fn shutdown(&self) -> Result<(), LoggerError> {
+        let mut marker = Ok(());
+        // Say what was held back since the last summaries, nothing else will now
+        if !self.disabled
+            && let Some(repeats) = self.dedup.as_ref().and_then(Dedup::take) {
+            marker = self.write_unlimited(None, &dedup::summary(repeats), &[]);
+        }
+        if !self.disabled
+            && let Some(limiter) = &self.rate_limit {
+            for (key, suppressed) in limiter.drain() {
+                marker = marker.and(self.write_unlimited(None, &rate_limit::summary(&key, suppressed), &[]));
+            }
+        }
+        if let Some(line) = self.shutdown_marker.lock().unwrap_or_else(|e| e.into_inner()).take() {
+            marker = marker.and(self.write_entry(None, &line, &[]));
+        }
+        if let Some(banner) = self.shutdown_banner.lock().unwrap_or_else(|e| e.into_inner()).take() {
+            let line = format::banner(&banner, &self.now());
+            marker = marker.and(self.write_entry(Some(Level::Info), &line, &[]));
+        }
+        let mut flushed = Ok(());
+        for slot in self.slots() {
+            let cached = slot.lock().unwrap_or_else(|e| e.into_inner()).take();
+            if let Some(mut file) = cached {
+                flushed = flushed.and(match self.sync_policy {
+                    SyncPolicy::Never => file.flush(),
+                    _ => file.sync(),
+                });
+            }
+        }
+        marker.and(flushed)
+    }
+
+    // The regex used to find this logger's files, one alternative per dated file name format
+    fn file_name_pattern(&self) -> String {
+        let mut pattern = clean::format_to_regex(&self.file_name_format.read().unwrap_or_else(|e| e.into_inner()));
+        for route in &self.routes {
+            pattern.push('|');
+            pattern.push_str(&clean::format_to_regex(&route.file_name_format));
+        }
+        pattern
+    }
+
+    // Run the log cleaner, counting what it did
+    fn clean(&self, settings: &CleanSettings) -> Result<CleanReport, LoggerError> {
+        let mut report = clean::clean_directory(&self.path, settings).inspect_err(|e| self.record_error(e.duplicate()))?;
+        self.stats.cleaned(report.deleted.len());
+        for event in report.events.drain(..) {
+            self.events.emit(event);
+        }
+        self.events.dispatch();
+        Ok(report)
+    }
+
+    // Everything the log cleaner needs, owned so it can be moved to another thread
+    fn clean_settings(&self, filter: Option<&str>) -> Result<CleanSettings, LoggerError> {
+        let derived = self.derive_clean_filter.then(|| self.file_name_pattern());
+        let regex = clean::compile_filter(filter.or(derived.as_deref()))?;
+        // The file for right now and the one held open, which can differ just after the date changes.
+        // These are skipped whatever their modified time says, in case the clock has jumped
+        let mut active = Vec::new();
+        let now = self.now();
+        let formats: Vec<String> = std::iter::once(self.file_name_format.read().unwrap_or_else(|e| e.into_inner()).clone())
+            .chain(self.routes.iter().map(|route| route.file_name_format.clone()))
+            .collect();
+        for format in &formats {
+            if let Ok(name) = render_date(&now, format, "file_name_format") {
+                active.push(self.path.join(name));
+            }
+        }
+        for slot in self.slots() {
+            if let Some(file) = slot.lock().unwrap_or_else(|e| e.into_inner()).as_ref() {
+                active.push(file.path.clone());
+            }
+        }
+        if let Some(link) = &self.latest_link {
+            active.extend(latest::paths(&self.path, link));
+        }
+        let day_start = now.with_time(NaiveTime::MIN).single().map_or(0, |start| u64::try_from(start.timestamp()).unwrap_or(0));
+        Ok(CleanSettings {
+            days_keep: *self.days_keep.read().unwrap_or_else(|e| e.into_inner()),
+            max_files: self.max_files,
+            max_total_size: self.max_total_size,
+            compress: self.compress,
+            rotated: self.max_file_size.is_some(),
+            strategy: self.clean_strategy,
+            file_name_formats: formats,
+            offset: *now.offset(),
+            regex,
+            active,
+            archive: None,
+            dry_run: false,
+            depth: self.clean_subdirectories.0,
+            remove_empty_dirs: self.clean_subdirectories.1,
+            now: self.clock.system_now(),
+            day_start,
+        })
+    }
+}
+
+impl Drop for LoggerInner {
+    fn drop(&mut self) {
+        // Errors can't be returned from drop, use close to see them
+        let _ = self.shutdown();
+    }
+}
@@ -0,0 +1,18 @@
+use std::fmt;
+
+/// Where in the source a log entry came from, filled in by the `d_*!` macros
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Location {
+    /// Source file, from `file!()`
+    pub file: &'static str,
+    /// Line number, from `line!()`
+    pub line: u32,
+    /// Module path, from `module_path!()`
+    pub module_path: &'static str,
+}
+
+impl fmt::Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.file, self.line)
+    }
+}
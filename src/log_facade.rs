@@ -0,0 +1,59 @@
+use crate::{Level, Logger};
+
+impl From<log::Level> for Level {
+    fn from(level: log::Level) -> Self {
+        match level {
+            log::Level::Trace => Level::Trace,
+            log::Level::Debug => Level::Debug,
+            log::Level::Info => Level::Info,
+            log::Level::Warn => Level::Warn,
+            log::Level::Error => Level::Error,
+        }
+    }
+}
+
+// Lines from the log facade are written as "<time> [LEVEL] <target>: <message>"
+impl log::Log for Logger {
+    fn enabled(&self, metadata: &log::Metadata) -> bool {
+        Level::from(metadata.level()) >= self.level()
+    }
+
+    fn log(&self, record: &log::Record) {
+        if self.enabled(record.metadata()) {
+            self.write_level(record.level().into(), &format!("{}: {}", record.target(), record.args()));
+        }
+    }
+
+    fn flush(&self) {
+        let _ = Logger::flush(self);
+    }
+}
+
+impl Logger {
+    /// Install this logger as the global logger for the `log` crate, so `log::info!` and friends write to it.
+    /// Only available with the `log` feature.
+    /// # Arguments
+    /// * `max_level` - The most verbose level the `log` macros will pass through
+    /// # Example
+    /// ```rust
+    /// use d_logger::Logger;
+    /// 
+    /// let path = std::env::temp_dir().join("d_logger_init_global");
+    /// Logger::builder().path(&path).file_name_format("global_%Y%m%d.log").build().unwrap()
+    ///     .init_global(log::LevelFilter::Info).unwrap();
+    /// 
+    /// log::info!(target: "app", "hello from the facade");
+    /// log::debug!("filtered out by the max level");
+    /// 
+    /// let contents = std::fs::read_to_string(path.join(chrono::Local::now().format("global_%Y%m%d.log").to_string())).unwrap();
+    /// assert!(contents.contains(" [INFO] app: hello from the facade"));
+    /// assert!(!contents.contains("filtered out by the max level"));
+    /// ```
+    /// # Returns
+    /// * `Err(log::SetLoggerError)` if a global logger has already been installed
+    pub fn init_global(self, max_level: log::LevelFilter) -> Result<(), log::SetLoggerError> {
+        log::set_boxed_logger(Box::new(self))?;
+        log::set_max_level(max_level);
+        Ok(())
+    }
+}
@@ -0,0 +1,37 @@
+use std::{io, path::PathBuf};
+
+// Paths this long or longer need the extended-length prefix with the usual Windows file APIs
+#[cfg(windows)]
+const MAX_PATH: usize = 260;
+
+// Left over for what's added to a file's name later: rotation numbers, ".gz" and archive folders
+#[cfg(windows)]
+const ROOM: usize = 32;
+
+// The log directory in extended-length "\\?\" form when a file in it would otherwise be too long a path, set with
+// LoggerBuilder::long_paths. A UNC share "\\server\share" becomes "\\?\UNC\server\share". The prefix turns off the
+// usual clean up of a path, so it's made absolute first, which also settles any "." and ".."
+#[cfg(windows)]
+pub(crate) fn extend(dir: PathBuf, file_name: &str) -> io::Result<PathBuf> {
+    if dir.as_os_str().is_empty() || dir.join(file_name).as_os_str().len() + ROOM < MAX_PATH {
+        return Ok(dir);
+    }
+    let absolute = std::path::absolute(&dir)?;
+    // A path that isn't valid Unicode can't be written out again, it's left to the platform
+    let Some(text) = absolute.to_str() else {
+        return Ok(absolute);
+    };
+    let extended = match text.strip_prefix(r"\\") {
+        // Already extended, or a device path
+        Some(rest) if rest.starts_with(r"?\") || rest.starts_with(r".\") => return Ok(absolute),
+        Some(share) => format!(r"\\?\UNC\{share}"),
+        None => format!(r"\\?\{text}"),
+    };
+    Ok(PathBuf::from(extended))
+}
+
+// Nothing else has the limit
+#[cfg(not(windows))]
+pub(crate) fn extend(dir: PathBuf, _file_name: &str) -> io::Result<PathBuf> {
+    Ok(dir)
+}
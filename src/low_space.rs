@@ -0,0 +1,116 @@
+use std::{path::Path, sync::{atomic::{AtomicBool, Ordering}, Arc, Mutex}, time::{Duration, Instant}};
+
+use crate::{health, Level};
+
+/// What the logger does while free space on the log volume is below `LoggerBuilder::min_free_space`.
+/// A warning line is written when the logger goes into and comes out of low space
+/// # Example
+/// ```rust
+/// use d_logger::{test_support::ManualClock, Logger, LowSpacePolicy};
+/// use chrono::TimeZone;
+/// use std::time::{Duration, SystemTime};
+/// 
+/// let path = std::env::temp_dir().join("d_logger_low_space_policy");
+/// # let _ = std::fs::remove_dir_all(&path);
+/// let start = chrono::Local.with_ymd_and_hms(2024, 5, 1, 12, 0, 0).unwrap();
+/// let build = |policy| Logger::builder()
+///     .path(&path)
+///     .file_name_format("policy_%Y%m%d.log")
+///     .days_keep(Some(7))
+///     .min_free_space(1_000_000)
+///     .low_space_policy(policy)
+///     .free_space_provider(|| Some(0))
+///     .clock(ManualClock::new(start))
+///     .build()
+///     .unwrap();
+/// let file = path.join(start.format("policy_%Y%m%d.log").to_string());
+/// 
+/// // Stop drops even errors, only the warning about low space gets through
+/// let logger = build(LowSpacePolicy::Stop);
+/// # let _ = std::fs::remove_file(&file);
+/// logger.error("dropped");
+/// assert!(!std::fs::read_to_string(&file).unwrap().contains("dropped"));
+/// 
+/// // Clean keeps writing and clears out old files to make room
+/// std::fs::write(path.join("policy_20240101.log"), "old").unwrap();
+/// let old = SystemTime::from(start) - Duration::from_secs(86400 * 30);
+/// std::fs::File::options().append(true).open(path.join("policy_20240101.log")).unwrap().set_modified(old).unwrap();
+/// let logger = build(LowSpacePolicy::Clean);
+/// logger.info("written");
+/// assert!(std::fs::read_to_string(&file).unwrap().contains("[INFO] written"));
+/// assert!(!path.join("policy_20240101.log").exists());
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LowSpacePolicy {
+    /// Keep writing Warn and Error, drop everything else including plain `write_log` lines
+    #[default]
+    Degrade,
+    /// Keep writing everything and run `log_clean(None)` each time space is checked and found low
+    Clean,
+    /// Drop every entry until there's space again
+    Stop,
+}
+
+pub(crate) type FreeSpaceProvider = Arc<dyn Fn() -> Option<u64> + Send + Sync>;
+
+// Watches free space on the log volume, set with LoggerBuilder::min_free_space
+pub(crate) struct SpaceGuard {
+    pub(crate) min: u64,
+    pub(crate) policy: LowSpacePolicy,
+    interval: Duration,
+    provider: Option<FreeSpaceProvider>,
+    last_check: Mutex<Option<Instant>>,
+    low: AtomicBool,
+}
+
+// A check that found the logger going into or coming out of low space
+pub(crate) enum Change {
+    Entered(u64),
+    Left(u64),
+}
+
+impl SpaceGuard {
+    pub(crate) fn new(min: u64, policy: LowSpacePolicy, interval: Duration, provider: Option<FreeSpaceProvider>) -> SpaceGuard {
+        SpaceGuard { min, policy, interval, provider, last_check: Mutex::new(None), low: AtomicBool::new(false) }
+    }
+
+    // Look at the free space if the interval has passed since the last look.
+    // Gives back whether a check ran and any change it found
+    pub(crate) fn poll(&self, dir: &Path) -> (bool, Option<Change>) {
+        {
+            let mut last_check = self.last_check.lock().unwrap_or_else(|e| e.into_inner());
+            if last_check.is_some_and(|last| last.elapsed() < self.interval) {
+                return (false, None);
+            }
+            *last_check = Some(Instant::now());
+        }
+        let free = match &self.provider {
+            Some(provider) => provider(),
+            None => health::free_space(dir),
+        };
+        // Not knowing counts as having space, so a failed lookup never stops the log
+        let Some(free) = free else {
+            return (true, None);
+        };
+        let low = free < self.min;
+        let change = match (self.low.swap(low, Ordering::Relaxed), low) {
+            (false, true) => Some(Change::Entered(free)),
+            (true, false) => Some(Change::Left(free)),
+            _ => None,
+        };
+        (true, change)
+    }
+
+    pub(crate) fn is_low(&self) -> bool {
+        self.low.load(Ordering::Relaxed)
+    }
+
+    // Whether an entry at this level is dropped while space is low
+    pub(crate) fn drops(&self, level: Option<Level>) -> bool {
+        self.is_low() && match self.policy {
+            LowSpacePolicy::Degrade => level.is_none_or(|level| level < Level::Warn),
+            LowSpacePolicy::Clean => false,
+            LowSpacePolicy::Stop => true,
+        }
+    }
+}
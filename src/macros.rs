@@ -0,0 +1,74 @@
+// Logging macros that capture the source location
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __d_log {
+    (global, $level:expr, $($arg:tt)+) => {
+        match $crate::global() {
+            Some(logger) => $crate::__d_log!(logger, $level, $($arg)+),
+            None => false,
+        }
+    };
+    ($logger:expr, $level:expr, $($arg:tt)+) => {
+        $logger.write_log_at_fmt(
+            $level,
+            &$crate::Location { file: file!(), line: line!(), module_path: module_path!() },
+            format_args!($($arg)+),
+        )
+    };
+}
+
+/// Write a Trace line with its source location, takes format arguments like `println!`.
+/// See `d_info!`
+#[macro_export]
+macro_rules! d_trace {
+    ($fmt:literal $($arg:tt)*) => { $crate::__d_log!(global, $crate::Level::Trace, $fmt $($arg)*) };
+    ($logger:expr, $($arg:tt)+) => { $crate::__d_log!($logger, $crate::Level::Trace, $($arg)+) };
+}
+
+/// Write a Debug line with its source location, takes format arguments like `println!`.
+/// See `d_info!`
+#[macro_export]
+macro_rules! d_debug {
+    ($fmt:literal $($arg:tt)*) => { $crate::__d_log!(global, $crate::Level::Debug, $fmt $($arg)*) };
+    ($logger:expr, $($arg:tt)+) => { $crate::__d_log!($logger, $crate::Level::Debug, $($arg)+) };
+}
+
+/// Write an Info line with its source location, takes format arguments like `println!`.
+/// The location is appended to the message, e.g. "2024-01-01 10:00:00 [INFO] started 3 workers (src/main.rs:42)".
+/// Leave out the logger, as in `d_info!("started {} workers", workers)`, to write to the one installed by `init`.
+/// Until then that does nothing and returns false
+/// # Example
+/// ```rust
+/// use d_logger::{d_info, Logger};
+/// 
+/// let path = std::env::temp_dir().join("d_logger_d_info");
+/// let logger = Logger::builder().path(&path).file_name_format("macros_%Y%m%d.log").build().unwrap();
+/// let workers = 3;
+/// let line = line!() + 1;
+/// d_info!(logger, "started {} workers", workers);
+/// 
+/// let contents = std::fs::read_to_string(path.join(chrono::Local::now().format("macros_%Y%m%d.log").to_string())).unwrap();
+/// assert!(contents.contains(&format!(" [INFO] started 3 workers ({}:{line})", file!())));
+/// ```
+#[macro_export]
+macro_rules! d_info {
+    ($fmt:literal $($arg:tt)*) => { $crate::__d_log!(global, $crate::Level::Info, $fmt $($arg)*) };
+    ($logger:expr, $($arg:tt)+) => { $crate::__d_log!($logger, $crate::Level::Info, $($arg)+) };
+}
+
+/// Write a Warn line with its source location, takes format arguments like `println!`.
+/// See `d_info!`
+#[macro_export]
+macro_rules! d_warn {
+    ($fmt:literal $($arg:tt)*) => { $crate::__d_log!(global, $crate::Level::Warn, $fmt $($arg)*) };
+    ($logger:expr, $($arg:tt)+) => { $crate::__d_log!($logger, $crate::Level::Warn, $($arg)+) };
+}
+
+/// Write an Error line with its source location, takes format arguments like `println!`.
+/// See `d_info!`
+#[macro_export]
+macro_rules! d_error {
+    ($fmt:literal $($arg:tt)*) => { $crate::__d_log!(global, $crate::Level::Error, $fmt $($arg)*) };
+    ($logger:expr, $($arg:tt)+) => { $crate::__d_log!($logger, $crate::Level::Error, $($arg)+) };
+}
@@ -0,0 +1,272 @@
+use std::{
+    collections::VecDeque,
+    io::{self, Write},
+    net::{SocketAddr, TcpStream, ToSocketAddrs, UdpSocket},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Condvar, Mutex, MutexGuard,
+    },
+    thread::JoinHandle,
+    time::{Duration, Instant},
+};
+
+use crate::Sink;
+
+const DEFAULT_BUFFER: usize = 1000;
+const DEFAULT_RETRY_INTERVAL: Duration = Duration::from_secs(1);
+// How long a UDP send may hold up a write, and a TCP connect or send the sink's thread, before giving up
+const IO_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// Ships every entry to a remote collector, added with `Logger::add_sink`. Entries are sent exactly as written to the
+/// file, newline included, over `udp://host:port` (one datagram per entry) or `tcp://host:port`.
+///
+/// Over TCP entries are sent on the sink's own thread, so writing only queues the entry and never waits on the network.
+/// The connection is made on the first entry and remade after a failure, at most once per retry interval.
+/// Entries that haven't been sent wait in a bounded buffer, 1000 by default, and go first once the collector is back.
+/// When the buffer is full the oldest are dropped and the write reports an error, which the logger counts in `sink_errors`.
+/// Dropping the sink sends what's still buffered if the collector can be reached, trying to connect once more if need be.
+/// An entry the OS accepted just before the connection died can be lost, as with any TCP sender
+/// # Example
+/// ```rust
+/// use d_logger::{Logger, NetworkSink};
+/// use std::io::{BufRead, BufReader};
+/// use std::net::TcpListener;
+/// use std::time::Duration;
+/// 
+/// let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+/// let addr = listener.local_addr().unwrap();
+/// let path = std::env::temp_dir().join("d_logger_network_sink");
+/// let logger = Logger::builder().path(&path).build().unwrap();
+/// logger.add_sink(Box::new(NetworkSink::new(&format!("tcp://{addr}")).unwrap().retry_interval(Duration::from_millis(50))));
+/// 
+/// logger.write_log("first");
+/// let (stream, _) = listener.accept().unwrap();
+/// let mut lines = BufReader::new(stream).lines();
+/// assert!(lines.next().unwrap().unwrap().ends_with(" first"));
+/// 
+/// // The collector goes away, writes carry on without failing or waiting
+/// drop(lines);
+/// drop(listener);
+/// for i in 0..5 {
+///     assert!(logger.write_log(&format!("during outage {i}")));
+///     std::thread::sleep(Duration::from_millis(20));
+/// }
+/// 
+/// // Once it's back the buffered entries are sent before the new one
+/// let listener = TcpListener::bind(addr).unwrap();
+/// std::thread::sleep(Duration::from_millis(100));
+/// logger.write_log("after outage");
+/// let (stream, _) = listener.accept().unwrap();
+/// let received: Vec<String> = BufReader::new(stream).lines().map(|l| l.unwrap()).take_while(|l| !l.ends_with("after outage")).collect();
+/// assert!(received.iter().any(|l| l.ends_with("during outage 4")));
+/// assert_eq!(logger.sink_errors(), 0);
+/// ```
+#[derive(Debug)]
+pub struct NetworkSink {
+    transport: Transport,
+    /// Entries dropped because the retry buffer was full
+    dropped: AtomicU64,
+}
+
+#[derive(Debug)]
+enum Transport {
+    Udp(UdpSocket),
+    Tcp { shared: Arc<TcpShared>, sender: Option<JoinHandle<()>> },
+}
+
+// The buffer shared with the thread that sends over TCP, which waits on `ready` for entries
+#[derive(Debug)]
+struct TcpShared {
+    queue: Mutex<TcpQueue>,
+    ready: Condvar,
+}
+
+#[derive(Debug)]
+struct TcpQueue {
+    pending: VecDeque<String>,
+    capacity: usize,
+    retry_interval: Duration,
+    closed: bool,
+}
+
+impl NetworkSink {
+    /// Set up a sink for `udp://host:port` or `tcp://host:port`. The host is resolved straight away,
+    /// a TCP connection isn't made until the first entry
+    /// # Arguments
+    /// * `url` - Where to send entries
+    /// # Example
+    /// ```rust
+    /// use d_logger::{Logger, NetworkSink};
+    /// use std::net::UdpSocket;
+    /// 
+    /// let collector = UdpSocket::bind("127.0.0.1:0").unwrap();
+    /// let path = std::env::temp_dir().join("d_logger_network_sink_udp");
+    /// let logger = Logger::builder().path(&path).file_name_format("udp_%Y%m%d.log").build().unwrap();
+    /// let file = path.join(chrono::Local::now().format("udp_%Y%m%d.log").to_string());
+    /// # let _ = std::fs::remove_file(&file);
+    /// logger.add_sink(Box::new(NetworkSink::new(&format!("udp://{}", collector.local_addr().unwrap())).unwrap()));
+    /// 
+    /// logger.write_log("shipped");
+    /// let mut buf = [0; 1024];
+    /// let len = collector.recv(&mut buf).unwrap();
+    /// assert_eq!(&buf[..len], std::fs::read(&file).unwrap().as_slice());
+    /// 
+    /// assert!(NetworkSink::new("http://localhost:80").is_err());
+    /// assert_eq!(NetworkSink::new("tcp://nowhere.invalid:514").unwrap_err().kind(), std::io::ErrorKind::InvalidInput);
+    /// ```
+    /// # Returns
+    /// * `Err(io::Error)` with `InvalidInput` if the scheme isn't udp or tcp, or the address doesn't resolve.
+    ///   For UDP also if the local socket couldn't be opened, and for TCP if the sending thread couldn't be started
+    pub fn new(url: &str) -> io::Result<NetworkSink> {
+        let invalid = |msg: String| io::Error::new(io::ErrorKind::InvalidInput, msg);
+        let (scheme, host) = url.split_once("://").ok_or_else(|| invalid(format!("\"{url}\" is not udp://host:port or tcp://host:port")))?;
+        let addr = host.to_socket_addrs()
+            .map_err(|e| invalid(format!("\"{host}\" did not resolve to an address | {e}")))?
+            .next()
+            .ok_or_else(|| invalid(format!("\"{host}\" did not resolve to an address")))?;
+        let transport = match scheme {
+            "udp" => {
+                let local: SocketAddr = if addr.is_ipv4() { ([0, 0, 0, 0], 0).into() } else { ([0u16; 8], 0).into() };
+                let socket = UdpSocket::bind(local)?;
+                socket.connect(addr)?;
+                socket.set_write_timeout(Some(IO_TIMEOUT))?;
+                Transport::Udp(socket)
+            }
+            "tcp" => {
+                let shared = Arc::new(TcpShared {
+                    queue: Mutex::new(TcpQueue {
+                        pending: VecDeque::new(),
+                        capacity: DEFAULT_BUFFER,
+                        retry_interval: DEFAULT_RETRY_INTERVAL,
+                        closed: false,
+                    }),
+                    ready: Condvar::new(),
+                });
+                let thread_shared = Arc::clone(&shared);
+                let sender = std::thread::Builder::new()
+                    .name("d_logger-network".to_string())
+                    .spawn(move || thread_shared.run(addr))?;
+                Transport::Tcp { shared, sender: Some(sender) }
+            }
+            _ => return Err(invalid(format!("unsupported scheme \"{scheme}\", use udp or tcp"))),
+        };
+        Ok(NetworkSink { transport, dropped: AtomicU64::new(0) })
+    }
+
+    /// How many unsent entries TCP keeps while the collector is unreachable, 1000 by default. 0 keeps none
+    pub fn buffer_capacity(self, capacity: usize) -> Self {
+        if let Transport::Tcp { shared, .. } = &self.transport {
+            shared.lock().capacity = capacity;
+        }
+        self
+    }
+
+    /// The shortest time between TCP connection attempts, 1 second by default
+    pub fn retry_interval(self, interval: Duration) -> Self {
+        if let Transport::Tcp { shared, .. } = &self.transport {
+            shared.lock().retry_interval = interval;
+        }
+        self
+    }
+
+    /// How many entries have been dropped because the retry buffer was full
+    pub fn dropped(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+}
+
+impl TcpShared {
+    fn lock(&self) -> MutexGuard<'_, TcpQueue> {
+        self.queue.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    // The sending thread: connect when needed and send entries oldest first, until the sink is dropped.
+    // An entry that failed to send is kept and sent again in full once a new connection is made
+    fn run(&self, addr: SocketAddr) {
+        let mut stream: Option<TcpStream> = None;
+        let mut last_attempt: Option<Instant> = None;
+        let mut current: Option<String> = None;
+        loop {
+            let (closed, retry_interval) = {
+                let mut queue = self.lock();
+                while current.is_none() && queue.pending.is_empty() && !queue.closed {
+                    queue = self.ready.wait(queue).unwrap_or_else(|e| e.into_inner());
+                }
+                if current.is_none() {
+                    current = queue.pending.pop_front();
+                }
+                (queue.closed, queue.retry_interval)
+            };
+            let Some(entry) = &current else {
+                // Closed with nothing left to send
+                return;
+            };
+            if stream.is_none() {
+                let wait = last_attempt.map_or(Duration::ZERO, |last| retry_interval.saturating_sub(last.elapsed()));
+                // Once closed there's one last try straight away rather than waiting out the interval
+                if !wait.is_zero() && !closed {
+                    let queue = self.lock();
+                    let _ = self.ready.wait_timeout(queue, wait);
+                    continue;
+                }
+                last_attempt = Some(Instant::now());
+                match TcpStream::connect_timeout(&addr, IO_TIMEOUT) {
+                    Ok(connected) => {
+                        let _ = connected.set_write_timeout(Some(IO_TIMEOUT));
+                        let _ = connected.set_nodelay(true);
+                        stream = Some(connected);
+                    }
+                    Err(_) if closed => return,
+                    Err(_) => continue,
+                }
+            }
+            if let Some(connected) = stream.as_mut()
+                && connected.write_all(entry.as_bytes()).is_err() {
+                stream = None;
+                last_attempt = Some(Instant::now());
+                if closed {
+                    return;
+                }
+                continue;
+            }
+            current = None;
+        }
+    }
+}
+
+impl Sink for NetworkSink {
+    fn write_entry(&self, formatted: &str) -> io::Result<()> {
+        match &self.transport {
+            Transport::Udp(socket) => socket.send(formatted.as_bytes()).map(|_| ()),
+            Transport::Tcp { shared, .. } => {
+                let mut queue = shared.lock();
+                queue.pending.push_back(formatted.to_string());
+                let mut dropped = 0;
+                while queue.pending.len() > queue.capacity {
+                    queue.pending.pop_front();
+                    dropped += 1;
+                }
+                drop(queue);
+                shared.ready.notify_one();
+                if dropped > 0 {
+                    self.dropped.fetch_add(dropped, Ordering::Relaxed);
+                    return Err(io::Error::other(format!("collector unreachable, dropped {dropped} buffered log entries")));
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl Drop for NetworkSink {
+    fn drop(&mut self) {
+        if let Transport::Tcp { shared, sender } = &mut self.transport {
+            // The thread sends what it can of what's left and stops
+            shared.lock().closed = true;
+            shared.ready.notify_all();
+            if let Some(sender) = sender.take() {
+                let _ = sender.join();
+            }
+        }
+    }
+}
@@ -0,0 +1,72 @@
+use std::{cell::Cell, panic::{self, PanicHookInfo}};
+
+use crate::{Level, Logger};
+
+impl Logger {
+    /// Log panics to this logger before the process carries on with them.
+    /// Each panic is written as an Error line saying which thread panicked, where and with what message,
+    /// the file is flushed straight away and then the hook that was installed before runs, by default printing to stderr.
+    /// Hooks chain, so calling this on several loggers has every one of them log the panic, newest first.
+    /// The hook keeps a clone of the logger for the rest of the process
+    /// # Example
+    /// ```rust
+    /// use d_logger::Logger;
+    /// 
+    /// let path = std::env::temp_dir().join("d_logger_panic_hook");
+    /// let logger = Logger::builder().path(&path).file_name_format("panic_%Y%m%d.log").build().unwrap();
+    /// let file = path.join(chrono::Local::now().format("panic_%Y%m%d.log").to_string());
+    /// # let _ = std::fs::remove_file(&file);
+    /// logger.install_panic_hook();
+    /// 
+    /// let result = std::thread::Builder::new().name("worker".to_string()).spawn(|| {
+    ///     std::panic::catch_unwind(|| panic!("ran out of {}", "widgets"))
+    /// }).unwrap().join().unwrap();
+    /// assert!(result.is_err());
+    /// 
+    /// let contents = std::fs::read_to_string(&file).unwrap();
+    /// let line = contents.lines().find(|l| l.contains("[ERROR]")).unwrap();
+    /// assert!(line.contains("[ERROR] thread 'worker' panicked at "));
+    /// assert!(line.ends_with(": ran out of widgets"));
+    /// ```
+    pub fn install_panic_hook(&self) {
+        let logger = self.clone();
+        let previous = panic::take_hook();
+        panic::set_hook(Box::new(move |info| {
+            // A panic while logging the panic, e.g. from a sink, goes straight to the previous hook
+            if !IN_HOOK.replace(true) {
+                let _guard = HookGuard;
+                logger.write_level(Level::Error, &panic_line(info));
+                let _ = logger.flush();
+            }
+            previous(info);
+        }));
+    }
+}
+
+thread_local! {
+    // Set while this thread is writing a panic to a logger
+    static IN_HOOK: Cell<bool> = const { Cell::new(false) };
+}
+
+// Clears IN_HOOK when the hook is done with the logger, even if writing unwinds
+struct HookGuard;
+
+impl Drop for HookGuard {
+    fn drop(&mut self) {
+        IN_HOOK.set(false);
+    }
+}
+
+// The same wording the default hook prints, e.g. "thread 'main' panicked at src/main.rs:2:5: oops"
+fn panic_line(info: &PanicHookInfo<'_>) -> String {
+    let thread = std::thread::current();
+    let name = thread.name().unwrap_or("<unnamed>");
+    let payload = info.payload();
+    let message = payload.downcast_ref::<&str>().copied()
+        .or_else(|| payload.downcast_ref::<String>().map(String::as_str))
+        .unwrap_or("Box<dyn Any>");
+    match info.location() {
+        Some(location) => format!("thread '{name}' panicked at {location}: {message}"),
+        None => format!("thread '{name}' panicked: {message}"),
+    }
+}
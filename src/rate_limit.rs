@@ -0,0 +1,74 @@
+use std::{collections::HashMap, sync::Mutex, time::{Duration, Instant}};
+
+// Most keys tracked at once, the least recently seen is forgotten to make room for a new one
+const MAX_KEYS: usize = 1024;
+
+// Counts entries per key in fixed windows, set with LoggerBuilder::rate_limit
+pub(crate) struct RateLimiter {
+    max: u32,
+    window: Duration,
+    keys: Mutex<Keys>,
+}
+
+#[derive(Default)]
+struct Keys {
+    windows: HashMap<String, Window>,
+    // Bumped on every check, so the smallest last_seen is the least recently used key
+    clock: u64,
+}
+
+struct Window {
+    start: Instant,
+    count: u32,
+    suppressed: u64,
+    last_seen: u64,
+}
+
+impl RateLimiter {
+    pub(crate) fn new(max: u32, window: Duration) -> RateLimiter {
+        RateLimiter { max, window, keys: Mutex::new(Keys::default()) }
+    }
+
+    // Whether an entry with this key can be written.
+    // Gives back None to drop it, or the keys whose suppressed entries need summarising first
+    pub(crate) fn check(&self, key: &str) -> Option<Vec<(String, u64)>> {
+        let mut keys = self.keys.lock().unwrap_or_else(|e| e.into_inner());
+        keys.clock += 1;
+        let clock = keys.clock;
+        let now = Instant::now();
+        let mut summaries = Vec::new();
+        if !keys.windows.contains_key(key) && keys.windows.len() >= MAX_KEYS
+            && let Some(oldest) = keys.windows.iter().min_by_key(|(_, w)| w.last_seen).map(|(k, _)| k.clone())
+            && let Some(evicted) = keys.windows.remove(&oldest)
+            && evicted.suppressed > 0 {
+            summaries.push((oldest, evicted.suppressed));
+        }
+        let window = keys.windows.entry(key.to_string()).or_insert(Window { start: now, count: 0, suppressed: 0, last_seen: clock });
+        window.last_seen = clock;
+        if now.duration_since(window.start) >= self.window {
+            if window.suppressed > 0 {
+                summaries.push((key.to_string(), window.suppressed));
+            }
+            window.start = now;
+            window.count = 0;
+            window.suppressed = 0;
+        }
+        if window.count >= self.max {
+            window.suppressed += 1;
+            return None;
+        }
+        window.count += 1;
+        Some(summaries)
+    }
+
+    // Every key with suppressed entries not yet summarised, clearing them
+    pub(crate) fn drain(&self) -> Vec<(String, u64)> {
+        let mut keys = self.keys.lock().unwrap_or_else(|e| e.into_inner());
+        keys.windows.drain().filter(|(_, w)| w.suppressed > 0).map(|(key, w)| (key, w.suppressed)).collect()
+    }
+}
+
+// The line written in place of the entries a window dropped
+pub(crate) fn summary(key: &str, suppressed: u64) -> String {
+    format!("Warning = suppressed {suppressed} similar messages | {key}")
+}
@@ -0,0 +1,255 @@
+use std::{
+    error::Error,
+    fmt,
+    fs::File,
+    io::{self, BufRead, BufReader, Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+};
+
+use chrono::{
+    format::{parse_and_remainder, Parsed, StrftimeItems},
+    DateTime, FixedOffset, Local, NaiveDate, NaiveTime,
+};
+
+#[cfg(feature = "compress")]
+use crate::rotation::gzipped;
+use crate::{time::TimeZoneMode, Level, LogEntry, LoggerError};
+
+/// A line in a log file that couldn't be read back as an entry, from `Logger::read_entries`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    /// The file the line is in
+    pub path: PathBuf,
+    /// Counting from 1
+    pub line_number: usize,
+    /// The line as it is in the file, invalid UTF-8 replaced
+    pub line: String,
+    /// Why it couldn't be read
+    pub reason: String,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "could not read line {} of {} | {}", self.line_number, self.path.display(), self.reason)
+    }
+}
+
+impl Error for ParseError {}
+
+// Takes plain lines apart the way the logger put them together: timestamp, fraction, sequence token, separator, level
+#[derive(Debug, Clone)]
+pub(crate) struct LineParser {
+    pub(crate) line_date_format: String,
+    // The subsec_precision specifier, when the fraction is added after the timestamp
+    pub(crate) subsec: Option<&'static str>,
+    pub(crate) sequence: bool,
+    pub(crate) separator: String,
+    pub(crate) time_zone: TimeZoneMode,
+}
+
+impl LineParser {
+    // Read one line, taking the day from `date` when the timestamp doesn't have one.
+    // Gives back why the line isn't an entry if it can't be read
+    pub(crate) fn parse(&self, line: &str, date: NaiveDate) -> Result<LogEntry, String> {
+        let mut parsed = Parsed::new();
+        let mut rest = parse_and_remainder(&mut parsed, line, StrftimeItems::new(&self.line_date_format))
+            .map_err(|e| format!("no timestamp matching \"{}\" | {e}", self.line_date_format))?;
+        if let Some(subsec) = self.subsec {
+            rest = parse_and_remainder(&mut parsed, rest, StrftimeItems::new(subsec)).map_err(|e| format!("no fraction of a second | {e}"))?;
+        }
+        if self.sequence
+            && let Some(after) = rest.strip_prefix(" #") {
+            let digits = after.len() - after.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+            rest = &after[digits..];
+        }
+        let rest = rest.strip_prefix(self.separator.as_str()).unwrap_or(rest);
+
+        // A format without a date or a time leaves them to the file's day and its start
+        let day = parsed.to_naive_date().unwrap_or(date);
+        let time = parsed.to_naive_time().unwrap_or(NaiveTime::MIN);
+        let naive = day.and_time(time);
+        let ts = match parsed.to_fixed_offset() {
+            Ok(offset) => naive.and_local_timezone(offset).single(),
+            Err(_) => self.time_zone.resolve(&naive),
+        }
+        .ok_or_else(|| format!("{naive} is not a valid time"))?;
+
+        let (level, message) = split_level(rest);
+        Ok(LogEntry { ts: ts.with_timezone(&Local), level, message: message.to_string() })
+    }
+}
+
+// The "[LEVEL] " the logger writes in front of a message
+fn split_level(text: &str) -> (Option<Level>, &str) {
+    for level in [Level::Trace, Level::Debug, Level::Info, Level::Warn, Level::Error] {
+        if let Some(message) = text.strip_prefix('[').and_then(|t| t.strip_prefix(level.as_str())).and_then(|t| t.strip_prefix("] ")) {
+            return (Some(level), message);
+        }
+    }
+    (None, text)
+}
+
+/// A line found by `Logger::search`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SearchHit {
+    /// The file the line is in
+    pub path: PathBuf,
+    /// Counting from 1
+    pub line_number: usize,
+    /// When the line was written, if its timestamp could be parsed
+    pub ts: Option<DateTime<Local>>,
+    /// The whole line as it is in the file, invalid UTF-8 replaced
+    pub line: String,
+}
+
+// Read the next line into the buffer, giving it back without its line ending. None at the end of the file
+pub(crate) fn next_line<'a>(reader: &mut impl BufRead, buf: &'a mut Vec<u8>) -> io::Result<Option<&'a [u8]>> {
+    buf.clear();
+    if reader.read_until(b'\n', buf)? == 0 {
+        return Ok(None);
+    }
+    let line = buf.strip_suffix(b"\n").unwrap_or(buf);
+    Ok(Some(line.strip_suffix(b"\r").unwrap_or(line)))
+}
+
+/// What `Logger::export_range` copied
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct ExportSummary {
+    /// Files copied
+    pub files: usize,
+    /// Bytes written, including any newline added to the end of a file
+    pub bytes: u64,
+    /// Days in the range without a file, oldest first
+    pub missing_days: Vec<NaiveDate>,
+}
+
+/// What `Logger::import_entries` wrote
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct ImportSummary {
+    /// Entries imported
+    pub entries: usize,
+    /// Each file written and how many of the entries went in it, in the order they were written
+    pub files: Vec<(PathBuf, usize)>,
+}
+
+// Copy a file onto the end of an export, or its gzipped copy if it's been compressed.
+// Gives back the bytes written, None if neither is there
+pub(crate) fn export_file(path: &Path, dest: &mut impl Write) -> Result<Option<u64>, LoggerError> {
+    let open_error = |source| LoggerError::FileOpen { path: path.to_path_buf(), source };
+    let mut reader: Box<dyn Read> = match File::open(path) {
+        Ok(file) => Box::new(file),
+        #[cfg(feature = "compress")]
+        Err(e) if e.kind() == io::ErrorKind::NotFound => match File::open(gzipped(path)) {
+            Ok(file) => Box::new(flate2::read::GzDecoder::new(BufReader::new(file))),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(open_error(e)),
+        },
+        #[cfg(not(feature = "compress"))]
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(open_error(e)),
+    };
+    let mut buf = vec![0; 64 * 1024];
+    let mut bytes = 0;
+    let mut last = None;
+    loop {
+        let read = match reader.read(&mut buf) {
+            Ok(0) => break,
+            Ok(read) => read,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(open_error(e)),
+        };
+        dest.write_all(&buf[..read]).map_err(LoggerError::Write)?;
+        bytes += read as u64;
+        last = Some(buf[read - 1]);
+    }
+    if last.is_some_and(|b| b != b'\n') {
+        dest.write_all(b"\n").map_err(LoggerError::Write)?;
+        bytes += 1;
+    }
+    Ok(Some(bytes))
+}
+
+// The entries of one file, line by line
+pub(crate) struct Entries {
+    pub(crate) path: PathBuf,
+    pub(crate) reader: BufReader<File>,
+    pub(crate) parser: LineParser,
+    pub(crate) date: NaiveDate,
+    pub(crate) line_number: usize,
+    pub(crate) buf: Vec<u8>,
+    pub(crate) failed: bool,
+}
+
+impl Iterator for Entries {
+    type Item = Result<LogEntry, ParseError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while !self.failed {
+            self.line_number += 1;
+            let error = |line: String, reason: String| ParseError { path: self.path.clone(), line_number: self.line_number, line, reason };
+            let bytes = match next_line(&mut self.reader, &mut self.buf) {
+                Ok(Some(bytes)) => bytes,
+                Ok(None) => return None,
+                Err(e) => {
+                    // Where the next line starts isn't known after a failed read, so end after reporting it
+                    self.failed = true;
+                    return Some(Err(error(String::new(), format!("could not read the file | {e}"))));
+                }
+            };
+            if bytes.is_empty() {
+                continue;
+            }
+            return Some(match std::str::from_utf8(bytes) {
+                Ok(line) => self.parser.parse(line, self.date).map_err(|reason| error(line.to_string(), reason)),
+                Err(e) => Err(error(String::from_utf8_lossy(bytes).into_owned(), format!("not valid UTF-8 | {e}"))),
+            });
+        }
+        None
+    }
+}
+
+// When a day starts in the logger's time zone, for rendering its file name.
+// Where the clocks go forward over midnight the day starts an hour in
+pub(crate) fn start_of_day(time_zone: &TimeZoneMode, date: NaiveDate) -> Option<DateTime<FixedOffset>> {
+    let midnight = date.and_time(NaiveTime::MIN);
+    time_zone.resolve(&midnight).or_else(|| time_zone.resolve(&(midnight + chrono::TimeDelta::hours(1))))
+}
+
+// How much of the file is read at a time going back from the end
+const TAIL_CHUNK: u64 = 8 * 1024;
+
+// The last n lines of a file, reading back from the end in chunks. Only what was in the file when it was opened is
+// looked at, so bytes appended while reading are left for next time
+pub(crate) fn tail(file: &mut File, n: usize) -> io::Result<Vec<String>> {
+    let end = file.metadata()?.len();
+    if n == 0 || end == 0 {
+        return Ok(Vec::new());
+    }
+    let mut start = end;
+    let mut chunks: Vec<Vec<u8>> = Vec::new();
+    // A final newline ends the last line rather than starting one more, so it takes n + 1 to have n whole lines
+    let mut newlines = 0;
+    let mut needed = n + 1;
+    while start > 0 && newlines < needed {
+        let size = TAIL_CHUNK.min(start);
+        start -= size;
+        let mut chunk = vec![0; size as usize];
+        file.seek(SeekFrom::Start(start))?;
+        file.read_exact(&mut chunk)?;
+        if start + size == end && chunk.last() != Some(&b'\n') {
+            needed = n;
+        }
+        newlines += chunk.iter().filter(|&&b| b == b'\n').count();
+        chunks.push(chunk);
+    }
+    let data: Vec<u8> = chunks.into_iter().rev().flatten().collect();
+    let data = data.strip_suffix(b"\n").unwrap_or(&data);
+    Ok(data
+        .rsplit(|&b| b == b'\n')
+        .take(n)
+        .collect::<Vec<_>>()
+        .into_iter()
+        .rev()
+        .map(|line| String::from_utf8_lossy(line.strip_suffix(b"\r").unwrap_or(line)).into_owned())
+        .collect())
+}
@@ -0,0 +1,58 @@
+use std::{io::{self, ErrorKind}, time::{Duration, Instant}};
+
+use crate::LoggerError;
+
+// Most time spent waiting between tries of one entry, however many attempts and however long a backoff is set
+const MAX_RETRY_TIME: Duration = Duration::from_secs(1);
+
+// Tries failed writes again, set with LoggerBuilder::retry
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Retry {
+    attempts: u32,
+    backoff: Duration,
+}
+
+impl Retry {
+    pub(crate) fn new(attempts: u32, backoff: Duration) -> Retry {
+        Retry { attempts, backoff }
+    }
+
+    // Run the write, trying it again after a doubling wait for as long as it fails in a way that may pass
+    pub(crate) fn run<T, E>(&self, transient: impl Fn(&E) -> bool, mut write: impl FnMut() -> Result<T, E>) -> Result<T, E> {
+        let start = Instant::now();
+        let mut wait = self.backoff;
+        let mut tries = 0;
+        loop {
+            let result = write();
+            match &result {
+                Err(e) if tries < self.attempts && transient(e) && start.elapsed() + wait <= MAX_RETRY_TIME => {
+                    std::thread::sleep(wait);
+                    wait = wait.saturating_mul(2);
+                    tries += 1;
+                }
+                _ => return result,
+            }
+        }
+    }
+}
+
+// Errors that may well be gone a moment later, e.g. an antivirus scanner briefly holding the file on Windows.
+// A missing directory is not one of them
+pub(crate) fn is_transient(e: &io::Error) -> bool {
+    match e.kind() {
+        ErrorKind::Interrupted | ErrorKind::WouldBlock => true,
+        // Sharing and lock violations show up as permission denied
+        ErrorKind::PermissionDenied => cfg!(windows),
+        // ERROR_SHARING_VIOLATION and ERROR_LOCK_VIOLATION where they aren't mapped to a kind
+        _ => cfg!(windows) && matches!(e.raw_os_error(), Some(32 | 33)),
+    }
+}
+
+// Whether a failed write to the log file is worth trying again
+pub(crate) fn is_transient_error(e: &LoggerError) -> bool {
+    match e {
+        LoggerError::FileOpen { source, .. } => is_transient(source),
+        LoggerError::Write(e) | LoggerError::Flush(e) | LoggerError::FileLock(e) => is_transient(e),
+        _ => false,
+    }
+}
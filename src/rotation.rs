@@ -0,0 +1,58 @@
+use std::{
+    ffi::OsString,
+    fs, io,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use crate::LoggerError;
+
+// The name of a rotated copy, e.g. "log_20240101.log.2"
+pub(crate) fn numbered(path: &Path, number: usize) -> PathBuf {
+    let mut name = OsString::from(path.as_os_str());
+    name.push(format!(".{number}"));
+    PathBuf::from(name)
+}
+
+// The same name with ".gz" on the end, for copies that have been compressed
+pub(crate) fn gzipped(path: &Path) -> PathBuf {
+    let mut name = OsString::from(path.as_os_str());
+    name.push(".gz");
+    PathBuf::from(name)
+}
+
+// Shift "<name>.1" to "<name>.2" and so on, dropping anything past keep, then move the file itself to "<name>.1".
+// Compressed copies ("<name>.1.gz") are shifted the same way. The file must already be closed.
+// At least one copy is always kept, so a keep of 0 works like 1 and the entries in the full file aren't lost.
+// Gives back the files removed and how long since each was written
+pub(crate) fn rotate(path: &Path, keep: usize) -> Result<Vec<(PathBuf, Duration)>, LoggerError> {
+    let keep = keep.max(1);
+    let mut removed = Vec::new();
+    remove_if_exists(&numbered(path, keep), &mut removed).map_err(LoggerError::Rotate)?;
+    remove_if_exists(&gzipped(&numbered(path, keep)), &mut removed).map_err(LoggerError::Rotate)?;
+    for number in (1..keep).rev() {
+        let from = numbered(path, number);
+        let to = numbered(path, number + 1);
+        // Windows won't rename over an existing file, the target was removed or moved on by the previous step
+        if from.exists() {
+            fs::rename(&from, &to).map_err(LoggerError::Rotate)?;
+        }
+        if gzipped(&from).exists() {
+            fs::rename(gzipped(&from), gzipped(&to)).map_err(LoggerError::Rotate)?;
+        }
+    }
+    fs::rename(path, numbered(path, 1)).map_err(LoggerError::Rotate)?;
+    Ok(removed)
+}
+
+fn remove_if_exists(path: &Path, removed: &mut Vec<(PathBuf, Duration)>) -> io::Result<()> {
+    let age = fs::metadata(path).and_then(|metadata| metadata.modified()).map(|modified| modified.elapsed().unwrap_or_default());
+    match fs::remove_file(path) {
+        Ok(()) => {
+            removed.push((path.to_path_buf(), age.unwrap_or_default()));
+            Ok(())
+        }
+        Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+        Err(_) => Ok(()),
+    }
+}
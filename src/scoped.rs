@@ -0,0 +1,124 @@
+use std::fmt;
+
+use crate::{Level, Location, Logger, LoggerError};
+
+/// A view of a `Logger` that puts a fixed prefix in front of every message, e.g. "[db] ".
+/// It borrows the parent, so it writes to the same dated file with the same settings.
+/// Made with `Logger::with_prefix`, and prefixes can be nested.
+/// # Example
+/// ```rust
+/// use d_logger::Logger;
+/// 
+/// let path = std::env::temp_dir().join("d_logger_with_prefix");
+/// let logger = Logger::builder().path(&path).file_name_format("prefix_%Y%m%d.log").build().unwrap();
+/// # let file = path.join(chrono::Local::now().format("prefix_%Y%m%d.log").to_string());
+/// # let _ = std::fs::remove_file(&file);
+/// let db = logger.with_prefix("[db] ");
+/// let http = logger.with_prefix("[http]");
+/// let auth = http.with_prefix("[auth] ");
+/// 
+/// logger.write_log("parent");
+/// db.write_log("connected");
+/// auth.warn("bad password");
+/// 
+/// let contents = std::fs::read_to_string(&file).unwrap();
+/// let lines: Vec<&str> = contents.lines().collect();
+/// assert!(lines[0].ends_with(" parent"));
+/// assert!(lines[1].ends_with(" [db] connected"));
+/// assert!(lines[2].ends_with(" [WARN] [http][auth] bad password"));
+/// ```
+#[derive(Clone)]
+pub struct ScopedLogger<'a> {
+    parent: &'a Logger,
+    prefix: String,
+}
+
+impl<'a> ScopedLogger<'a> {
+    pub(crate) fn new(parent: &'a Logger, prefix: String) -> Self {
+        ScopedLogger { parent, prefix }
+    }
+
+    /// Make a child with this prefix followed by another one
+    pub fn with_prefix(&self, prefix: &str) -> ScopedLogger<'a> {
+        ScopedLogger::new(self.parent, format!("{}{}", self.prefix, prefix))
+    }
+
+    /// The full prefix written before each message
+    pub fn prefix(&self) -> &str {
+        &self.prefix
+    }
+
+    /// The logger this writes through
+    pub fn parent(&self) -> &'a Logger {
+        self.parent
+    }
+
+    /// Write a prefixed line to the log, see `Logger::write_log`
+    pub fn write_log(&self, line: &str) -> bool {
+        self.try_write_log(line).is_ok()
+    }
+
+    /// Write a prefixed line to the log, see `Logger::try_write_log`
+    pub fn try_write_log(&self, line: &str) -> Result<(), LoggerError> {
+        self.parent.try_write_log(&self.prefixed(line))
+    }
+
+    /// Write a prefixed line with key value pairs, see `Logger::write_log_kv`
+    pub fn write_log_kv(&self, msg: &str, kvs: &[(&str, &str)]) -> bool {
+        self.parent.write_log_kv(&self.prefixed(msg), kvs)
+    }
+
+    /// Write a prefixed line at the given level, see `Logger::write_level`
+    pub fn write_level(&self, level: Level, line: &str) -> bool {
+        self.try_write_level(level, line).is_ok()
+    }
+
+    /// Write a prefixed line at the given level, see `Logger::try_write_level`
+    pub fn try_write_level(&self, level: Level, line: &str) -> Result<(), LoggerError> {
+        self.parent.try_write_level(level, &self.prefixed(line))
+    }
+
+    /// Write a prefixed line with its source location, see `Logger::write_log_at`
+    pub fn write_log_at(&self, level: Level, location: &Location, line: &str) -> bool {
+        self.parent.write_log_at(level, location, &self.prefixed(line))
+    }
+
+    /// Write a prefixed line given as format arguments, see `Logger::write_log_fmt`
+    pub fn write_log_fmt(&self, args: fmt::Arguments<'_>) -> bool {
+        self.parent.write_log_fmt(format_args!("{}{args}", self.prefix))
+    }
+
+    /// Write a prefixed line given as format arguments with its source location, see `Logger::write_log_at_fmt`
+    pub fn write_log_at_fmt(&self, level: Level, location: &Location, args: fmt::Arguments<'_>) -> bool {
+        self.parent.write_log_at_fmt(level, location, format_args!("{}{args}", self.prefix))
+    }
+
+    /// Write a prefixed line at Trace level
+    pub fn trace(&self, line: &str) -> bool {
+        self.write_level(Level::Trace, line)
+    }
+
+    /// Write a prefixed line at Debug level
+    pub fn debug(&self, line: &str) -> bool {
+        self.write_level(Level::Debug, line)
+    }
+
+    /// Write a prefixed line at Info level
+    pub fn info(&self, line: &str) -> bool {
+        self.write_level(Level::Info, line)
+    }
+
+    /// Write a prefixed line at Warn level
+    pub fn warn(&self, line: &str) -> bool {
+        self.write_level(Level::Warn, line)
+    }
+
+    /// Write a prefixed line at Error level
+    pub fn error(&self, line: &str) -> bool {
+        self.write_level(Level::Error, line)
+    }
+
+    fn prefixed(&self, line: &str) -> String {
+        format!("{}{}", self.prefix, line)
+    }
+}
@@ -0,0 +1,50 @@
+use std::{io, sync::Arc, thread::JoinHandle};
+
+use signal_hook::{consts::SIGHUP, iterator::Signals};
+
+use crate::{LogEvent, Logger};
+
+impl Logger {
+    /// Start a thread that calls `reopen` on the logger every time the process receives SIGHUP.
+    /// The thread keeps its own reference to the logger and runs for the rest of the process.
+    /// If the old file can't be flushed, the entries lost are reported through `last_error` and `LogEvent::WriteFailed`
+    /// # Arguments
+    /// * `logger` - The shared logger to reopen
+    /// # Example
+    /// ```rust
+    /// use d_logger::Logger;
+    /// use std::sync::Arc;
+    /// 
+    /// let path = std::env::temp_dir().join("d_logger_sighup");
+    /// let logger = Arc::new(Logger::builder().path(&path).file_name_format("sighup_%Y%m%d.log").build().unwrap());
+    /// let file = path.join(chrono::Local::now().format("sighup_%Y%m%d.log").to_string());
+    /// let rotated = path.join("sighup.log.1");
+    /// # let _ = std::fs::remove_file(&file);
+    /// # let _ = std::fs::remove_file(&rotated);
+    /// Logger::reopen_on_sighup(&logger).unwrap();
+    /// 
+    /// logger.write_log("before rotate");
+    /// std::fs::rename(&file, &rotated).unwrap();
+    /// signal_hook::low_level::raise(signal_hook::consts::SIGHUP).unwrap();
+    /// std::thread::sleep(std::time::Duration::from_millis(200));
+    /// logger.write_log("after rotate");
+    /// assert!(!std::fs::read_to_string(&rotated).unwrap().contains("after rotate"));
+    /// assert!(std::fs::read_to_string(&file).unwrap().contains("after rotate"));
+    /// ```
+    /// # Returns
+    /// * `Err(io::Error)` if the signal handler couldn't be installed
+    pub fn reopen_on_sighup(logger: &Arc<Logger>) -> io::Result<JoinHandle<()>> {
+        let mut signals = Signals::new([SIGHUP])?;
+        let logger = Arc::clone(logger);
+        Ok(std::thread::spawn(move || {
+            for _ in signals.forever() {
+                if let Err(e) = logger.reopen() {
+                    // There's no caller to hand the error to
+                    logger.inner.record_error(e.duplicate());
+                    logger.inner.events.emit(LogEvent::WriteFailed { error: e });
+                    logger.inner.events.dispatch();
+                }
+            }
+        }))
+    }
+}
@@ -0,0 +1,174 @@
+use std::{
+    collections::VecDeque,
+    fs::{File, OpenOptions},
+    io::{self, Write},
+    path::Path,
+    sync::{Arc, Mutex},
+};
+
+use chrono::{DateTime, FixedOffset};
+
+use crate::Level;
+
+/// Somewhere else entries are copied to, added with `Logger::add_sink`.
+/// Each call gets one formatted entry exactly as written to the dated file, ending with the newline
+pub trait Sink {
+    /// Write one formatted entry
+    fn write_entry(&self, formatted: &str) -> io::Result<()>;
+
+    /// Write one entry, given the parts it was made from as well as the formatted text.
+    /// This is what the logger calls, override it for sinks that lay entries out their own way or need the level.
+    /// By default it hands the formatted text to `write_entry`
+    fn write_record(&self, record: &Record) -> io::Result<()> {
+        self.write_entry(record.formatted)
+    }
+
+    /// The last `n` entries, oldest first, for sinks that keep them like `RingBufferSink`. None by default
+    fn tail(&self, _n: usize) -> Option<Vec<String>> {
+        None
+    }
+}
+
+/// An entry as handed to a sink
+#[derive(Debug, Clone, Copy)]
+pub struct Record<'a> {
+    /// The entry exactly as written to the file, ending with the newline
+    pub formatted: &'a str,
+    /// When the entry was written, in the logger's time zone
+    pub time: &'a DateTime<FixedOffset>,
+    /// The level, None for lines written without one
+    pub level: Option<Level>,
+    /// The message as given, without the timestamp or key value pairs
+    pub message: &'a str,
+    /// Key value pairs given with `write_log_kv`
+    pub kvs: &'a [(&'a str, &'a str)],
+}
+
+/// Appends every entry to a single file, e.g. a copy of the log on another disk.
+/// The file isn't dated, rotated or cleaned
+/// # Example
+/// ```rust
+/// use d_logger::{FileSink, Sink};
+/// 
+/// let path = std::env::temp_dir().join("d_logger_file_sink.log");
+/// # let _ = std::fs::remove_file(&path);
+/// let sink = FileSink::new(&path).unwrap();
+/// sink.write_entry("2024-01-01 10:00:00 copied\n").unwrap();
+/// assert_eq!(std::fs::read_to_string(&path).unwrap(), "2024-01-01 10:00:00 copied\n");
+/// ```
+#[derive(Debug)]
+pub struct FileSink {
+    file: Mutex<File>,
+}
+
+impl FileSink {
+    /// Open the file for appending, creating it if needed
+    /// # Arguments
+    /// * `path` - The file to append entries to
+    pub fn new(path: impl AsRef<Path>) -> io::Result<FileSink> {
+        let file = OpenOptions::new().append(true).create(true).open(path)?;
+        Ok(FileSink { file: Mutex::new(file) })
+    }
+}
+
+impl Sink for FileSink {
+    fn write_entry(&self, formatted: &str) -> io::Result<()> {
+        // One write per entry so lines from different threads aren't interleaved
+        self.file.lock().unwrap_or_else(|e| e.into_inner()).write_all(formatted.as_bytes())
+    }
+}
+
+/// Keeps every entry in memory, mostly for checking what a test logged.
+/// Clones share the same entries, so keep one and give the logger the other
+/// # Example
+/// ```rust
+/// use d_logger::{MemorySink, Sink};
+/// 
+/// let sink = MemorySink::new();
+/// let handle = sink.clone();
+/// sink.write_entry("first\n").unwrap();
+/// assert_eq!(handle.entries(), vec!["first\n".to_string()]);
+/// handle.clear();
+/// assert!(sink.entries().is_empty());
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct MemorySink {
+    entries: Arc<Mutex<Vec<String>>>,
+}
+
+impl MemorySink {
+    pub fn new() -> MemorySink {
+        MemorySink::default()
+    }
+
+    /// A copy of the entries written so far, oldest first
+    pub fn entries(&self) -> Vec<String> {
+        self.entries.lock().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    /// Throw away the entries written so far
+    pub fn clear(&self) {
+        self.entries.lock().unwrap_or_else(|e| e.into_inner()).clear();
+    }
+}
+
+impl Sink for MemorySink {
+    fn write_entry(&self, formatted: &str) -> io::Result<()> {
+        self.entries.lock().unwrap_or_else(|e| e.into_inner()).push(formatted.to_string());
+        Ok(())
+    }
+}
+
+/// Keeps the most recent `capacity` entries in memory, e.g. for a diagnostics endpoint showing the last 200 lines.
+/// Once attached `Logger::tail` reads them back. Clones share the same entries
+/// # Example
+/// ```rust
+/// use d_logger::{Logger, RingBufferSink};
+/// 
+/// let path = std::env::temp_dir().join("d_logger_ring_buffer");
+/// let logger = Logger::builder().path(&path).build().unwrap();
+/// assert!(logger.tail(10).is_none());
+/// logger.add_sink(Box::new(RingBufferSink::new(200)));
+/// 
+/// for i in 0..500 {
+///     logger.write_log(&format!("line {i}"));
+/// }
+/// let lines = logger.tail(1000).unwrap();
+/// assert_eq!(lines.len(), 200);
+/// for (line, i) in lines.iter().zip(300..500) {
+///     assert!(line.trim_end().ends_with(&format!(" line {i}")));
+/// }
+/// let last = logger.tail(2).unwrap();
+/// assert!(last[0].contains("line 498") && last[1].contains("line 499"));
+/// ```
+#[derive(Debug, Clone)]
+pub struct RingBufferSink {
+    entries: Arc<Mutex<VecDeque<String>>>,
+    capacity: usize,
+}
+
+impl RingBufferSink {
+    /// Keep up to `capacity` entries, the oldest is dropped to make room for each new one after that
+    pub fn new(capacity: usize) -> RingBufferSink {
+        RingBufferSink { entries: Arc::new(Mutex::new(VecDeque::with_capacity(capacity))), capacity }
+    }
+}
+
+impl Sink for RingBufferSink {
+    fn write_entry(&self, formatted: &str) -> io::Result<()> {
+        if self.capacity == 0 {
+            return Ok(());
+        }
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        if entries.len() == self.capacity {
+            entries.pop_front();
+        }
+        entries.push_back(formatted.to_string());
+        Ok(())
+    }
+
+    fn tail(&self, n: usize) -> Option<Vec<String>> {
+        let entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        Some(entries.iter().skip(entries.len().saturating_sub(n)).cloned().collect())
+    }
+}
@@ -0,0 +1,104 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// What a logger has done since it was built or since `Logger::reset_stats`, from `Logger::stats`.
+/// An entry written to a routed level's file as well as the main one counts once for each file
+/// # Example
+/// ```rust
+/// use d_logger::{test_support::ManualClock, Logger, LoggerStats};
+/// use chrono::TimeZone;
+/// 
+/// let path = std::env::temp_dir().join("d_logger_stats");
+/// # let _ = std::fs::remove_file(&path);
+/// # let _ = std::fs::remove_dir_all(&path);
+/// let start = chrono::Local.with_ymd_and_hms(2024, 5, 1, 12, 0, 0).unwrap();
+/// let logger = Logger::builder().path(&path).file_name_format("stats.log").line_date_format("").separator("").days_keep(Some(7)).clock(ManualClock::new(start)).build().unwrap();
+/// 
+/// // With a file in place of the directory the first write fails
+/// std::fs::remove_dir_all(&path).unwrap();
+/// std::fs::write(&path, "").unwrap();
+/// assert!(!logger.write_log("lost"));
+/// std::fs::remove_file(&path).unwrap();
+/// std::fs::create_dir(&path).unwrap();
+/// logger.write_log("one");
+/// logger.write_log("two");
+/// 
+/// let old = std::time::SystemTime::from(start) - std::time::Duration::from_secs(86400 * 30);
+/// std::fs::write(path.join("old.log"), "old").unwrap();
+/// std::fs::File::options().append(true).open(path.join("old.log")).unwrap().set_modified(old).unwrap();
+/// logger.log_clean(None).unwrap();
+/// 
+/// let nl = if cfg!(windows) { 2 } else { 1 };
+/// let stats = logger.stats();
+/// assert_eq!(stats.lines_written, 2);
+/// assert_eq!(stats.bytes_written, 2 * (3 + nl));
+/// assert_eq!(stats.write_failures, 1);
+/// assert_eq!(stats.clean_runs, 1);
+/// assert_eq!(stats.files_deleted, 1);
+/// assert_eq!(stats.entries_dropped, 0);
+/// 
+/// logger.reset_stats();
+/// assert_eq!(logger.stats(), LoggerStats::default());
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct LoggerStats {
+    /// Entries written to a file
+    pub lines_written: u64,
+    /// Bytes written to files, newlines included
+    pub bytes_written: u64,
+    /// Writes that failed, each losing its entries
+    pub write_failures: u64,
+    /// Times the log cleaner has run, by hand or by auto clean. Dry runs don't count
+    pub clean_runs: u64,
+    /// Files the log cleaner has deleted or moved to an archive
+    pub files_deleted: u64,
+    /// Entries dropped by the rate limit or by a full `AsyncLogger` queue
+    pub entries_dropped: u64,
+}
+
+// The counters behind LoggerStats, bumped from the write and clean paths
+#[derive(Default)]
+pub(crate) struct Stats {
+    pub(crate) lines_written: AtomicU64,
+    pub(crate) bytes_written: AtomicU64,
+    pub(crate) write_failures: AtomicU64,
+    pub(crate) clean_runs: AtomicU64,
+    pub(crate) files_deleted: AtomicU64,
+    pub(crate) entries_dropped: AtomicU64,
+}
+
+impl Stats {
+    fn counters(&self) -> [&AtomicU64; 6] {
+        [&self.lines_written, &self.bytes_written, &self.write_failures, &self.clean_runs, &self.files_deleted, &self.entries_dropped]
+    }
+
+    pub(crate) fn snapshot(&self) -> LoggerStats {
+        let [lines_written, bytes_written, write_failures, clean_runs, files_deleted, entries_dropped] =
+            self.counters().map(|counter| counter.load(Ordering::Relaxed));
+        LoggerStats { lines_written, bytes_written, write_failures, clean_runs, files_deleted, entries_dropped }
+    }
+
+    pub(crate) fn reset(&self) {
+        for counter in self.counters() {
+            counter.store(0, Ordering::Relaxed);
+        }
+    }
+
+    // Count the result of writing entries to a file
+    pub(crate) fn written<T, E>(&self, result: &Result<T, E>, count: u32, bytes: usize) {
+        match result {
+            Ok(_) => {
+                self.lines_written.fetch_add(u64::from(count), Ordering::Relaxed);
+                self.bytes_written.fetch_add(bytes as u64, Ordering::Relaxed);
+            }
+            Err(_) => {
+                self.write_failures.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+    }
+
+    // Count a finished run of the log cleaner
+    pub(crate) fn cleaned(&self, deleted: usize) {
+        self.clean_runs.fetch_add(1, Ordering::Relaxed);
+        self.files_deleted.fetch_add(deleted as u64, Ordering::Relaxed);
+    }
+}
@@ -0,0 +1,225 @@
+// RFC 5424 syslog entries, for the Syslog output format and the syslog socket sink
+use std::sync::OnceLock;
+
+use chrono::{DateTime, FixedOffset, SecondsFormat};
+
+use crate::{format, Level, LineFormatter};
+
+/// Syslog facility, combined with the entry's level into the PRI value at the start of each RFC 5424 entry
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum Facility {
+    Kern = 0,
+    #[default]
+    User = 1,
+    Mail = 2,
+    Daemon = 3,
+    Auth = 4,
+    Syslog = 5,
+    Lpr = 6,
+    News = 7,
+    Uucp = 8,
+    Cron = 9,
+    Authpriv = 10,
+    Ftp = 11,
+    Local0 = 16,
+    Local1 = 17,
+    Local2 = 18,
+    Local3 = 19,
+    Local4 = 20,
+    Local5 = 21,
+    Local6 = 22,
+    Local7 = 23,
+}
+
+impl Facility {
+    /// The PRI value for an entry at this level, facility * 8 + severity.
+    /// Error is severity 3, Warn 4, Info and lines without a level 6, Debug and Trace 7
+    /// # Example
+    /// ```rust
+    /// use d_logger::{Facility, Level};
+    /// 
+    /// assert_eq!(Facility::User.priority(Some(Level::Error)), 11);
+    /// assert_eq!(Facility::Local0.priority(Some(Level::Info)), 134);
+    /// assert_eq!(Facility::Daemon.priority(None), 30);
+    /// ```
+    pub fn priority(self, level: Option<Level>) -> u8 {
+        self as u8 * 8 + severity(level)
+    }
+}
+
+// The syslog severity for a level, also used as the journal's PRIORITY
+pub(crate) fn severity(level: Option<Level>) -> u8 {
+    match level {
+        Some(Level::Error) => 3,
+        Some(Level::Warn) => 4,
+        Some(Level::Info) | None => 6,
+        Some(Level::Debug) | Some(Level::Trace) => 7,
+    }
+}
+
+/// Formats entries as RFC 5424 syslog messages,
+/// "<PRI>1 TIMESTAMP HOSTNAME APP-NAME PROCID MSGID STRUCTURED-DATA MSG" with no MSGID or structured data.
+/// The host name defaults to the machine's and the app name to the executable's, PROCID is the process id.
+/// `OutputFormat::Syslog` uses this with the defaults, set it with `formatter` on the builder to choose the names
+/// # Example
+/// ```rust
+/// use d_logger::{Facility, Level, LineFormatter, SyslogFormatter};
+/// use chrono::TimeZone;
+/// 
+/// let formatter = SyslogFormatter::new(Facility::Local0).hostname("web-1").app_name("api");
+/// let pid = std::process::id();
+/// let ts = chrono::FixedOffset::east_opt(0).unwrap().with_ymd_and_hms(2024, 1, 1, 10, 0, 0).unwrap();
+/// assert_eq!(formatter.format(&ts, Some(Level::Info), "started"), format!("<134>1 2024-01-01T10:00:00Z web-1 api {pid} - - started"));
+/// assert_eq!(formatter.format(&ts, Some(Level::Error), "failed"), format!("<131>1 2024-01-01T10:00:00Z web-1 api {pid} - - failed"));
+/// 
+/// let ts = chrono::FixedOffset::east_opt(-5 * 3600).unwrap().with_ymd_and_hms(2024, 6, 30, 23, 59, 59).unwrap();
+/// let formatter = SyslogFormatter::new(Facility::Daemon).hostname("db 2").app_name("");
+/// assert_eq!(formatter.format(&ts, None, "no level"), format!("<30>1 2024-06-30T23:59:59-05:00 db_2 - {pid} - - no level"));
+/// ```
+#[derive(Debug, Clone)]
+pub struct SyslogFormatter {
+    facility: Facility,
+    hostname: Option<String>,
+    app_name: Option<String>,
+    seconds: SecondsFormat,
+}
+
+impl SyslogFormatter {
+    pub fn new(facility: Facility) -> Self {
+        SyslogFormatter { facility, hostname: None, app_name: None, seconds: SecondsFormat::Secs }
+    }
+
+    /// The HOSTNAME field, instead of the machine's name
+    pub fn hostname(mut self, hostname: impl Into<String>) -> Self {
+        self.hostname = Some(hostname.into());
+        self
+    }
+
+    /// The APP-NAME field, instead of the executable's name
+    pub fn app_name(mut self, app_name: impl Into<String>) -> Self {
+        self.app_name = Some(app_name.into());
+        self
+    }
+
+    // Fractional seconds in the timestamp, RFC 5424 allows at most six digits
+    pub(crate) fn seconds(mut self, seconds: SecondsFormat) -> Self {
+        self.seconds = seconds;
+        self
+    }
+}
+
+impl LineFormatter for SyslogFormatter {
+    fn format(&self, ts: &DateTime<FixedOffset>, level: Option<Level>, msg: &str) -> String {
+        let hostname = self.hostname.as_deref().unwrap_or_else(|| local_hostname());
+        let app_name = self.app_name.as_deref().unwrap_or_else(|| local_app_name());
+        format!(
+            "<{}>1 {} {} {} {} - - {}",
+            self.facility.priority(level),
+            ts.to_rfc3339_opts(self.seconds, true),
+            header_field(hostname, 255),
+            header_field(app_name, 48),
+            std::process::id(),
+            msg,
+        )
+    }
+}
+
+// Header fields are printable ASCII without spaces, "-" when there's nothing to put
+fn header_field(value: &str, max_len: usize) -> String {
+    let field: String = value.chars().take(max_len).map(|c| if c.is_ascii_graphic() { c } else { '_' }).collect();
+    if field.is_empty() { "-".to_string() } else { field }
+}
+
+fn local_hostname() -> &'static str {
+    static HOSTNAME: OnceLock<String> = OnceLock::new();
+    HOSTNAME.get_or_init(|| format::system_hostname().unwrap_or_default())
+}
+
+pub(crate) fn local_app_name() -> &'static str {
+    static APP_NAME: OnceLock<String> = OnceLock::new();
+    APP_NAME.get_or_init(|| {
+        std::env::current_exe()
+            .ok()
+            .and_then(|exe| exe.file_stem().map(|stem| stem.to_string_lossy().into_owned()))
+            .unwrap_or_default()
+    })
+}
+
+#[cfg(unix)]
+pub use unix::SyslogSink;
+
+#[cfg(unix)]
+mod unix {
+    use std::{io, os::unix::net::UnixDatagram, path::Path};
+
+    use crate::{format, sink::Record, Facility, LineFormatter, Sink, SyslogFormatter};
+
+    /// Sends each entry to the local syslog daemon as an RFC 5424 message, one datagram per entry.
+    /// The entry is formatted for syslog whatever the logger's output format, so the file can stay in its own layout.
+    /// Unix only
+    /// # Example
+    /// ```rust
+    /// use d_logger::{Facility, Logger, SyslogSink};
+    /// use std::os::unix::net::UnixDatagram;
+    /// 
+    /// let socket_path = std::env::temp_dir().join("d_logger_syslog.sock");
+    /// # let _ = std::fs::remove_file(&socket_path);
+    /// let collector = UnixDatagram::bind(&socket_path).unwrap();
+    /// let path = std::env::temp_dir().join("d_logger_syslog_sink");
+    /// let logger = Logger::builder().path(&path).file_name_format("syslog_%Y%m%d.log").build().unwrap();
+    /// let file = path.join(chrono::Local::now().format("syslog_%Y%m%d.log").to_string());
+    /// # let _ = std::fs::remove_file(&file);
+    /// logger.add_sink(Box::new(SyslogSink::with_path(&socket_path, Facility::Local3).unwrap()));
+    /// 
+    /// logger.warn("disk nearly full");
+    /// let mut buf = [0; 1024];
+    /// let len = collector.recv(&mut buf).unwrap();
+    /// let message = std::str::from_utf8(&buf[..len]).unwrap();
+    /// let priority: u8 = message[1..message.find('>').unwrap()].parse().unwrap();
+    /// assert_eq!(priority, Facility::Local3.priority(Some(d_logger::Level::Warn)));
+    /// assert!(message.ends_with(" - - disk nearly full"));
+    /// // The file keeps the plain layout
+    /// assert!(std::fs::read_to_string(&file).unwrap().trim_end().ends_with("[WARN] disk nearly full"));
+    /// ```
+    #[derive(Debug)]
+    pub struct SyslogSink {
+        socket: UnixDatagram,
+        formatter: SyslogFormatter,
+    }
+
+    impl SyslogSink {
+        /// Send to /dev/log, where syslog daemons and journald listen
+        /// # Returns
+        /// * `Err(io::Error)` if /dev/log can't be connected to
+        pub fn new(facility: Facility) -> io::Result<Self> {
+            Self::with_path("/dev/log", facility)
+        }
+
+        /// Send to the unix datagram socket at this path
+        /// # Returns
+        /// * `Err(io::Error)` if the socket can't be connected to
+        pub fn with_path(path: impl AsRef<Path>, facility: Facility) -> io::Result<Self> {
+            let socket = UnixDatagram::unbound()?;
+            socket.connect(path)?;
+            Ok(SyslogSink { socket, formatter: SyslogFormatter::new(facility) })
+        }
+
+        /// Use this formatter for the messages, e.g. to set the host or app name
+        pub fn formatter(mut self, formatter: SyslogFormatter) -> Self {
+            self.formatter = formatter;
+            self
+        }
+    }
+
+    impl Sink for SyslogSink {
+        fn write_entry(&self, formatted: &str) -> io::Result<()> {
+            self.socket.send(formatted.trim_end().as_bytes()).map(|_| ())
+        }
+
+        fn write_record(&self, record: &Record) -> io::Result<()> {
+            let mut msg = record.message.to_string();
+            format::push_logfmt(&mut msg, record.kvs);
+            self.socket.send(self.formatter.format(record.time, record.level, &msg).as_bytes()).map(|_| ())
+        }
+    }
+}
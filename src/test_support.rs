@@ -0,0 +1,105 @@
+//! Helpers for testing code that logs
+
+use std::{sync::{Arc, Mutex}, time::Duration};
+use chrono::{DateTime, Local};
+
+use crate::{Clock, ConsoleMode};
+
+/// A clock that only moves when told to, so a test can cross midnight or age files by days without waiting.
+/// Clones share the same time, keep one to move the clock given to a logger
+/// # Example
+/// ```rust
+/// use d_logger::{test_support::ManualClock, Logger};
+/// use chrono::TimeZone;
+/// use std::time::Duration;
+/// 
+/// let path = std::env::temp_dir().join("d_logger_manual_clock");
+/// # let _ = std::fs::remove_dir_all(&path);
+/// let clock = ManualClock::new(chrono::Local.with_ymd_and_hms(2024, 5, 1, 23, 59, 59).unwrap());
+/// let logger = Logger::builder().path(&path).file_name_format("app_%Y%m%d.log").line_date_format("%H:%M:%S").clock(clock.clone()).build().unwrap();
+/// 
+/// logger.write_log("before midnight");
+/// clock.advance(Duration::from_secs(1));
+/// logger.write_log("after midnight");
+/// 
+/// assert_eq!(std::fs::read_to_string(path.join("app_20240501.log")).unwrap().trim_end(), "23:59:59 before midnight");
+/// assert_eq!(std::fs::read_to_string(path.join("app_20240502.log")).unwrap().trim_end(), "00:00:00 after midnight");
+/// ```
+#[derive(Debug, Clone)]
+pub struct ManualClock {
+    now: Arc<Mutex<DateTime<Local>>>,
+}
+
+impl ManualClock {
+    /// A clock stopped at `start`
+    pub fn new(start: DateTime<Local>) -> ManualClock {
+        ManualClock { now: Arc::new(Mutex::new(start)) }
+    }
+
+    /// Move the clock to another time, backwards as well as forwards
+    pub fn set(&self, now: DateTime<Local>) {
+        *self.now.lock().unwrap_or_else(|e| e.into_inner()) = now;
+    }
+
+    /// Move the clock forwards, it stays where it is if that would go past the latest time chrono can hold
+    pub fn advance(&self, by: Duration) {
+        let mut now = self.now.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(later) = chrono::Duration::from_std(by).ok().and_then(|by| now.checked_add_signed(by)) {
+            *now = later;
+        }
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> DateTime<Local> {
+        *self.now.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Stands in for standard output and standard error, so a test can see what each `ConsoleMode` echoes.
+/// Clones share what was written, give one to `.console_capture()` on the builder and keep one to read back
+/// # Example
+/// ```rust
+/// use d_logger::{test_support::ConsoleCapture, ConsoleMode, Logger};
+/// 
+/// let path = std::env::temp_dir().join("d_logger_console_capture");
+/// # let _ = std::fs::remove_dir_all(&path);
+/// let capture = ConsoleCapture::new();
+/// let logger = Logger::builder().path(&path).line_date_format("").separator("").console(ConsoleMode::Stdout).console_capture(capture.clone()).build().unwrap();
+/// 
+/// logger.write_log("hello");
+/// assert_eq!(capture.stdout().trim_end(), "hello");
+/// assert_eq!(capture.stderr(), "");
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct ConsoleCapture {
+    stdout: Arc<Mutex<String>>,
+    stderr: Arc<Mutex<String>>,
+}
+
+impl ConsoleCapture {
+    /// A capture with nothing written to either stream yet
+    pub fn new() -> ConsoleCapture {
+        ConsoleCapture::default()
+    }
+
+    /// Everything echoed to standard output so far
+    pub fn stdout(&self) -> String {
+        self.stdout.lock().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    /// Everything echoed to standard error so far
+    pub fn stderr(&self) -> String {
+        self.stderr.lock().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    // Take an echo in place of the stream the mode names
+    pub(crate) fn echo(&self, mode: ConsoleMode, entry: &str) {
+        let stream = match mode {
+            ConsoleMode::Off => return,
+            ConsoleMode::Stdout => &self.stdout,
+            ConsoleMode::Stderr => &self.stderr,
+        };
+        stream.lock().unwrap_or_else(|e| e.into_inner()).push_str(entry);
+    }
+}
@@ -0,0 +1,192 @@
+use std::{path::{Path, PathBuf}, sync::Mutex, time::Duration};
+use chrono::{DateTime, FixedOffset, Local, NaiveDateTime, TimeZone, Utc};
+
+use crate::{render_date, LoggerError, SECS_1_DAY};
+
+/// Which time zone timestamps and dated file names are rendered in.
+/// The same zone is used for both, so files roll over at midnight in that zone.
+/// # Example
+/// ```rust
+/// use d_logger::{Logger, TimeZoneMode};
+/// use chrono::FixedOffset;
+/// 
+/// let path = std::env::temp_dir().join("d_logger_time_zone");
+/// let offset = FixedOffset::east_opt(5 * 3600 + 30 * 60).unwrap();
+/// let logger = Logger::builder()
+///     .path(&path)
+///     .file_name_format("tz_%z.log")
+///     .line_date_format("%Y-%m-%d %H:%M:%S %z")
+///     .time_zone(TimeZoneMode::FixedOffset(offset))
+///     .build()
+///     .unwrap();
+/// logger.write_log("fixed offset");
+/// 
+/// // The file name and the line both use the fixed offset rather than the local one
+/// let contents = std::fs::read_to_string(path.join("tz_+0530.log")).unwrap();
+/// let line = contents.lines().last().unwrap();
+/// assert!(line.ends_with(" +0530 fixed offset"));
+/// let expected = chrono::Utc::now().with_timezone(&offset).format("%Y-%m-%d").to_string();
+/// assert!(line.starts_with(&expected));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TimeZoneMode {
+    /// The machine's local time zone
+    #[default]
+    Local,
+    /// UTC
+    Utc,
+    /// A fixed offset from UTC
+    FixedOffset(FixedOffset),
+}
+
+impl TimeZoneMode {
+    // Convert a point in time into this time zone for rendering
+    pub(crate) fn convert(&self, now: &DateTime<Local>) -> DateTime<FixedOffset> {
+        match self {
+            TimeZoneMode::Local => now.fixed_offset(),
+            TimeZoneMode::Utc => now.with_timezone(&Utc).fixed_offset(),
+            TimeZoneMode::FixedOffset(offset) => now.with_timezone(offset),
+        }
+    }
+
+    // The point in time a wall clock reading in this time zone stands for, the earlier one where the clocks go back.
+    // None for a reading skipped when the clocks go forward
+    pub(crate) fn resolve(&self, naive: &NaiveDateTime) -> Option<DateTime<FixedOffset>> {
+        match self {
+            TimeZoneMode::Local => Local.from_local_datetime(naive).earliest().map(|date| date.fixed_offset()),
+            TimeZoneMode::Utc => Some(Utc.from_utc_datetime(naive).fixed_offset()),
+            TimeZoneMode::FixedOffset(offset) => offset.from_local_datetime(naive).single(),
+        }
+    }
+}
+
+/// Precision of the fractional seconds appended to line timestamps
+/// # Example
+/// ```rust
+/// use d_logger::{Logger, SubsecPrecision};
+/// 
+/// let path = std::env::temp_dir().join("d_logger_subsec");
+/// let logger = Logger::builder()
+///     .path(&path)
+///     .file_name_format("subsec_%Y%m%d.log")
+///     .subsec_precision(Some(SubsecPrecision::Millis))
+///     .build()
+///     .unwrap();
+/// logger.write_log("with milliseconds");
+/// 
+/// let contents = std::fs::read_to_string(path.join(chrono::Local::now().format("subsec_%Y%m%d.log").to_string())).unwrap();
+/// let line = contents.lines().last().unwrap();
+/// assert!(chrono::NaiveDateTime::parse_from_str(&line[..23], "%Y-%m-%d %H:%M:%S%.3f").is_ok());
+/// assert_eq!(&line[23..], " with milliseconds");
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SubsecPrecision {
+    /// Three digits, e.g. .123
+    Millis,
+    /// Six digits, e.g. .123456
+    Micros,
+    /// Nine digits, e.g. .123456789
+    Nanos,
+}
+
+impl SubsecPrecision {
+    // The chrono specifier for this precision
+    pub(crate) fn specifier(&self) -> &'static str {
+        match self {
+            SubsecPrecision::Millis => "%.3f",
+            SubsecPrecision::Micros => "%.6f",
+            SubsecPrecision::Nanos => "%.9f",
+        }
+    }
+}
+
+// Whether a date format already renders fractional seconds itself
+pub(crate) fn has_subsec(format: &str) -> bool {
+    ["%.f", "%.3f", "%.6f", "%.9f", "%3f", "%6f", "%9f", "%f"].iter().any(|s| format.contains(s))
+}
+
+// A whole number followed by a unit, one of ms, s, m, h or d, e.g. "250ms" or "5m"
+pub(crate) fn parse_duration(text: &str) -> Option<Duration> {
+    let text = text.trim().to_ascii_lowercase();
+    let (number, unit) = text.split_at(text.find(|c: char| !c.is_ascii_digit())?);
+    let number: u64 = number.parse().ok()?;
+    let secs = match unit.trim() {
+        "ms" => return Some(Duration::from_millis(number)),
+        "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        _ => return None,
+    };
+    number.checked_mul(secs).map(Duration::from_secs)
+}
+
+// The other way round, in the largest unit that keeps it whole. Anything under a millisecond is left off
+pub(crate) fn format_duration(duration: Duration) -> String {
+    let millis = duration.as_millis();
+    for (unit, size) in [("d", 86_400_000), ("h", 3_600_000), ("m", 60_000), ("s", 1000)] {
+        if millis > 0 && millis.is_multiple_of(size) {
+            return format!("{}{unit}", millis / size);
+        }
+    }
+    format!("{millis}ms")
+}
+
+// The last file name rendered from a format and the stretch of time it holds for,
+// so writes only render the name again once the time moves on past it
+#[derive(Default)]
+pub(crate) struct NameCache {
+    last: Mutex<Option<((i64, i32), PathBuf)>>,
+}
+
+impl NameCache {
+    // The dated file for a moment, rendered afresh only when the moment is outside the last one's period
+    pub(crate) fn get(&self, dir: &Path, now: &DateTime<FixedOffset>, format: &str) -> Result<PathBuf, LoggerError> {
+        let Some(period) = name_period(format) else {
+            return Ok(dir.join(render_date(now, format, "file_name_format")?));
+        };
+        // Local time counted in whole periods, with no gaps or repeats so every day is the same length.
+        // The offset is part of the key, so a name is rendered again if the zone changes
+        let key = (now.naive_local().and_utc().timestamp().div_euclid(period), now.offset().local_minus_utc());
+        let mut last = self.last.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some((cached, name)) = last.as_ref()
+            && *cached == key {
+            return Ok(name.clone());
+        }
+        let name = dir.join(render_date(now, format, "file_name_format")?);
+        *last = Some((key, name.clone()));
+        Ok(name)
+    }
+
+    // Forget the last name, for when the format changes
+    pub(crate) fn clear(&self) {
+        *self.last.lock().unwrap_or_else(|e| e.into_inner()) = None;
+    }
+}
+
+// How long a name rendered from this format stays the same, in seconds: a day unless it holds the time of day.
+// None if it can't be known, for fractional seconds or anything not listed here, and the name is rendered every time
+fn name_period(format: &str) -> Option<i64> {
+    let mut period = SECS_1_DAY as i64;
+    let mut chars = format.chars();
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            continue;
+        }
+        let mut spec = chars.next()?;
+        // Padding flags, and the colons in "%:z"
+        while matches!(spec, '-' | '_' | '0' | '#' | ':') {
+            spec = chars.next()?;
+        }
+        let spec_period = match spec {
+            '%' | 'n' | 't' | 'Y' | 'C' | 'y' | 'G' | 'g' | 'm' | 'b' | 'B' | 'h' | 'd' | 'e' | 'a' | 'A' | 'w' | 'u'
+            | 'U' | 'W' | 'V' | 'j' | 'D' | 'x' | 'F' | 'v' | 'z' | 'Z' => SECS_1_DAY as i64,
+            'H' | 'k' | 'I' | 'l' | 'p' | 'P' => 3600,
+            'M' | 'R' => 60,
+            'S' | 'T' | 'X' | 'r' | 'c' | 's' => 1,
+            _ => return None,
+        };
+        period = period.min(spec_period);
+    }
+    Some(period)
+}
@@ -0,0 +1,77 @@
+use std::{io, sync::Arc};
+
+use crate::{clean::clean_directory, CleanReport, Logger, LoggerError};
+
+impl Logger {
+    /// Write a line to the log without blocking the async runtime, the line is written on tokio's blocking thread pool.
+    /// It goes through the same path as `write_log`: the shared file handle, rotation, the fallback directory,
+    /// the rate limit and dedup checks, the console echo and the sinks
+    /// # Arguments
+    /// * `line` - The line to write to the log
+    /// # Example
+    /// ```rust
+    /// use d_logger::Logger;
+    /// use std::sync::Arc;
+    /// 
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// let path = std::env::temp_dir().join("d_logger_write_log_async");
+    /// let logger = Arc::new(Logger::builder().path(&path).file_name_format("async_%Y%m%d.log").build().unwrap());
+    /// let file = path.join(chrono::Local::now().format("async_%Y%m%d.log").to_string());
+    /// # let _ = std::fs::remove_file(&file);
+    /// 
+    /// let mut tasks = Vec::new();
+    /// for t in 0..8 {
+    ///     let logger = Arc::clone(&logger);
+    ///     tasks.push(tokio::spawn(async move {
+    ///         for i in 0..50 {
+    ///             logger.write_log_async(&format!("task {t} line {i}")).await.unwrap();
+    ///         }
+    ///     }));
+    /// }
+    /// for task in tasks {
+    ///     task.await.unwrap();
+    /// }
+    /// 
+    /// let contents = std::fs::read_to_string(&file).unwrap();
+    /// assert_eq!(contents.lines().count(), 8 * 50);
+    /// for t in 0..8 {
+    ///     assert!(contents.lines().any(|l| l.ends_with(&format!(" task {t} line 49"))));
+    /// }
+    /// # }
+    /// ```
+    /// # Returns
+    /// * `Ok(())` if the log was written successfully
+    /// * `Err(LoggerError)` as for `try_write_log`
+    /// * `Err(LoggerError::Write)` if the blocking task panicked or was cancelled
+    pub async fn write_log_async(&self, line: &str) -> Result<(), LoggerError> {
+        if self.is_disabled() {
+            return Ok(());
+        }
+        let line = line.to_string();
+        let inner = Arc::clone(&self.inner);
+        tokio::task::spawn_blocking(move || inner.write_entry(None, &line, &[]))
+            .await
+            .map_err(|e| LoggerError::Write(io::Error::other(e)))?
+    }
+
+    /// Clean up the log path like `log_clean` without blocking the async runtime, the directory is cleaned on tokio's blocking thread pool
+    /// # Arguments
+    /// * `filter` - Optional regex to filter by name, None to delete any file older than date
+    /// # Returns
+    /// * `Ok(CleanReport)` what was deleted and what was left
+    /// * `Err(LoggerError::InvalidFilter)` if the regex filter did not compile
+    /// * `Err(LoggerError::DirectoryRead)` if the log directory could not be read, or the blocking task panicked or was cancelled
+    pub async fn log_clean_async(&self, filter: Option<&str>) -> Result<CleanReport, LoggerError> {
+        if self.is_disabled() {
+            return Ok(CleanReport::default());
+        }
+        let settings = self.inner.clean_settings(filter)?;
+        let path = self.inner.path.clone();
+        let report = tokio::task::spawn_blocking(move || clean_directory(&path, &settings))
+            .await
+            .map_err(|e| LoggerError::DirectoryRead(io::Error::other(e)))??;
+        self.inner.stats.cleaned(report.deleted.len());
+        Ok(report)
+    }
+}
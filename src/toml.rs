@@ -0,0 +1,212 @@
+// Reads the small part of TOML a LoggerConfig needs, so config files don't need a TOML crate: bare or quoted keys,
+// basic and literal strings, whole numbers, booleans, inline tables and [table] headers one level deep.
+// Arrays, floats, dates, dotted keys and multi-line strings are refused with the line they're on
+
+use serde_json::{Map, Value};
+
+// The document as the same values serde_json would give for it, so LoggerConfig is read the same way from both
+pub(crate) fn parse(text: &str) -> Result<Value, String> {
+    let mut root = Map::new();
+    let mut table: Option<String> = None;
+    for (number, line) in text.lines().enumerate() {
+        parse_line(line, &mut root, &mut table).map_err(|e| format!("line {} | {e}", number + 1))?;
+    }
+    Ok(Value::Object(root))
+}
+
+fn parse_line(line: &str, root: &mut Map<String, Value>, table: &mut Option<String>) -> Result<(), String> {
+    let mut cursor = Cursor { rest: line };
+    cursor.skip_space();
+    if cursor.at_end() {
+        return Ok(());
+    }
+    if cursor.eat('[') {
+        if cursor.eat('[') {
+            return Err("arrays of tables aren't supported".to_string());
+        }
+        cursor.skip_space();
+        let name = cursor.key()?;
+        cursor.skip_space();
+        if !cursor.eat(']') {
+            return Err(format!("expected `]` after the table name `{name}`"));
+        }
+        cursor.end()?;
+        if root.contains_key(&name) {
+            return Err(format!("`{name}` is set twice"));
+        }
+        root.insert(name.clone(), Value::Object(Map::new()));
+        *table = Some(name);
+        return Ok(());
+    }
+    let (key, value) = cursor.pair()?;
+    cursor.end()?;
+    let target = match table {
+        Some(name) => match root.get_mut(name.as_str()) {
+            Some(Value::Object(map)) => map,
+            _ => unreachable!("the table is added when its header is read"),
+        },
+        None => root,
+    };
+    insert(target, key, value)
+}
+
+fn insert(map: &mut Map<String, Value>, key: String, value: Value) -> Result<(), String> {
+    if map.contains_key(&key) {
+        return Err(format!("`{key}` is set twice"));
+    }
+    map.insert(key, value);
+    Ok(())
+}
+
+struct Cursor<'a> {
+    rest: &'a str,
+}
+
+impl Cursor<'_> {
+    fn at_end(&self) -> bool {
+        self.rest.is_empty() || self.rest.starts_with('#')
+    }
+
+    fn skip_space(&mut self) {
+        self.rest = self.rest.trim_start_matches([' ', '\t']);
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        match self.rest.strip_prefix(c) {
+            Some(rest) => {
+                self.rest = rest;
+                true
+            }
+            None => false,
+        }
+    }
+
+    // Only spaces and a comment may follow
+    fn end(&mut self) -> Result<(), String> {
+        self.skip_space();
+        if self.at_end() {
+            Ok(())
+        } else {
+            Err(format!("expected the end of the line, found `{}`", self.rest))
+        }
+    }
+
+    fn pair(&mut self) -> Result<(String, Value), String> {
+        let key = self.key()?;
+        self.skip_space();
+        if self.rest.starts_with('.') {
+            return Err(format!("dotted keys aren't supported, put `{key}` in a [table] instead"));
+        }
+        if !self.eat('=') {
+            return Err(format!("expected `=` after `{key}`"));
+        }
+        self.skip_space();
+        Ok((key, self.value()?))
+    }
+
+    fn key(&mut self) -> Result<String, String> {
+        if self.eat('"') {
+            return self.basic_string();
+        }
+        if self.eat('\'') {
+            return self.literal_string();
+        }
+        let len = self.rest.find(|c: char| !(c.is_ascii_alphanumeric() || c == '_' || c == '-')).unwrap_or(self.rest.len());
+        if len == 0 {
+            return Err(format!("expected a key, found `{}`", self.rest));
+        }
+        let (key, rest) = self.rest.split_at(len);
+        self.rest = rest;
+        Ok(key.to_string())
+    }
+
+    fn value(&mut self) -> Result<Value, String> {
+        if self.rest.starts_with("\"\"\"") || self.rest.starts_with("'''") {
+            return Err("multi-line strings aren't supported".to_string());
+        }
+        if self.eat('"') {
+            return self.basic_string().map(Value::String);
+        }
+        if self.eat('\'') {
+            return self.literal_string().map(Value::String);
+        }
+        if self.eat('{') {
+            return self.inline_table();
+        }
+        if self.rest.starts_with('[') {
+            return Err("arrays aren't supported".to_string());
+        }
+        let len = self.rest.find(|c: char| !(c.is_ascii_alphanumeric() || "_+-.:".contains(c))).unwrap_or(self.rest.len());
+        let (word, rest) = self.rest.split_at(len);
+        self.rest = rest;
+        match word {
+            "true" => Ok(Value::Bool(true)),
+            "false" => Ok(Value::Bool(false)),
+            "" => Err(format!("expected a value, found `{}`", self.rest)),
+            _ => word.replace('_', "").parse::<i64>()
+                .map(Value::from)
+                .map_err(|_| format!("`{word}` isn't a string, whole number, true, false or inline table")),
+        }
+    }
+
+    // After the opening quote, escapes are read as TOML has them
+    fn basic_string(&mut self) -> Result<String, String> {
+        let mut text = String::new();
+        let mut chars = self.rest.char_indices();
+        while let Some((i, c)) = chars.next() {
+            match c {
+                '"' => {
+                    self.rest = &self.rest[i + 1..];
+                    return Ok(text);
+                }
+                '\\' => match chars.next().map(|(_, c)| c) {
+                    Some('"') => text.push('"'),
+                    Some('\\') => text.push('\\'),
+                    Some('b') => text.push('\u{8}'),
+                    Some('t') => text.push('\t'),
+                    Some('n') => text.push('\n'),
+                    Some('f') => text.push('\u{c}'),
+                    Some('r') => text.push('\r'),
+                    Some(kind @ ('u' | 'U')) => {
+                        let digits: String = chars.by_ref().take(if kind == 'u' { 4 } else { 8 }).map(|(_, c)| c).collect();
+                        let escaped = u32::from_str_radix(&digits, 16).ok().and_then(char::from_u32);
+                        text.push(escaped.ok_or_else(|| format!("`\\{kind}{digits}` isn't a character"))?);
+                    }
+                    Some(other) => return Err(format!("`\\{other}` isn't an escape")),
+                    None => break,
+                },
+                _ => text.push(c),
+            }
+        }
+        Err("the string has no closing quote".to_string())
+    }
+
+    // After the opening quote, taken as it is
+    fn literal_string(&mut self) -> Result<String, String> {
+        let end = self.rest.find('\'').ok_or_else(|| "the string has no closing quote".to_string())?;
+        let text = self.rest[..end].to_string();
+        self.rest = &self.rest[end + 1..];
+        Ok(text)
+    }
+
+    // After the opening brace, on one line as TOML requires
+    fn inline_table(&mut self) -> Result<Value, String> {
+        let mut map = Map::new();
+        self.skip_space();
+        if self.eat('}') {
+            return Ok(Value::Object(map));
+        }
+        loop {
+            self.skip_space();
+            let (key, value) = self.pair()?;
+            insert(&mut map, key, value)?;
+            self.skip_space();
+            if self.eat('}') {
+                return Ok(Value::Object(map));
+            }
+            if !self.eat(',') {
+                return Err("expected `,` or `}` in the inline table".to_string());
+            }
+        }
+    }
+}
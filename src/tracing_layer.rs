@@ -0,0 +1,128 @@
+use std::fmt::{self, Write};
+use tracing::{field::{Field, Visit}, span, Event, Subscriber};
+use tracing_subscriber::{layer::Context, registry::LookupSpan, Layer};
+use crate::{Level, Logger};
+
+impl From<tracing::Level> for Level {
+    fn from(level: tracing::Level) -> Self {
+        match level {
+            tracing::Level::TRACE => Level::Trace,
+            tracing::Level::DEBUG => Level::Debug,
+            tracing::Level::INFO => Level::Info,
+            tracing::Level::WARN => Level::Warn,
+            tracing::Level::ERROR => Level::Error,
+        }
+    }
+}
+
+/// A `tracing_subscriber` layer that writes events to a `Logger`.
+/// Events are written as "<time> [LEVEL] <target>: <message> key=value ...", optionally followed by the fields of the spans the event is in.
+/// Only available with the `tracing` feature.
+/// # Example
+/// ```rust
+/// use d_logger::{DLoggerLayer, Logger};
+/// use tracing_subscriber::prelude::*;
+/// 
+/// let path = std::env::temp_dir().join("d_logger_tracing");
+/// let logger = Logger::builder().path(&path).file_name_format("tracing_%Y%m%d.log").build().unwrap();
+/// # let file = path.join(chrono::Local::now().format("tracing_%Y%m%d.log").to_string());
+/// # let _ = std::fs::remove_file(&file);
+/// let subscriber = tracing_subscriber::registry().with(DLoggerLayer::new(logger).with_span_fields(true));
+/// 
+/// tracing::subscriber::with_default(subscriber, || {
+///     let span = tracing::info_span!("request", id = 7);
+///     let _guard = span.enter();
+///     tracing::info!(target: "app", user = "bob", "logged in");
+///     tracing::warn!(target: "app", "slow response");
+///     tracing::error!(target: "app", code = 500, "request failed");
+/// });
+/// 
+/// let contents = std::fs::read_to_string(&file).unwrap();
+/// assert!(contents.contains(" [INFO] app: logged in user=bob request{id=7}"));
+/// assert!(contents.contains(" [WARN] app: slow response request{id=7}"));
+/// assert!(contents.contains(" [ERROR] app: request failed code=500 request{id=7}"));
+/// ```
+pub struct DLoggerLayer {
+    logger: Logger,
+    span_fields: bool,
+}
+
+impl DLoggerLayer {
+    /// Create a layer writing to the given logger, span fields are not included
+    pub fn new(logger: Logger) -> Self {
+        DLoggerLayer { logger, span_fields: false }
+    }
+
+    /// Append the fields of the spans an event is in, outermost first, e.g. "request{id=7}"
+    pub fn with_span_fields(mut self, span_fields: bool) -> Self {
+        self.span_fields = span_fields;
+        self
+    }
+}
+
+// Collects the message and flattens the other fields into "key=value" pairs
+#[derive(Default)]
+struct FieldVisitor {
+    message: String,
+    fields: String,
+}
+
+impl Visit for FieldVisitor {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == "message" {
+            self.message = value.to_string();
+        } else {
+            let _ = write!(self.fields, " {}={}", field.name(), value);
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        if field.name() == "message" {
+            self.message = format!("{value:?}");
+        } else {
+            let _ = write!(self.fields, " {}={:?}", field.name(), value);
+        }
+    }
+}
+
+// Formatted fields of a span, stored in the span's extensions when it's created
+struct SpanFields(String);
+
+impl<S> Layer<S> for DLoggerLayer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_new_span(&self, attrs: &span::Attributes<'_>, id: &span::Id, ctx: Context<'_, S>) {
+        if !self.span_fields {
+            return;
+        }
+        if let Some(span) = ctx.span(id) {
+            let mut visitor = FieldVisitor::default();
+            attrs.record(&mut visitor);
+            span.extensions_mut().insert(SpanFields(visitor.fields.trim_start().to_string()));
+        }
+    }
+
+    fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+        let metadata = event.metadata();
+        let level = Level::from(*metadata.level());
+        if level < self.logger.level() {
+            return;
+        }
+
+        let mut visitor = FieldVisitor::default();
+        event.record(&mut visitor);
+        let mut line = format!("{}: {}{}", metadata.target(), visitor.message, visitor.fields);
+
+        if self.span_fields
+            && let Some(scope) = ctx.event_scope(event) {
+            for span in scope.from_root() {
+                let extensions = span.extensions();
+                let fields = extensions.get::<SpanFields>().map(|f| f.0.as_str()).unwrap_or("");
+                let _ = write!(line, " {}{{{}}}", span.name(), fields);
+            }
+        }
+
+        self.logger.write_level(level, &line);
+    }
+}
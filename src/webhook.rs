@@ -0,0 +1,156 @@
+use std::{
+    io,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        mpsc::{self, SyncSender, TrySendError},
+        Arc,
+    },
+    thread::JoinHandle,
+    time::Duration,
+};
+
+use crate::{sink::Record, Level, Sink};
+
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(5);
+const DEFAULT_QUEUE: usize = 100;
+
+/// POSTs a JSON payload to a webhook for each entry at or above a level, e.g. errors to Slack, Teams or incident tooling.
+/// The payload is `{"text": "[ERROR] message", "ts": "<RFC 3339>", "level": "ERROR", "msg": "message", "fields": {...}}`,
+/// `text` being what chat webhooks show and `fields` the key value pairs from `write_log_kv`.
+///
+/// Requests are made on the sink's own thread, so writing only queues the entry and never waits on the network.
+/// Up to 100 entries can wait to be delivered, past that they are dropped. Dropped entries, failed requests and
+/// responses other than 2xx are all counted by `failures`, they never make the file write fail.
+/// Dropping the sink delivers anything still queued first. Only with the `http` feature
+/// # Example
+/// ```rust
+/// use d_logger::{Level, Logger, WebhookSink};
+/// use std::io::{BufRead, BufReader, Read, Write};
+/// use std::net::TcpListener;
+/// 
+/// // A tiny HTTP server that accepts one request and keeps its body
+/// let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+/// let url = format!("http://{}/alerts", listener.local_addr().unwrap());
+/// let server = std::thread::spawn(move || {
+///     let (mut stream, _) = listener.accept().unwrap();
+///     let mut reader = BufReader::new(stream.try_clone().unwrap());
+///     let mut length = 0;
+///     loop {
+///         let mut header = String::new();
+///         reader.read_line(&mut header).unwrap();
+///         if header.trim().is_empty() {
+///             break;
+///         }
+///         if let Some(value) = header.to_ascii_lowercase().strip_prefix("content-length:") {
+///             length = value.trim().parse().unwrap();
+///         }
+///     }
+///     let mut body = vec![0; length];
+///     reader.read_exact(&mut body).unwrap();
+///     stream.write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\nConnection: close\r\n\r\n").unwrap();
+///     String::from_utf8(body).unwrap()
+/// });
+/// 
+/// let path = std::env::temp_dir().join("d_logger_webhook");
+/// let logger = Logger::builder().path(&path).build().unwrap();
+/// let sink = WebhookSink::new(&url, Level::Error);
+/// let failures = sink.failures();
+/// logger.add_sink(Box::new(sink));
+/// 
+/// logger.warn("not important enough");
+/// logger.write_log_kv("ignored, no level", &[]);
+/// logger.error("database unreachable");
+/// let body: serde_json::Value = serde_json::from_str(&server.join().unwrap()).unwrap();
+/// assert_eq!(body["text"], "[ERROR] database unreachable");
+/// assert_eq!(body["level"], "ERROR");
+/// assert_eq!(body["msg"], "database unreachable");
+/// assert!(chrono::DateTime::parse_from_rfc3339(body["ts"].as_str().unwrap()).is_ok());
+/// drop(logger);
+/// assert_eq!(failures.load(std::sync::atomic::Ordering::Relaxed), 0);
+/// ```
+#[derive(Debug)]
+pub struct WebhookSink {
+    min_level: Level,
+    sender: Option<SyncSender<String>>,
+    delivery: Option<JoinHandle<()>>,
+    failures: Arc<AtomicU64>,
+}
+
+impl WebhookSink {
+    /// Start the delivery thread for a webhook, requests time out after 5 seconds
+    /// # Arguments
+    /// * `url` - Where to POST the payloads
+    /// * `min_level` - The lowest level sent, entries without a level are never sent
+    pub fn new(url: &str, min_level: Level) -> Self {
+        Self::with_timeout(url, min_level, DEFAULT_TIMEOUT)
+    }
+
+    /// As `new`, with a different limit on how long each request may take
+    pub fn with_timeout(url: &str, min_level: Level, timeout: Duration) -> Self {
+        let (sender, receiver) = mpsc::sync_channel::<String>(DEFAULT_QUEUE);
+        let failures = Arc::new(AtomicU64::new(0));
+        let agent = ureq::AgentBuilder::new().timeout(timeout).build();
+        let url = url.to_string();
+        let delivery_failures = Arc::clone(&failures);
+        let delivery = std::thread::Builder::new()
+            .name("d_logger-webhook".to_string())
+            .spawn(move || {
+                // Runs until the sink is dropped and the queue is empty
+                for payload in receiver {
+                    // Errors include any status outside 2xx
+                    if agent.post(&url).set("Content-Type", "application/json").send_string(&payload).is_err() {
+                        delivery_failures.fetch_add(1, Ordering::Relaxed);
+                    }
+                }
+            })
+            .expect("failed to spawn the d_logger webhook thread");
+        WebhookSink { min_level, sender: Some(sender), delivery: Some(delivery), failures }
+    }
+
+    /// A shared count of entries that couldn't be delivered, still readable once the sink has been given to the logger
+    pub fn failures(&self) -> Arc<AtomicU64> {
+        Arc::clone(&self.failures)
+    }
+}
+
+impl Sink for WebhookSink {
+    fn write_entry(&self, _formatted: &str) -> io::Result<()> {
+        // Without a level there's nothing to compare with the minimum, see write_record
+        Ok(())
+    }
+
+    fn write_record(&self, record: &Record) -> io::Result<()> {
+        let Some(level) = record.level.filter(|level| *level >= self.min_level) else {
+            return Ok(());
+        };
+        let fields: serde_json::Map<String, serde_json::Value> =
+            record.kvs.iter().map(|(key, value)| (key.to_string(), serde_json::Value::from(*value))).collect();
+        let payload = serde_json::json!({
+            "text": format!("[{level}] {}", record.message),
+            "ts": record.time.to_rfc3339(),
+            "level": level.as_str(),
+            "msg": record.message,
+            "fields": fields,
+        });
+        let Some(sender) = &self.sender else {
+            return Ok(());
+        };
+        match sender.try_send(payload.to_string()) {
+            Ok(()) => Ok(()),
+            Err(TrySendError::Full(_)) | Err(TrySendError::Disconnected(_)) => {
+                self.failures.fetch_add(1, Ordering::Relaxed);
+                Ok(())
+            }
+        }
+    }
+}
+
+impl Drop for WebhookSink {
+    fn drop(&mut self) {
+        // Closing the queue lets the thread finish what's left and stop
+        drop(self.sender.take());
+        if let Some(delivery) = self.delivery.take() {
+            let _ = delivery.join();
+        }
+    }
+}
@@ -0,0 +1,277 @@
+use std::{fmt, fs::{File, OpenOptions}, io::{self, BufWriter, Write}, path::{Path, PathBuf}, str::FromStr, time::{Duration, Instant}};
+use crate::{time, LoggerError};
+
+/// When written entries are flushed out of the logger and synced to disk.
+/// Syncing every line is the safest but slowest choice, the others trade durability for speed.
+/// Without a buffer (see `LoggerBuilder::buffer_capacity`) entries reach the file straight away and only the sync is delayed,
+/// with a buffer they are held until the policy says to flush. `Logger::flush` can always be called to force everything out.
+/// Syncing a file leaves its directory entry alone, `LoggerBuilder::sync_directory` syncs the directory for new files too
+/// # Example
+/// ```rust
+/// use d_logger::{Logger, SyncPolicy};
+/// 
+/// let path = std::env::temp_dir().join("d_logger_sync_policy");
+/// let logger = Logger::builder()
+///     .path(&path)
+///     .file_name_format("sync_%Y%m%d.log")
+///     .buffer_capacity(64 * 1024)
+///     .sync_policy(SyncPolicy::EveryN(10))
+///     .build()
+///     .unwrap();
+/// let file = path.join(chrono::Local::now().format("sync_%Y%m%d.log").to_string());
+/// # let _ = std::fs::remove_file(&file);
+/// let count = || std::fs::read_to_string(&file).map(|c| c.lines().count()).unwrap_or(0);
+/// 
+/// for i in 0..9 {
+///     logger.write_log(&format!("line {i}"));
+/// }
+/// assert_eq!(count(), 0);
+/// logger.write_log("line 9");
+/// assert_eq!(count(), 10);
+/// 
+/// // flush forces pending entries out early
+/// logger.write_log("line 10");
+/// assert_eq!(count(), 10);
+/// logger.flush().unwrap();
+/// assert_eq!(count(), 11);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SyncPolicy {
+    /// Flush and sync after every entry
+    #[default]
+    EveryLine,
+    /// Flush and sync after every n entries
+    EveryN(u32),
+    /// Flush and sync on the first write after this much time has passed since the last sync
+    Interval(Duration),
+    /// Only flush and sync when the logger is dropped, or when `flush` is called
+    OnDrop,
+    /// Never sync, entries are written out when the buffer fills, when `flush` is called and when the logger is dropped
+    Never,
+}
+
+impl fmt::Display for SyncPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SyncPolicy::EveryLine => f.write_str("every_line"),
+            SyncPolicy::EveryN(n) => write!(f, "every_n({n})"),
+            SyncPolicy::Interval(interval) => write!(f, "interval({})", time::format_duration(*interval)),
+            SyncPolicy::OnDrop => f.write_str("on_drop"),
+            SyncPolicy::Never => f.write_str("never"),
+        }
+    }
+}
+
+/// A sync policy by name in any case: `every_line`, `every_n(10)`, `interval(30s)`, `on_drop` or `never`.
+/// A duration on its own such as `5m` is an interval too. Durations are a whole number followed by ms, s, m, h or d
+/// # Example
+/// ```rust
+/// use d_logger::SyncPolicy;
+/// use std::time::Duration;
+/// 
+/// assert_eq!("EVERY_LINE".parse::<SyncPolicy>().unwrap(), SyncPolicy::EveryLine);
+/// assert_eq!("5m".parse::<SyncPolicy>().unwrap(), SyncPolicy::Interval(Duration::from_secs(300)));
+/// for policy in [
+///     SyncPolicy::EveryLine,
+///     SyncPolicy::EveryN(10),
+///     SyncPolicy::Interval(Duration::from_millis(250)),
+///     SyncPolicy::Interval(Duration::from_secs(2 * 3600)),
+///     SyncPolicy::OnDrop,
+///     SyncPolicy::Never,
+/// ] {
+///     assert_eq!(policy.to_string().parse::<SyncPolicy>().unwrap(), policy);
+/// }
+/// assert_eq!(SyncPolicy::Interval(Duration::from_secs(90)).to_string(), "interval(90s)");
+/// assert!("sometimes".parse::<SyncPolicy>().unwrap_err().to_string().contains("every_line, every_n(n)"));
+/// ```
+impl FromStr for SyncPolicy {
+    type Err = LoggerError;
+
+    fn from_str(s: &str) -> Result<SyncPolicy, LoggerError> {
+        let name = s.trim().to_ascii_lowercase();
+        let argument = |prefix: &str| name.strip_prefix(prefix).and_then(|rest| rest.strip_suffix(')')).map(str::trim);
+        let policy = match name.as_str() {
+            "every_line" => Some(SyncPolicy::EveryLine),
+            "on_drop" => Some(SyncPolicy::OnDrop),
+            "never" => Some(SyncPolicy::Never),
+            _ => match (argument("every_n("), argument("interval(")) {
+                (Some(n), _) => n.parse().ok().map(SyncPolicy::EveryN),
+                (_, Some(interval)) => time::parse_duration(interval).map(SyncPolicy::Interval),
+                _ => time::parse_duration(&name).map(SyncPolicy::Interval),
+            },
+        };
+        policy.ok_or_else(|| LoggerError::InvalidConfig(format!(
+            "\"{s}\" is not a sync policy, expected every_line, every_n(n), interval(30s), on_drop, never or a duration such as 5m"
+        )))
+    }
+}
+
+// The log file currently held open, along with the dated path it was opened for
+pub(crate) struct OpenFile {
+    pub(crate) path: PathBuf,
+    file: BufWriter<File>,
+    // Bytes in the file, including anything still buffered
+    pub(crate) size: u64,
+    unsynced: u32,
+    last_sync: Instant,
+    // Which file on disk the handle points at, to spot it being renamed or deleted
+    identity: Option<(u64, u64)>,
+    last_check: Instant,
+    // Hold an exclusive lock on the file while writing to it
+    lock: bool,
+    // Whether opening it made the file
+    pub(crate) created: bool,
+}
+
+// How often a cached handle is checked against the path it was opened for
+const MOVED_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+// Device and inode of the file on Unix
+#[cfg(unix)]
+fn identity(metadata: &std::fs::Metadata) -> Option<(u64, u64)> {
+    use std::os::unix::fs::MetadataExt;
+    Some((metadata.dev(), metadata.ino()))
+}
+
+// Other platforms can only tell that the path has gone
+#[cfg(not(unix))]
+fn identity(_metadata: &std::fs::Metadata) -> Option<(u64, u64)> {
+    None
+}
+
+// Open a log file for appending, creating it if needed.
+// A file the open creates gets exactly the mode given, if any, whatever the umask. Files already there are left alone
+pub(crate) fn open_append(path: &Path, mode: Option<u32>) -> io::Result<File> {
+    open_or_create(path, mode).map(|(file, _)| file)
+}
+
+// As open_append, also giving back whether the file was created by this open
+fn open_or_create(path: &Path, mode: Option<u32>) -> io::Result<(File, bool)> {
+    let mut options = OpenOptions::new();
+    options.append(true).create_new(true);
+    #[cfg(unix)]
+    if let Some(mode) = mode {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(mode);
+    }
+    match options.open(path) {
+        Ok(file) => {
+            #[cfg(unix)]
+            if let Some(mode) = mode {
+                use std::os::unix::fs::PermissionsExt;
+                file.set_permissions(std::fs::Permissions::from_mode(mode))?;
+            }
+            #[cfg(not(unix))]
+            let _ = mode;
+            Ok((file, true))
+        }
+        // Made by someone else in between counts as already there
+        Err(e) if e.kind() == io::ErrorKind::AlreadyExists => OpenOptions::new().append(true).create(true).open(path).map(|file| (file, false)),
+        Err(e) => Err(e),
+    }
+}
+
+// Sync the directory holding a file, so a file just created there survives a power cut. Windows can't sync a directory
+#[cfg(unix)]
+pub(crate) fn sync_dir(file: &Path) -> io::Result<()> {
+    let dir = match file.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    File::open(dir)?.sync_all()
+}
+
+#[cfg(not(unix))]
+pub(crate) fn sync_dir(_file: &Path) -> io::Result<()> {
+    Ok(())
+}
+
+impl OpenFile {
+    // Open a log file for appending, creating it if needed.
+    // A zero capacity buffer passes writes straight through to the file
+    pub(crate) fn open(path: PathBuf, buffer_capacity: usize, lock: bool, mode: Option<u32>) -> Result<OpenFile, LoggerError> {
+        match open_or_create(&path, mode) {
+            Ok((file, created)) => {
+                let metadata = file.metadata().ok();
+                Ok(OpenFile {
+                    path,
+                    size: metadata.as_ref().map_or(0, |m| m.len()),
+                    identity: metadata.as_ref().and_then(identity),
+                    file: BufWriter::with_capacity(buffer_capacity, file),
+                    unsynced: 0,
+                    last_sync: Instant::now(),
+                    last_check: Instant::now(),
+                    lock,
+                    created,
+                })
+            }
+            Err(e) => Err(LoggerError::FileOpen { path, source: e }),
+        }
+    }
+
+    pub(crate) fn is_for(&self, path: &Path) -> bool {
+        self.path == path
+    }
+
+    // Whether the file has been renamed or deleted since it was opened, e.g. by logrotate.
+    // Only looks at the disk once a second, in between it assumes nothing has changed
+    pub(crate) fn moved(&mut self) -> bool {
+        if self.last_check.elapsed() < MOVED_CHECK_INTERVAL {
+            return false;
+        }
+        self.last_check = Instant::now();
+        match std::fs::metadata(&self.path) {
+            Ok(metadata) => self.identity.is_some_and(|opened| Some(opened) != identity(&metadata)),
+            Err(_) => true,
+        }
+    }
+
+    // Write one or more complete entries in one go, counting each of them towards the sync policy,
+    // then flush and sync them if the policy says it's time
+    pub(crate) fn write_entries(&mut self, entries: &[u8], count: u32, policy: SyncPolicy) -> Result<(), LoggerError> {
+        if self.lock {
+            self.write_locked(entries)?;
+        } else {
+            self.file.write_all(entries).map_err(LoggerError::Write)?;
+            self.size += entries.len() as u64;
+        }
+        self.unsynced = self.unsynced.saturating_add(count);
+
+        let due = match policy {
+            SyncPolicy::EveryLine => true,
+            SyncPolicy::EveryN(n) => self.unsynced >= n,
+            SyncPolicy::Interval(interval) => self.last_sync.elapsed() >= interval,
+            SyncPolicy::OnDrop | SyncPolicy::Never => false,
+        };
+        if due {
+            self.sync()?;
+        }
+        Ok(())
+    }
+
+    // Write entries out in full while holding the file lock, so another process's write can't land in the middle.
+    // Nothing is left in the buffer, and the size is read back as other processes grow the file too
+    fn write_locked(&mut self, entries: &[u8]) -> Result<(), LoggerError> {
+        self.file.get_ref().lock().map_err(LoggerError::FileLock)?;
+        let written = self.file.write_all(entries).and_then(|_| self.file.flush()).map_err(LoggerError::Write);
+        if let Ok(metadata) = self.file.get_ref().metadata() {
+            self.size = metadata.len();
+        }
+        let unlocked = self.file.get_ref().unlock().map_err(LoggerError::FileLock);
+        written.and(unlocked)
+    }
+
+    // Flush anything buffered and sync the file to disk
+    pub(crate) fn sync(&mut self) -> Result<(), LoggerError> {
+        self.file.flush().map_err(LoggerError::Flush)?;
+        self.file.get_ref().sync_all().map_err(LoggerError::Flush)?;
+        self.unsynced = 0;
+        self.last_sync = Instant::now();
+        Ok(())
+    }
+
+    // Flush anything buffered without syncing
+    pub(crate) fn flush(&mut self) -> Result<(), LoggerError> {
+        self.file.flush().map_err(LoggerError::Flush)
+    }
+}